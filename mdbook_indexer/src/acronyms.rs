@@ -0,0 +1,171 @@
+use crate::extract::parser;
+use crate::generate::escape_html;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+/// Shortest and longest acronym, in characters.
+const ACRONYM_LEN: Range<usize> = 2..9;
+
+/// Whether `word` looks like an acronym: mostly capitals, like `HTML` or
+/// `MP3`, starting with one.
+fn is_acronym(word: &str) -> bool {
+    ACRONYM_LEN.contains(&word.len())
+        && word.starts_with(|c: char| c.is_ascii_uppercase())
+        && word
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && word.chars().filter(char::is_ascii_uppercase).count() >= 2
+}
+
+/// The words of `text` made of letters and digits, with where they start.
+fn words(text: &str) -> Vec<(usize, &str)> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (at, c) in text.char_indices().chain([(text.len(), ' ')]) {
+        match (start, c.is_alphanumeric()) {
+            (None, true) => start = Some(at),
+            (Some(from), false) => {
+                words.push((from, &text[from..at]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    words
+}
+
+/// Whether the initials of `words` spell the letters of `acronym`.
+fn spells(acronym: &str, words: &[(usize, &str)]) -> bool {
+    let letters: Vec<char> = acronym.chars().filter(char::is_ascii_uppercase).collect();
+    letters.len() == words.len()
+        && letters.iter().zip(words).all(|(&letter, (_, word))| {
+            word.chars()
+                .next()
+                .is_some_and(|c| c.to_ascii_uppercase() == letter)
+        })
+}
+
+/// The acronyms `text` defines, as in `ABC (Always Be Coding)` or
+/// `Always Be Coding (ABC)`, with the range of the acronym in `text`.
+fn definitions(text: &str) -> Vec<(Range<usize>, String, String)> {
+    let mut found = Vec::new();
+    for (start, word) in words(text).into_iter().filter(|(_, word)| is_acronym(word)) {
+        let end = start + word.len();
+        let (before, after) = (&text[..start], &text[end..]);
+        // `ABC (Always Be Coding)`
+        if let Some((inside, _)) = after
+            .strip_prefix(" (")
+            .and_then(|rest| rest.split_once(')'))
+        {
+            if spells(word, &words(inside)) {
+                found.push((start..end, word.to_string(), inside.trim().to_string()));
+                continue;
+            }
+        }
+        // `Always Be Coding (ABC)`
+        if let (Some(before), true) = (before.strip_suffix('('), after.starts_with(')')) {
+            let preceding = words(before);
+            let count = word.chars().filter(char::is_ascii_uppercase).count();
+            let expansion = &preceding[preceding.len().saturating_sub(count)..];
+            if spells(word, expansion) {
+                let from = expansion[0].0;
+                found.push((
+                    start..end,
+                    word.to_string(),
+                    before[from..].trim().to_string(),
+                ));
+            }
+        }
+    }
+    found
+}
+
+/// The ranges of the plain text of `content`: no code, HTML, headings,
+/// links or images, nor what an `<abbr>` already wraps. Wrapped text comes
+/// separately, as it still counts as a use.
+fn plain_text(content: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    let mut texts = Vec::new();
+    let mut wrapped = Vec::new();
+    let mut skipped = 0;
+    let mut in_abbr = false;
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(
+                Tag::Heading { .. }
+                | Tag::Link { .. }
+                | Tag::Image { .. }
+                | Tag::CodeBlock(_)
+                | Tag::MetadataBlock(_),
+            ) => skipped += 1,
+            Event::End(
+                TagEnd::Heading(_)
+                | TagEnd::Link
+                | TagEnd::Image
+                | TagEnd::CodeBlock
+                | TagEnd::MetadataBlock(_),
+            ) => skipped -= 1,
+            Event::InlineHtml(html) if html.starts_with("<abbr") => in_abbr = true,
+            Event::InlineHtml(html) if html.starts_with("</abbr") => in_abbr = false,
+            Event::Text(_) if in_abbr => wrapped.push(range),
+            Event::Text(_) if skipped == 0 => texts.push(range),
+            _ => {}
+        }
+    }
+    (texts, wrapped)
+}
+
+/// The acronyms defined in the plain text of `content`, each with what it
+/// stands for.
+pub(crate) fn defined(content: &str) -> Vec<(String, String)> {
+    let (texts, _) = plain_text(content);
+    texts
+        .into_iter()
+        .flat_map(|text| definitions(&content[text]))
+        .map(|(_, acronym, expansion)| (acronym, expansion))
+        .collect()
+}
+
+/// `content` with the occurrences of `acronyms` in plain text wrapped in an
+/// `<abbr>` giving what they stand for if `wrap` is on, all but where they
+/// are defined, and the acronyms it uses.
+pub(crate) fn expand(
+    content: &str,
+    acronyms: &BTreeMap<String, String>,
+    wrap: bool,
+) -> (String, BTreeSet<String>) {
+    let (texts, wrapped) = plain_text(content);
+    let mut used: BTreeSet<String> = wrapped
+        .into_iter()
+        .map(|range| &content[range])
+        .filter(|text| acronyms.contains_key(*text))
+        .map(str::to_string)
+        .collect();
+
+    let mut expanded = String::with_capacity(content.len());
+    let mut last = 0;
+    for text in texts {
+        let definitions: Vec<Range<usize>> = definitions(&content[text.clone()])
+            .into_iter()
+            .map(|(range, _, _)| range)
+            .collect();
+        for (start, word) in words(&content[text.clone()]) {
+            let Some(expansion) = acronyms.get(word) else {
+                continue;
+            };
+            used.insert(word.to_string());
+            if !wrap || definitions.iter().any(|range| range.start == start) {
+                continue;
+            }
+            let start = text.start + start;
+            expanded.push_str(&content[last..start]);
+            expanded.push_str(&format!(
+                "<abbr title=\"{}\">{word}</abbr>",
+                escape_html(expansion)
+            ));
+            last = start + word.len();
+        }
+    }
+    expanded.push_str(&content[last..]);
+    (expanded, used)
+}
@@ -0,0 +1,89 @@
+use crate::extract::parser;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use regex::Regex;
+use std::ops::Range;
+
+/// What `commit_pattern` matches by default: hashes marked as commits, like
+/// `commit:3f2a9c1`.
+pub(crate) const COMMIT_PATTERN: &str = r"(?i)\bcommit:([0-9a-f]{7,40})\b";
+
+/// What `ticket_pattern` matches by default: keys like `PROJ-1234`.
+pub(crate) const TICKET_PATTERN: &str = r"\b[A-Z][A-Z0-9]+-[0-9]+\b";
+
+/// Where `pattern` matches `content` outside code, HTML and links, with
+/// what it captures first, or else the whole match. Matches right after one
+/// of `prefixes` belong to a token, like `#PROJ-12`, and are left out.
+pub(crate) fn find(
+    content: &str,
+    pattern: &Regex,
+    prefixes: &[char],
+) -> Vec<(Range<usize>, String)> {
+    let skipped: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Link { .. } | Tag::Image { .. })
+            | Event::Code(_)
+            | Event::Html(_)
+            | Event::InlineHtml(_) => Some(range),
+            _ => None,
+        })
+        .collect();
+    pattern
+        .captures_iter(content)
+        .filter_map(|captures| {
+            let whole = captures.get(0)?;
+            let prefixed = content[..whole.start()].ends_with(prefixes);
+            if prefixed || skipped.iter().any(|r| r.contains(&whole.start())) {
+                return None;
+            }
+            let key = captures.get(1).unwrap_or(whole).as_str().to_string();
+            Some((whole.range(), key))
+        })
+        .collect()
+}
+
+/// The keys of the links of `content` an earlier run made, those showing a
+/// key and leading where `url` says it is.
+pub(crate) fn linked(content: &str, url: impl Fn(&str) -> String) -> Vec<String> {
+    let mut keys = Vec::new();
+    let mut open: Option<(String, String)> = None;
+    for event in parser(content) {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                open = Some((dest_url.to_string(), String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, key)) = open.as_mut() {
+                    key.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Link) => {
+                if let Some((dest, key)) = open.take() {
+                    if !key.is_empty() && url(&key) == dest {
+                        keys.push(key);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    keys
+}
+
+/// `content` with each match of `found` replaced by a link showing its key,
+/// to where `url` says the key is.
+pub(crate) fn link(
+    content: &str,
+    found: &[(Range<usize>, String)],
+    url: impl Fn(&str) -> String,
+) -> String {
+    let mut linked = String::with_capacity(content.len());
+    let mut last = 0;
+    for (range, key) in found {
+        linked.push_str(&content[last..range.start]);
+        linked.push_str(&format!("[{key}]({})", url(key)));
+        last = range.end;
+    }
+    linked.push_str(&content[last..]);
+    linked
+}
@@ -0,0 +1,60 @@
+use crate::extract::parser;
+use pulldown_cmark::{Event, Tag};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// A line ending with a block ID, like `Some paragraph. ^key-point`, with
+/// what opens the line before its text: indentation, quotes, a heading's
+/// hashes, or a list item's marker and checkbox.
+static MARKED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^(\s*(?:>\s*)*(?:#{1,6}\s+|(?:[-*+]|\d+[.)])\s+(?:\[[ xX]\]\s+)?)?)(?:(.*\S)\s+)?\^([A-Za-z0-9][A-Za-z0-9-]*)\s*$",
+    )
+    .expect("the block marker pattern is valid")
+});
+
+/// The anchor of the block `id`, which `[[page#^id]]` links to.
+pub(crate) fn anchor(id: &str) -> String {
+    format!("block-{}", id.to_lowercase())
+}
+
+/// `content` with every block ID ending a line outside code, as in
+/// `Some paragraph. ^key-point`, replaced by an anchor at the start of the
+/// line. An ID on a line of its own, after a list or a quote, leaves the
+/// anchor there.
+pub(crate) fn rewrite(content: &str) -> String {
+    let code: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| {
+            matches!(
+                event,
+                Event::Start(Tag::CodeBlock(_)) | Event::Code(_) | Event::Html(_)
+            )
+            .then_some(range)
+        })
+        .collect();
+
+    let mut rewritten = String::with_capacity(content.len());
+    let mut offset = 0;
+    for line in content.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+        let text = line.trim_end_matches(['\n', '\r']);
+        let Some(captures) = MARKED.captures(text) else {
+            rewritten.push_str(line);
+            continue;
+        };
+        let id = captures.get(3).expect("the pattern captures an ID");
+        if code.iter().any(|r| r.contains(&(start + id.start()))) {
+            rewritten.push_str(line);
+            continue;
+        }
+        let opening = &captures[1];
+        let body = captures.get(2).map_or("", |body| body.as_str());
+        let anchor = format!("<a id=\"{}\"></a>", anchor(id.as_str()));
+        rewritten.push_str(&format!("{opening}{anchor}{body}"));
+        rewritten.push_str(&line[text.len()..]);
+    }
+    rewritten
+}
@@ -0,0 +1,398 @@
+use crate::config::Labels;
+use crate::extract::{collapse_whitespace, parser};
+use crate::generate::escape_markdown;
+use crate::paths::link_to;
+use anyhow::Context;
+use mdbook::errors::Error;
+use pulldown_cmark::{Event, Tag};
+use std::collections::BTreeMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+/// An entry of the bibliography.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Reference {
+    pub key: String,
+    /// Fields by name, in lowercase, with their braces taken out.
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Reference {
+    fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .get(name)
+            .map(String::as_str)
+            .filter(|value| !value.is_empty())
+    }
+
+    /// The authors, or else the editors, as `(last name, full name)`.
+    fn authors(&self) -> Vec<(String, String)> {
+        let Some(names) = self.field("author").or_else(|| self.field("editor")) else {
+            return Vec::new();
+        };
+        names
+            .split(" and ")
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| match name.split_once(',') {
+                // `Knuth, Donald E.`
+                Some((last, first)) => {
+                    let last = last.trim().to_string();
+                    (last.clone(), format!("{last}, {}", first.trim()))
+                }
+                // `Donald E. Knuth`
+                None => {
+                    let last = name.rsplit(' ').next().unwrap_or(name).to_string();
+                    (last, name.to_string())
+                }
+            })
+            .collect()
+    }
+
+    fn year(&self) -> Option<&str> {
+        self.field("year")
+            .or_else(|| self.field("date").and_then(|date| date.get(..4)))
+    }
+
+    /// What a citation shows, like `Knuth 1984` or `Knuth et al. 1984`.
+    pub fn label(&self, labels: &Labels) -> String {
+        let authors: Vec<String> = self.authors().into_iter().map(|(last, _)| last).collect();
+        let names = match authors.as_slice() {
+            [] => self.field("title").unwrap_or(&self.key).to_string(),
+            [author] => author.clone(),
+            [first, second] => format!("{first} {} {second}", labels.and),
+            [first, ..] => format!("{first} {}", labels.et_al),
+        };
+        match self.year() {
+            Some(year) => format!("{names} {year}"),
+            None => names,
+        }
+    }
+
+    /// How the references' list sorts the entry: by the last name of its
+    /// first author, then year and title.
+    pub fn sort_key(&self) -> (String, String, String) {
+        let author = self.authors().into_iter().next().map(|(last, _)| last);
+        let title = self.field("title").unwrap_or_default().to_lowercase();
+        (
+            author.unwrap_or_else(|| title.clone()).to_lowercase(),
+            self.year().unwrap_or_default().to_string(),
+            title,
+        )
+    }
+
+    /// The entry as the references' list shows it, in Markdown, like
+    /// `Knuth, Donald E. (1984). *The TeXbook*. Addison-Wesley.`
+    pub fn formatted(&self, labels: &Labels) -> String {
+        let authors: Vec<String> = self.authors().into_iter().map(|(_, full)| full).collect();
+        let authors = match authors.as_slice() {
+            [] => String::new(),
+            [author] => author.clone(),
+            [rest @ .., last] => format!("{} {} {last}", rest.join(", "), labels.and),
+        };
+        let mut parts = Vec::new();
+        let mut opening = escape_markdown(&authors);
+        if let Some(year) = self.year() {
+            opening = format!("{opening} ({year})").trim().to_string();
+        }
+        if !opening.is_empty() {
+            parts.push(opening);
+        }
+        // Books go in italics, articles and chapters by what holds them
+        let container = self
+            .field("journal")
+            .or_else(|| self.field("booktitle"))
+            .map(escape_markdown);
+        let title = self.field("title").map(escape_markdown);
+        match (title, &container) {
+            (Some(title), Some(_)) => parts.push(title),
+            (Some(title), None) => parts.push(format!("*{title}*")),
+            (None, _) => {}
+        }
+        if let Some(container) = container {
+            let mut held = format!("*{container}*");
+            if let Some(volume) = self.field("volume") {
+                held.push_str(&format!(", {volume}"));
+                if let Some(number) = self.field("number") {
+                    held.push_str(&format!("({number})"));
+                }
+            }
+            if let Some(pages) = self.field("pages") {
+                held.push_str(&format!(", {pages}"));
+            }
+            parts.push(held);
+        }
+        if let Some(publisher) = self
+            .field("publisher")
+            .or_else(|| self.field("school"))
+            .or_else(|| self.field("institution"))
+        {
+            parts.push(escape_markdown(publisher));
+        }
+        let mut formatted: String = parts
+            .iter()
+            .map(|part| format!("{}.", part.trim_end_matches('.')))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if let Some(doi) = self.field("doi") {
+            formatted.push_str(&format!(" <https://doi.org/{doi}>"));
+        } else if let Some(url) = self.field("url") {
+            formatted.push_str(&format!(" <{url}>"));
+        }
+        formatted
+    }
+}
+
+/// The anchor of the entry `key` in the references' list.
+pub(crate) fn anchor(key: &str) -> String {
+    format!("ref-{}", key.to_lowercase())
+}
+
+/// The entries of the BibTeX file at `path`, by key.
+pub(crate) fn load(path: &Path) -> Result<BTreeMap<String, Reference>, Error> {
+    let bib = fs::read_to_string(path)
+        .with_context(|| format!("couldn't read the bibliography {}", path.display()))?;
+    Ok(parse(&bib)
+        .into_iter()
+        .map(|reference| (reference.key.clone(), reference))
+        .collect())
+}
+
+/// The entries of `bib`, leaving out `@string`, `@preamble` and `@comment`.
+fn parse(bib: &str) -> Vec<Reference> {
+    let mut references = Vec::new();
+    let mut rest = bib;
+    while let Some(at) = rest.find('@') {
+        rest = &rest[at + 1..];
+        let Some(open) = rest.find(['{', '(']) else {
+            break;
+        };
+        let kind = rest[..open].trim().to_lowercase();
+        let close = if rest[open..].starts_with('{') {
+            '}'
+        } else {
+            ')'
+        };
+        let Some(len) = balanced(&rest[open + 1..], close) else {
+            break;
+        };
+        let body = &rest[open + 1..open + 1 + len];
+        rest = &rest[open + 1 + len + 1..];
+        if kind.is_empty()
+            || !kind.chars().all(|c| c.is_ascii_alphabetic())
+            || matches!(kind.as_str(), "string" | "preamble" | "comment")
+        {
+            continue;
+        }
+        let Some((key, fields)) = body.split_once(',') else {
+            continue;
+        };
+        references.push(Reference {
+            key: key.trim().to_string(),
+            fields: parse_fields(fields),
+        });
+    }
+    references
+}
+
+/// Where `close` ends `text`, outside braces.
+fn balanced(text: &str, close: char) -> Option<usize> {
+    let mut depth = 0usize;
+    for (at, c) in text.char_indices() {
+        match c {
+            c if c == close && depth == 0 => return Some(at),
+            '{' => depth += 1,
+            '}' => depth = depth.checked_sub(1)?,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The fields of an entry, as in `title = {The {\TeX}book}, year = 1984`.
+fn parse_fields(text: &str) -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    let mut rest = text;
+    while let Some((name, after)) = rest.split_once('=') {
+        let name = name.trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let after = after.trim_start();
+        let (value, next) = if let Some(inner) = after.strip_prefix('{') {
+            let Some(len) = balanced(inner, '}') else {
+                break;
+            };
+            (&inner[..len], &inner[len + 1..])
+        } else if let Some(inner) = after.strip_prefix('"') {
+            let Some(len) = inner.find('"') else {
+                break;
+            };
+            (&inner[..len], &inner[len + 1..])
+        } else {
+            let len = after.find(',').unwrap_or(after.len());
+            (after[..len].trim(), &after[len..])
+        };
+        fields.insert(name.to_lowercase(), clean(value));
+        rest = next;
+    }
+    fields
+}
+
+/// A field's value as plain text, keeping what LaTeX commands like `\&`
+/// or `{\TeX}` print, bar accents.
+fn clean(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| !matches!(c, '{' | '}' | '\\'))
+        .collect();
+    let value = value.replace("---", "—").replace("--", "–");
+    collapse_whitespace(&value)
+}
+
+/// One key of a citation, as in `see @knuth1984, p. 33`.
+struct Cited {
+    prefix: String,
+    key: String,
+    locator: String,
+}
+
+/// `content` of the chapter at `from` with every citation outside code and
+/// HTML, like `[@knuth1984]` or `[see @knuth1984, p. 33; @lamport1994]`,
+/// shown by author and year and linking to its entries in the references'
+/// list, `file`. Returns the keys it cites of `references`, those an
+/// earlier run linked included, and those it cites that the bibliography
+/// lacks, whose citations are kept as written.
+pub(crate) fn cite(
+    content: &str,
+    from: &str,
+    references: &BTreeMap<String, Reference>,
+    file: &str,
+    labels: &Labels,
+) -> (String, Vec<String>, Vec<String>) {
+    let mut rewritten = String::with_capacity(content.len());
+    let mut cited = linked(content, from, references, file);
+    let mut missing = Vec::new();
+    let mut last = 0;
+    for (range, keys) in find(content) {
+        let unknown: Vec<&Cited> = keys
+            .iter()
+            .filter(|key| !references.contains_key(&key.key))
+            .collect();
+        if !unknown.is_empty() {
+            missing.extend(unknown.iter().map(|key| key.key.clone()));
+            continue;
+        }
+        let shown: Vec<String> = keys
+            .iter()
+            .map(|key| {
+                let reference = &references[&key.key];
+                let href = link_to(Path::new(from), Path::new(file), &anchor(&key.key));
+                let label = escape_markdown(&reference.label(labels));
+                let mut shown = format!("{}[{label}]({href})", key.prefix);
+                if !key.locator.is_empty() {
+                    shown.push_str(&format!(", {}", key.locator));
+                }
+                shown
+            })
+            .collect();
+        cited.extend(keys.into_iter().map(|key| key.key));
+        rewritten.push_str(&content[last..range.start]);
+        rewritten.push_str(&format!("({})", shown.join("; ")));
+        last = range.end;
+    }
+    rewritten.push_str(&content[last..]);
+    (rewritten, cited, missing)
+}
+
+/// The keys of `references` whose entries in `file` the links of `content`,
+/// the chapter at `from`, lead to.
+fn linked(
+    content: &str,
+    from: &str,
+    references: &BTreeMap<String, Reference>,
+    file: &str,
+) -> Vec<String> {
+    parser(content)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                let (_, fragment) = dest_url.split_once('#')?;
+                references
+                    .keys()
+                    .find(|key| {
+                        let anchor = anchor(key);
+                        anchor == fragment
+                            && link_to(Path::new(from), Path::new(file), &anchor) == *dest_url
+                    })
+                    .cloned()
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Every citation in `content` and the keys it cites, skipping code, HTML,
+/// links and escaped brackets.
+fn find(content: &str) -> Vec<(Range<usize>, Vec<Cited>)> {
+    let skipped: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Link { .. })
+            | Event::Code(_)
+            | Event::Html(_)
+            | Event::InlineHtml(_) => Some(range),
+            _ => None,
+        })
+        .collect();
+
+    let mut citations = Vec::new();
+    let mut from = 0;
+    while let Some(found) = content[from..].find('[') {
+        let start = from + found;
+        from = start + 1;
+        if content[..start].ends_with('\\') || skipped.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+        let Some(len) = content[from..].find(['[', ']', '\n']) else {
+            break;
+        };
+        let end = from + len;
+        if !content[end..].starts_with(']') {
+            continue;
+        }
+        // `[@key]: ...` defines a link instead
+        if content[end + 1..].starts_with(':') {
+            continue;
+        }
+        let keys: Option<Vec<Cited>> = content[from..end].split(';').map(parse_cited).collect();
+        if let Some(keys) = keys {
+            citations.push((start..end + 1, keys));
+            from = end + 1;
+        }
+    }
+    citations
+}
+
+/// The key `part` of a citation cites, with what comes before and after it.
+fn parse_cited(part: &str) -> Option<Cited> {
+    let part = part.trim();
+    let at = part.find('@')?;
+    let prefix = &part[..at];
+    if !prefix.is_empty() && !prefix.ends_with(char::is_whitespace) {
+        return None;
+    }
+    let rest = &part[at + 1..];
+    let len = rest
+        .find(|c: char| !(c.is_alphanumeric() || "_:.-/".contains(c)))
+        .unwrap_or(rest.len());
+    let key = rest[..len].trim_end_matches(['.', ':', '-', '/']);
+    let after = rest[key.len()..].trim();
+    let locator = match after.strip_prefix(',') {
+        Some(locator) => locator.trim(),
+        None if after.is_empty() => "",
+        None => return None,
+    };
+    (!key.is_empty()).then(|| Cited {
+        prefix: prefix.to_string(),
+        key: key.to_string(),
+        locator: locator.to_string(),
+    })
+}
@@ -0,0 +1,1760 @@
+use anyhow::Context;
+use mdbook::errors::Error;
+use mdbook::preprocess::PreprocessorContext;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Settings read from the `[preprocessor.indexer]` table of `book.toml`.
+///
+/// Every key is optional; anything left out falls back to the defaults below,
+/// and a key it doesn't know, like a misspelled one, is an error.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IndexerConfig {
+    /// Whether `#tags` are linked and indexed.
+    pub tags: bool,
+    /// Whether `@mentions` are linked and indexed.
+    pub mentions: bool,
+    /// Character introducing a tag, `#` by default.
+    pub tag_prefix: char,
+    /// Chapter generated for the tag index.
+    pub tags_file: String,
+    /// Title of the tag index.
+    pub tags_title: String,
+    /// Whether tags may be nested with `/`, as in `#rust/async`.
+    pub nested_tags: bool,
+    /// Whether every tag gets its own page under `tags/`, with `tags_file`
+    /// listing them. The tag index is then neither split by `split_entries`
+    /// nor folded by `collapse`.
+    pub page_per_tag: bool,
+    /// Text shown on a tag's page, keyed by tag.
+    pub tag_descriptions: BTreeMap<String, String>,
+    /// Tags indexed as another, keyed by alias, like `js = "javascript"`.
+    /// Tokens keep their spelling but link to the entry they stand for.
+    pub tag_aliases: BTreeMap<String, String>,
+    /// Whether a chapter shows every tag sized by how often it occurs.
+    pub tag_cloud: bool,
+    /// Chapter generated for the tag cloud.
+    pub tag_cloud_file: String,
+    /// Title of the tag cloud.
+    pub tag_cloud_title: String,
+    /// Whether a chapter lists the pairs of tags found in the most chapters
+    /// together.
+    pub co_occurrence: bool,
+    /// Chapter generated for the pairs of tags.
+    pub co_occurrence_file: String,
+    /// Title of the pairs of tags' list.
+    pub co_occurrence_title: String,
+    /// Number of pairs listed.
+    pub co_occurrence_pairs: usize,
+    /// Whether a chapter sums up the indexes: totals, the most used keys and
+    /// the chapters with the most and fewest indexed tokens.
+    pub stats: bool,
+    /// Chapter generated for the statistics.
+    pub stats_file: String,
+    /// Title of the statistics chapter.
+    pub stats_title: String,
+    /// Whether a chapter draws the chapters, the keys they use and the links
+    /// between them as a Mermaid diagram, for a renderer that supports it.
+    pub graph: bool,
+    /// Chapter generated for the graph.
+    pub graph_file: String,
+    /// Title of the graph chapter.
+    pub graph_title: String,
+    /// Entity types drawn in the graph, by name, all of them by default.
+    pub graph_kinds: Option<Vec<String>>,
+    /// Deepest level of nested keys drawn, their ancestors' chapters using
+    /// them instead; `0` only draws top-level keys.
+    pub graph_depth: Option<usize>,
+    /// Whether the graph draws the links between chapters.
+    pub graph_links: bool,
+    /// Whether the graph chapter of the HTML renderer is an interactive,
+    /// force-directed view instead of the Mermaid diagram.
+    pub graph_viewer: bool,
+    /// File the graph is also written to as JSON, relative to the book's
+    /// root, for other tools to explore.
+    pub graph_json: Option<String>,
+    /// File the graph is also written to in Graphviz DOT, relative to the
+    /// book's root. The HTML renderer clears its own output directory, so
+    /// one under the build directory only survives with several renderers.
+    pub graph_dot: Option<String>,
+    /// Files the graph is also written to in GraphML and GEXF, relative to
+    /// the book's root, for network analysis tools like Gephi.
+    pub graph_graphml: Option<String>,
+    pub graph_gexf: Option<String>,
+    /// Whether `[[Chapter name]]` links, optionally with `#Heading` or
+    /// `|text`, are turned into links to the chapter with that name or path.
+    pub wikilinks: bool,
+    /// What happens to wikilinks naming no chapter, which are reported unless
+    /// they link to a stub.
+    pub unresolved_wikilinks: UnresolvedLinks,
+    /// Directory of the stub chapters with `unresolved_wikilinks = "stub"`.
+    pub stub_dir: String,
+    /// Whether `{{#snippet name}}` is replaced by the snippet of that name
+    /// before anything is indexed, so its keys count for the chapter
+    /// including it: the file `name.md` of `snippets_dir`, or the section of
+    /// `snippets_chapter` headed `name`. Chapters among the snippets are left
+    /// out of the book.
+    pub include_snippets: bool,
+    /// Directory of the snippets, relative to the source directory.
+    pub snippets_dir: String,
+    /// Chapter whose sections are snippets, by path.
+    pub snippets_chapter: Option<String>,
+    /// Whether lines like `![[Chapter name]]` or `![[Chapter name#Heading]]`
+    /// are replaced by that chapter, or that section of it.
+    pub embeds: bool,
+    /// How embedded chapters are set off from the text around them.
+    pub embed_style: EmbedStyle,
+    /// Whether a line ending with a block ID, as in `Some point. ^key`, gets
+    /// an anchor `[[Chapter#^key]]` links to.
+    pub block_references: bool,
+    /// Whether every former name a chapter gives under `aliases` in its
+    /// front matter, like `aliases: [old-name]`, gets a page sending readers
+    /// on to it, so links to it keep working. Names without `.md` stand for
+    /// a file next to the chapter. Wikilinks resolve aliases either way.
+    pub alias_redirects: bool,
+    /// Chapter generated for the list of former names, which holds their
+    /// pages.
+    pub redirects_file: String,
+    /// Title of the list of former names.
+    pub redirects_title: String,
+    /// Whether notes have Zettelkasten IDs, given as `id` in their front
+    /// matter or opening their file name, like `202501151230 Habits.md`.
+    /// `[[202501151230]]` then links to the note, and a register lists the
+    /// notes by ID.
+    pub zettelkasten: bool,
+    /// What IDs match, timestamps like `202501151230` by default.
+    pub zettel_pattern: Option<String>,
+    /// Chapter generated for the register.
+    pub register_file: String,
+    /// Title of the register.
+    pub register_title: String,
+    /// Whether the book is an Obsidian vault: `%%comments%%` are dropped,
+    /// `![[image.png]]` shows the attachment wherever it is, and notes the
+    /// summary leaves out are added at its end, folder by folder. Turns on
+    /// `wikilinks`, `embeds`, `block_references` and `nested_tags` unless
+    /// they are set.
+    pub obsidian: bool,
+    /// Whether every chapter ends with the list of chapters linking to it.
+    pub backlinks: bool,
+    /// Number of related chapters listed at the end of every chapter, none
+    /// by default.
+    pub related: Option<usize>,
+    /// What makes chapters related: the tags they share, or how alike their
+    /// wording is. With `text`, the graph's JSON export also gives every
+    /// chapter the chapters closest to it.
+    pub related_by: RelatedBy,
+    /// File the words of every chapter are kept in between builds with
+    /// `related_by = "text"` or tag suggestions, relative to the book's root,
+    /// so that only the chapters that changed are read again.
+    pub similarity_cache: Option<String>,
+    /// Whether a chapter lists the chapters without any indexed key, to find
+    /// the notes that still need tagging.
+    pub untagged: bool,
+    /// Chapter generated for the untagged chapters.
+    pub untagged_file: String,
+    /// Title of the untagged chapters' list.
+    pub untagged_title: String,
+    /// Whether a chapter lists the orphans: chapters no other chapter links
+    /// to or shares a key with.
+    pub orphans: bool,
+    /// Chapter generated for the orphans.
+    pub orphans_file: String,
+    /// Title of the orphans' list.
+    pub orphans_title: String,
+    /// Whether every orphan is reported on stderr.
+    pub warn_orphans: bool,
+    /// File the orphans are also written to as JSON, relative to the book's
+    /// root.
+    pub orphans_json: Option<String>,
+    /// Where the glossary is defined: a chapter, by path in the source
+    /// directory, whose `##` headings are the terms, each defined by what
+    /// follows it, or a TOML or YAML file mapping terms to definitions,
+    /// relative to the book's root. Every term is then linked, wherever it
+    /// appears, to a generated glossary chapter.
+    pub glossary: Option<String>,
+    /// Chapter generated for the glossary.
+    pub glossary_file: String,
+    /// Title of the glossary.
+    pub glossary_title: String,
+    /// Acronyms and what they stand for, wrapped in an `<abbr>` giving it
+    /// wherever they appear with the HTML renderer.
+    pub acronyms: BTreeMap<String, String>,
+    /// Whether acronyms are also taken from where the book defines them, as
+    /// in `ABC (Always Be Coding)` or `Always Be Coding (ABC)`.
+    pub detect_acronyms: bool,
+    /// Whether a chapter lists the acronyms, what they stand for and the
+    /// chapters using them.
+    pub acronym_index: bool,
+    /// Chapter generated for the acronyms.
+    pub acronym_file: String,
+    /// Title of the acronyms' list.
+    pub acronym_title: String,
+    /// Whether a chapter gathers the task list items, `- [ ]` and `- [x]`,
+    /// of every chapter.
+    pub tasks: bool,
+    /// Chapter generated for the tasks.
+    pub tasks_file: String,
+    /// Title of the tasks' list.
+    pub tasks_title: String,
+    /// Whether chapters with tasks say how many of them are done, below
+    /// their title.
+    pub task_progress: bool,
+    /// Status tags, like `todo`, `doing` and `done`, in the order of the
+    /// columns of a board listing the chapters and tasks tagged with each.
+    /// No board by default.
+    pub kanban: Vec<String>,
+    /// Chapter generated for the board.
+    pub kanban_file: String,
+    /// Title of the board.
+    pub kanban_title: String,
+    /// Workflow tags, like `draft`, `review` and `published`, in the order
+    /// chapters go through them. A chapter tagged with several is at the
+    /// earliest.
+    pub statuses: Vec<String>,
+    /// Statuses of `statuses` whose chapters a renderer leaves out, by
+    /// renderer name, as in `html = ["draft"]`, along with the chapters
+    /// nested below them. Renderers not listed keep every chapter.
+    pub exclude_statuses: BTreeMap<String, Vec<String>>,
+    /// Renderers whose readers may not see what chapters mark as private:
+    /// the blocks tagged with `private_tag`, and regions
+    /// between `<!-- indexer:private -->` and `<!-- indexer:public -->`.
+    pub redact: Vec<String>,
+    /// Tag marking a paragraph, list item, table row or heading section as
+    /// private. With redaction set up, it is never indexed.
+    pub private_tag: String,
+    /// Whether redacted parts leave `labels.redacted` in their place.
+    pub redaction_notice: bool,
+    /// Builds for different audiences, by name, as in
+    /// `[preprocessor.indexer.profiles.public]`.
+    pub profiles: BTreeMap<String, BuildProfile>,
+    /// Profile of `profiles` the book is built with, for every renderer,
+    /// also set with `MDBOOK_PREPROCESSOR__INDEXER__PROFILE=public`.
+    pub profile: Option<String>,
+    /// Profiles of `profiles` by renderer name, as in `html = "public"`,
+    /// unless `profile` is set.
+    pub renderer_profiles: BTreeMap<String, String>,
+    /// Whether a chapter lists what is due, by date, after what is overdue
+    /// at the date of the build.
+    pub agenda: bool,
+    /// Chapter generated for the agenda.
+    pub agenda_file: String,
+    /// Title of the agenda.
+    pub agenda_title: String,
+    /// What gives a due date in the text of a task or chapter, followed by
+    /// the date in parentheses, as in `@due(2025-03-01)`. A chapter may also
+    /// give one as `due` in its front matter.
+    pub due_marker: String,
+    /// Whether a chapter lists the dates written `YYYY-MM-DD` in the book,
+    /// and those given as `date` in front matter, in order, each linking
+    /// to where it appears.
+    pub timeline: bool,
+    /// Chapter generated for the timeline.
+    pub timeline_file: String,
+    /// Title of the timeline.
+    pub timeline_title: String,
+    /// Directory of the source directory holding daily notes, named after
+    /// their day as in `journal/2025-01-15.md`. Each note links to the ones
+    /// before and after it, and a chapter lays them out month by month.
+    pub journal: Option<String>,
+    /// Chapter generated for the journal's calendar.
+    pub journal_file: String,
+    /// Title of the journal's calendar.
+    pub journal_title: String,
+    /// Whether a chapter lists what is tagged with `reading_tag`, each by
+    /// the link or text on the line of the tag, or by the chapter tagged.
+    pub reading_list: bool,
+    /// Tag of what is yet to be read.
+    pub reading_tag: String,
+    /// Chapter generated for the reading list.
+    pub reading_list_file: String,
+    /// Title of the reading list.
+    pub reading_list_title: String,
+    /// Whether a chapter gathers the blockquotes of the book, with who
+    /// said them and where they are quoted.
+    pub quotes: bool,
+    /// When set, the tag a blockquote needs to be gathered, like `quote`.
+    pub quote_tag: Option<String>,
+    /// Chapter generated for the quotes.
+    pub quotes_file: String,
+    /// Title of the quotes' list.
+    pub quotes_title: String,
+    /// Whether a chapter gathers the footnotes of every chapter, numbered
+    /// anew across the book, each linking to the chapters defining it.
+    pub endnotes: bool,
+    /// Chapter generated for the endnotes.
+    pub endnotes_file: String,
+    /// Title of the endnotes.
+    pub endnotes_title: String,
+    /// Whether a chapter lists the callouts of the book, like `> [!NOTE]`
+    /// or `> [!WARNING]`, by kind.
+    pub callouts: bool,
+    /// Kinds of callouts listed, in order, all of them by name by default.
+    pub callout_kinds: Option<Vec<String>>,
+    /// Chapter generated for the callouts.
+    pub callouts_file: String,
+    /// Title of the callouts' list.
+    pub callouts_title: String,
+    /// Whether a chapter lists the images of the book, with their alt text
+    /// and the chapters showing them. Images shown with different alt texts
+    /// are warned about.
+    pub images: bool,
+    /// Whether the images' list shows each image small with the HTML
+    /// renderer, instead of linking to it.
+    pub image_thumbnails: bool,
+    /// Chapter generated for the images.
+    pub images_file: String,
+    /// Title of the images' list.
+    pub images_title: String,
+    /// Whether the tables of the book are numbered, with their captions
+    /// given by a `Table:` paragraph right after or before them, and listed
+    /// in a chapter.
+    pub tables: bool,
+    /// Chapter generated for the tables.
+    pub tables_file: String,
+    /// Title of the tables' list.
+    pub tables_title: String,
+    /// Whether a chapter lists the `http(s)` links leaving the book, each
+    /// once, by domain, with the chapters linking to them.
+    pub external_links: bool,
+    /// Chapter generated for the external links.
+    pub external_links_file: String,
+    /// Title of the external links' list.
+    pub external_links_title: String,
+    /// Whether cross-references like `{#ref:installing}` link to the heading
+    /// of the book with that id, or `{#ref:setup.md#installing}` to the one
+    /// of a chapter. The build fails on one naming no heading, or more than
+    /// one.
+    pub cross_references: bool,
+    /// File every heading of the book is also written to as JSON, with its
+    /// chapter and id, relative to the book's root.
+    pub headings_json: Option<String>,
+    /// Whether a chapter lists the symbols inline code spans show, like
+    /// `` `tokio::spawn` ``, with the chapters showing them.
+    pub symbols: bool,
+    /// Regular expression a code span matches to be listed as a symbol;
+    /// paths with `::` and calls like `spawn()` by default.
+    pub symbol_pattern: Option<String>,
+    /// Chapter generated for the symbols.
+    pub symbols_file: String,
+    /// Title of the symbols' list.
+    pub symbols_title: String,
+    /// BibTeX file, relative to the book's root, that citations like
+    /// `[@knuth1984]` or `[see @knuth1984, p. 33; @lamport1994]` are
+    /// resolved against. Each is then shown by author and year, linking to
+    /// a generated list of the references cited.
+    pub bibliography: Option<String>,
+    /// Chapter generated for the references.
+    pub references_file: String,
+    /// Title of the references' list.
+    pub references_title: String,
+    /// Whether DOIs and arXiv identifiers written out, like
+    /// `doi:10.1000/xyz123` or `arXiv:2101.12345`, link to their resolvers.
+    pub link_identifiers: bool,
+    /// Whether a chapter lists the DOIs and arXiv identifiers of the book,
+    /// with the chapters giving them.
+    pub sources: bool,
+    /// Chapter generated for the sources.
+    pub sources_file: String,
+    /// Title of the sources' list.
+    pub sources_title: String,
+    /// Repository, like `https://github.com/owner/repo`, that tokens made
+    /// only of digits with the prefix of tags, like `#123`, refer to the
+    /// issues and pull requests of. They then link there, whatever
+    /// `skip_numeric_tags` says, while `#words` stay tags.
+    pub issue_repository: Option<String>,
+    /// Repository the commits of `commit_pattern` link to, like
+    /// `https://github.com/owner/repo`; `issue_repository` by default.
+    pub commit_repository: Option<String>,
+    /// Regular expression matching commit hashes, the hash being what it
+    /// captures first; `commit:` followed by 7 to 40 hex digits by default.
+    pub commit_pattern: Option<String>,
+    /// Whether a chapter lists the commits the book links to, with the
+    /// chapters naming them.
+    pub commits: bool,
+    /// Chapter generated for the commits.
+    pub commits_file: String,
+    /// Title of the commits' list.
+    pub commits_title: String,
+    /// Where the tickets of `ticket_pattern` link to, with `{key}` standing
+    /// for the ticket, like `https://jira.example.com/browse/{key}`.
+    pub ticket_url: Option<String>,
+    /// Regular expression matching ticket keys, the key being what it
+    /// captures first; keys like `PROJ-1234` by default. Naming the projects,
+    /// as in `\b(PROJ|OPS)-[0-9]+\b`, keeps `UTF-8` from being a ticket.
+    pub ticket_pattern: Option<String>,
+    /// Whether a chapter lists the tickets of the book by project, with the
+    /// chapters discussing them.
+    pub tickets: bool,
+    /// Chapter generated for the tickets.
+    pub tickets_file: String,
+    /// Title of the tickets' list.
+    pub tickets_title: String,
+    /// Sources outside the book by prefix, with where their pages are,
+    /// `{key}` standing for the page, as in
+    /// `wiki = "https://en.wikipedia.org/wiki/{key}"`; without it the page
+    /// goes at the end. `wiki:Rust_(programming_language)` then links there.
+    pub interwiki: BTreeMap<String, String>,
+    /// Whether a chapter lists the pages of `interwiki` sources the book
+    /// links to, source by source.
+    pub interwiki_references: bool,
+    /// Chapter generated for the external references.
+    pub interwiki_file: String,
+    /// Title of the external references' list.
+    pub interwiki_title: String,
+    /// Where the book is published, for the other books of its workspace
+    /// to link to.
+    pub site_url: Option<String>,
+    /// File the chapters and index entries of the book are written to as
+    /// JSON, relative to the book's root, for the other books of its
+    /// workspace to read. Needs `site_url`.
+    pub workspace_json: Option<String>,
+    /// The other books of the workspace, by name, with the path of their
+    /// `workspace_json` relative to the book's root, as in
+    /// `runbooks = "../runbooks/workspace.json"`. Wikilinks like
+    /// `[[runbooks:Deploy]]`, or naming a chapter only they have, link to
+    /// where they publish it, and index entries they list too link there.
+    pub workspace: BTreeMap<String, String>,
+    /// Whether a chapter lists the email addresses of the book, with the
+    /// chapters referencing them.
+    pub contacts: bool,
+    /// Whether addresses written out are spelled so that harvesters don't
+    /// read them, as character references for the HTML renderer and as
+    /// `alice [at] example [dot] com` for the others.
+    pub obfuscate_emails: bool,
+    /// Chapter generated for the contacts.
+    pub contacts_file: String,
+    /// Title of the contacts' list.
+    pub contacts_title: String,
+    /// Whether a chapter lists the places chapters are set in: those nested
+    /// tags below `location_tag` name, like `#loc/paris`, and coordinates
+    /// written as `@@(48.85,2.35)`, which link to OpenStreetMap.
+    pub locations: bool,
+    /// Tag the places are nested below.
+    pub location_tag: String,
+    /// Latitude and longitude of places, by the name their tag gives them,
+    /// as in `paris = [48.8566, 2.3522]`.
+    pub places: BTreeMap<String, (f64, f64)>,
+    /// Chapter generated for the locations.
+    pub locations_file: String,
+    /// Title of the locations' list.
+    pub locations_title: String,
+    /// Whether the HTML renderer gets a chapter plotting the located places
+    /// on a Leaflet map of OpenStreetMap tiles, loaded from unpkg.com.
+    pub location_map: bool,
+    /// Chapter generated for the map.
+    pub location_map_file: String,
+    /// Title of the map.
+    pub location_map_title: String,
+    /// Whether a calendar lists the events chapters describe, written as
+    /// `@event(2025-06-10, RustConf)`, month by month.
+    pub events: bool,
+    /// Chapter generated for the events' calendar.
+    pub events_file: String,
+    /// Title of the events' calendar.
+    pub events_title: String,
+    /// Whether a review chapter lists the flashcards of the chapters:
+    /// paragraphs opening with `Q:` with a line opening with `A:`, and list
+    /// items tagged with `flashcard_tag`, their nested lines or what follows
+    /// `::` being the answer.
+    pub flashcards: bool,
+    /// Tag marking a list item as a flashcard, replaced by a comment
+    /// in the item.
+    pub flashcard_tag: String,
+    /// Chapter generated for the review of the flashcards. The HTML renderer
+    /// gets a link there to download them for Anki.
+    pub flashcards_file: String,
+    /// Title of the review chapter.
+    pub flashcards_title: String,
+    /// File the flashcards are also written to, relative to the book's
+    /// root, as tab-separated text Anki imports into a deck named after the
+    /// book.
+    pub flashcards_tsv: Option<String>,
+    /// Whether a chapter lists the entries of every kind together, in
+    /// alphabetical order, like the index at the back of a book.
+    pub master_index: bool,
+    /// Chapter generated for the combined index. `index.md` would replace the
+    /// landing page of the HTML renderer unless the book opens with it.
+    pub master_index_file: String,
+    /// Title of the combined index.
+    pub master_index_title: String,
+    /// Whether purely numeric tokens like `#42` are left out of the tags.
+    pub skip_numeric_tags: bool,
+    /// Tags that are never linked or indexed.
+    pub ignore: Vec<String>,
+    /// When set, the only tags that are linked and indexed.
+    pub allow: Option<Vec<String>>,
+    /// Character introducing a mention, `@` by default.
+    pub mention_prefix: char,
+    /// Chapter generated for the mention index.
+    pub mentions_file: String,
+    /// Title of the mention index.
+    pub mentions_title: String,
+    /// Whether every mention gets its own page under `mentions/`, with
+    /// `mentions_file` listing them.
+    pub page_per_mention: bool,
+    /// Details shown on a person's page, keyed by mention.
+    pub people: BTreeMap<String, Profile>,
+    /// TOML or YAML file, relative to the book root, declaring people the
+    /// same way as `people`, which wins for a mention declared in both.
+    pub people_file: Option<String>,
+    /// Whether mentions of people declared nowhere are warned about.
+    pub warn_unknown_people: bool,
+    /// Profiles outside the book mentions link to: a URL per mention, like
+    /// `{ alice = "https://github.com/alice" }`, or one URL with
+    /// `{mention}` standing for the name, like `https://github.com/{mention}`.
+    pub mention_links: Option<MentionLinks>,
+    /// Whether mentions with a profile link there instead of to the mention
+    /// index, or link to both.
+    pub mention_links_mode: MentionLinksMode,
+    /// Mentions that are never linked or indexed.
+    pub mention_ignore: Vec<String>,
+    /// When set, the only mentions that are linked and indexed.
+    pub mention_allow: Option<Vec<String>>,
+    /// Keys occurring fewer times than this are left out of the index pages.
+    pub min_occurrences: usize,
+    /// Whether keys below `min_occurrences` are still linked inline; they link
+    /// to an index page that no longer lists them.
+    pub link_rare: bool,
+    /// Whether index entries show how often a key occurs, in total and in
+    /// each chapter, as in `#rust (37)`.
+    pub counts: bool,
+    /// Whether linked tokens are followed by how often their key occurs in
+    /// the book, as in `#rust<sup>12</sup>`.
+    pub count_badges: bool,
+    /// Whether index entries quote the line around every occurrence.
+    pub snippets: bool,
+    /// Whether every occurrence gets an anchor like `tags-rust-3`, and index
+    /// entries link to the exact occurrence instead of the chapter.
+    pub deep_links: bool,
+    /// Whether index entries list every occurrence on its own line, linked
+    /// to its anchor, instead of every chapter once. Needs `deep_links`.
+    pub list_occurrences: bool,
+    /// Whether index pages group entries by initial, with an A–Z bar of
+    /// links to the groups at the top.
+    pub letter_nav: bool,
+    /// Entries with at least this many occurrences are folded into a
+    /// `<details>` block on the index page, behind a summary with the count.
+    /// Ignored for tags with `page_per_tag`.
+    pub collapse: Option<usize>,
+    /// Index pages listing more entries than this are split into a
+    /// sub-chapter per range of initials, like `tags-a-f.md`, below an
+    /// overview of the ranges. Ignored for tags with `page_per_tag`.
+    pub split_entries: Option<usize>,
+    /// Fixed words of the generated chapters.
+    pub labels: Labels,
+    /// Where the generated chapters go in the book.
+    pub placement: Placement,
+    /// Whether every part of the book gets indexes of its own.
+    pub part_indexes: PartIndexes,
+    /// How the entries of an index page are ordered.
+    pub sort: SortOrder,
+    /// How the chapters listed under an entry are ordered.
+    pub page_sort: PageOrder,
+    /// Whether index entries show the names of a chapter's parents before its
+    /// own, as in `Guides › Setup`.
+    pub breadcrumbs: bool,
+    /// How differently cased spellings of a key are merged.
+    pub case: CaseMode,
+    /// Character in front of a prefix that keeps the token as literal text,
+    /// `\` by default (`\#notatag`).
+    pub escape: char,
+    /// What happens to a chapter's YAML front matter once its keys are indexed.
+    pub front_matter: FrontMatterMode,
+    /// Whether chapters show the `metadata_fields` of their front matter
+    /// in a block below their title.
+    pub metadata: bool,
+    /// Keys of the front matter shown, in order. Those naming an entity
+    /// type, like `tags`, link to their entries.
+    pub metadata_fields: Vec<String>,
+    /// What the fields are called in the block, by key, the key capitalized
+    /// by default, as in `created = "Written"`.
+    pub metadata_labels: BTreeMap<String, String>,
+    /// How the block is laid out.
+    pub metadata_style: MetadataStyle,
+    /// Whether fenced `indexer-query` blocks, like `tag:#rust AND
+    /// status:draft`, are replaced by the list of the other chapters matching
+    /// them. A condition names an entity type, singular or plural, or just gives
+    /// the key with its prefix, as in `#rust`; `status` also reads the tags
+    /// of `statuses`, `path` takes the start of paths, and other fields are
+    /// front matter keys. Words alone are looked for in titles.
+    pub queries: bool,
+    /// Links appended, under `see_also` of `labels`, to every chapter
+    /// matching a rule, set as `[[preprocessor.indexer.see_also]]` tables.
+    pub see_also: Vec<SeeAlsoRule>,
+    /// Whether variables outside code, like `{{date}}`, `{{book.title}}`,
+    /// `{{chapter.title}}`, `{{chapter.path}}`, `{{chapter_count}}` and a
+    /// count for each entity type, like `{{tag_count}}`, are replaced by
+    /// their value, along with those of `variables`.
+    pub expand_variables: bool,
+    /// Values of variables, by name, as in `version = "2.1"`, taking
+    /// precedence over the built-in ones.
+    pub variables: BTreeMap<String, String>,
+    /// Directory of Handlebars templates for index pages, like `tags.hbs`,
+    /// relative to the book root. The HTML theme directory by default.
+    pub templates: Option<String>,
+    /// What happens to links between the book's pages, generated ones
+    /// included, that lead to no chapter, file or id.
+    pub dead_links: DeadLinks,
+    /// What happens to chapter titles and mentioned names written as plain
+    /// text in a chapter that doesn't link to them yet.
+    pub unlinked_mentions: UnlinkedMentions,
+    /// What happens to the tags suggested for chapters without any, taken
+    /// from the words weighing most in them, existing tags first.
+    pub tag_suggestions: TagSuggestions,
+    /// Number of tags suggested for each untagged chapter.
+    pub suggested_tags: usize,
+    /// Additional prefixed entity types, keyed by name.
+    pub entities: BTreeMap<String, EntityConfig>,
+}
+
+/// How keys differing only in case, like `#Rust` and `#rust`, are indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CaseMode {
+    /// Every spelling gets its own entry.
+    Sensitive,
+    /// Spellings are merged and shown in lowercase.
+    Lowercase,
+    /// Spellings are merged and shown as first written in the book.
+    FirstSeen,
+}
+
+/// Words the generated chapters use besides keys and titles, to be
+/// translated under `[preprocessor.indexer.labels]`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Labels {
+    /// Heading of the chapters referencing a key on its own page.
+    pub pages: String,
+    /// Heading of the keys nested below a key on its own page.
+    pub children: String,
+    pub name: String,
+    pub role: String,
+    pub email: String,
+    pub homepage: String,
+    /// Text of the link to a mention's profile, with
+    /// `mention_links_mode = "alongside"`.
+    pub profile: String,
+    pub links: String,
+    /// Before the links to an entry in the other books of the workspace.
+    pub also_in: String,
+    /// Group of keys not starting with a letter or digit in `letter_nav`.
+    pub other: String,
+    /// Headings and columns of the statistics chapter.
+    pub totals: String,
+    pub entries: String,
+    pub occurrences: String,
+    pub per_chapter: String,
+    pub most_used: String,
+    pub most_linked: String,
+    pub least_linked: String,
+    /// Heading of the chapters linking to a chapter, with `backlinks` on.
+    pub backlinks: String,
+    /// Text of a stub chapter, above the chapters linking to it.
+    pub stub: String,
+    /// Before the link on the page left at a chapter's former name.
+    pub moved_to: String,
+    /// Text of the link downloading the flashcards for Anki.
+    pub anki_download: String,
+    /// In place of a private part of a chapter, for the renderers in `redact`.
+    pub redacted: String,
+    /// In place of the list of a query no other chapter matches.
+    pub no_matches: String,
+    /// Heading of the related chapters listed at the end of a chapter.
+    pub related: String,
+    /// Heading of the chapters appended by the rules of `see_also`.
+    pub see_also: String,
+    /// Before the chapters using a term of the glossary.
+    pub used_in: String,
+    /// Columns of the acronyms' list, along with `used_in`.
+    pub acronym: String,
+    pub meaning: String,
+    /// Columns of the pairs of tags' list.
+    pub together: String,
+    pub shared_chapters: String,
+    /// After the number of tasks done out of all, as in `3/7 tasks complete`.
+    pub tasks_complete: String,
+    /// Heading of what is past due in the agenda.
+    pub overdue: String,
+    /// Columns of the images' list, along with `used_in`.
+    pub image: String,
+    pub alt_text: String,
+    /// Opens the numbered captions of the tables, as in `Table 1: Formats`.
+    pub table: String,
+    /// Join the authors of a reference, as in `Knuth and Lamport 1986` or
+    /// `Knuth et al. 1986`.
+    pub and: String,
+    pub et_al: String,
+    /// Names of the months and days of the week, from Monday, in the
+    /// journal's calendar.
+    pub months: Vec<String>,
+    pub weekdays: Vec<String>,
+}
+
+impl Default for Labels {
+    fn default() -> Self {
+        Labels {
+            pages: String::from("Pages"),
+            children: String::from("Children"),
+            name: String::from("Name"),
+            role: String::from("Role"),
+            email: String::from("Email"),
+            homepage: String::from("Homepage"),
+            profile: String::from("↗"),
+            links: String::from("Links"),
+            also_in: String::from("Also in"),
+            other: String::from("Other"),
+            totals: String::from("Totals"),
+            entries: String::from("Entries"),
+            occurrences: String::from("Occurrences"),
+            per_chapter: String::from("Per chapter"),
+            most_used: String::from("Most used"),
+            most_linked: String::from("Most linked chapters"),
+            least_linked: String::from("Least linked chapters"),
+            backlinks: String::from("Linked from"),
+            stub: String::from("This note doesn't exist yet — linked from:"),
+            moved_to: String::from("This page has moved to"),
+            anki_download: String::from("Download for Anki"),
+            redacted: String::from("This part is private."),
+            no_matches: String::from("No chapter matches."),
+            related: String::from("Related"),
+            see_also: String::from("See also"),
+            used_in: String::from("Used in"),
+            acronym: String::from("Acronym"),
+            meaning: String::from("Meaning"),
+            together: String::from("Tags"),
+            shared_chapters: String::from("Chapters"),
+            tasks_complete: String::from("tasks complete"),
+            overdue: String::from("Overdue"),
+            image: String::from("Image"),
+            alt_text: String::from("Alt text"),
+            table: String::from("Table"),
+            and: String::from("and"),
+            et_al: String::from("et al."),
+            months: [
+                "January",
+                "February",
+                "March",
+                "April",
+                "May",
+                "June",
+                "July",
+                "August",
+                "September",
+                "October",
+                "November",
+                "December",
+            ]
+            .map(String::from)
+            .to_vec(),
+            weekdays: ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+/// Position of the generated chapters, set under
+/// `[preprocessor.indexer.placement]`. They are appended to the book, without
+/// numbers, unless told otherwise.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Placement {
+    /// Title of a part opening the generated chapters, like `Indexes`.
+    pub part: Option<String>,
+    /// Whether the generated chapters are numbered along with the book's.
+    pub numbered: bool,
+    /// Path or name of the chapter the generated chapters follow.
+    pub after: Option<String>,
+    /// Path or name of the chapter the generated chapters precede.
+    pub before: Option<String>,
+}
+
+/// Chapters every chapter matching a condition links to, like
+/// `when = "tag:#kubernetes"` with `pages = ["ops/k8s-cheatsheet.md"]`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SeeAlsoRule {
+    /// A condition written as in an `indexer-query` block.
+    pub when: String,
+    /// Chapters, by path or by name.
+    pub pages: Vec<String>,
+}
+
+/// What a build for an audience leaves out, set under
+/// `[preprocessor.indexer.profiles.<name>]`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuildProfile {
+    /// Chapters, or directories of the source directory holding them.
+    pub exclude_chapters: Vec<String>,
+    /// Tags whose chapters are left out, along with their nested tags.
+    pub exclude_tags: Vec<String>,
+    /// Statuses of `statuses` whose chapters are left out.
+    pub exclude_statuses: Vec<String>,
+    /// Whether private parts are redacted, as for the renderers of `redact`.
+    pub redact: bool,
+}
+
+impl BuildProfile {
+    /// Whether the chapter at `page` is in one of `exclude_chapters`.
+    pub fn excludes_page(&self, page: &str) -> bool {
+        self.exclude_chapters.iter().any(|excluded| {
+            let excluded = excluded.trim_start_matches("./").trim_end_matches('/');
+            page == excluded
+                || page
+                    .strip_prefix(excluded)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+/// Indexes scoped to the parts of the book, each generated at the end of
+/// its part as `<part>/tags.md` and so on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PartIndexes {
+    /// Only the book-wide indexes.
+    Off,
+    /// Part indexes besides the book-wide ones, which inline links go to.
+    Also,
+    /// Part indexes instead of the book-wide ones; chapters outside any
+    /// part keep book-wide indexes of their own.
+    Only,
+}
+
+/// What happens to a link whose target can't be found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnresolvedLinks {
+    /// Left as written.
+    Keep,
+    /// Replaced by its text.
+    Text,
+    /// Linked to a stub chapter listing the chapters linking to it. Embeds
+    /// naming nothing are left as written.
+    Stub,
+}
+
+/// How `![[...]]` embeds are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmbedStyle {
+    /// Quoted, as with `>`.
+    Blockquote,
+    /// As if written in place.
+    Plain,
+}
+
+/// Order of the entries on an index page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    Alphabetical,
+    /// Most occurrences first.
+    Count,
+    /// Entries referenced by the most recently modified chapters first.
+    Recent,
+}
+
+/// Order of the chapters listed under an index entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PageOrder {
+    /// By source path.
+    Path,
+    /// By chapter title.
+    Title,
+    /// In reading order.
+    Book,
+    /// Chapters with the most occurrences first.
+    Count,
+    /// Most recently modified chapters first.
+    Recent,
+}
+
+/// Whether front matter is removed from the rendered chapter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FrontMatterMode {
+    /// Removed, so renderers don't show it as text under a horizontal rule.
+    Strip,
+    /// Passed on unchanged for later preprocessors or renderers.
+    Keep,
+}
+
+/// How the metadata block of a chapter is laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MetadataStyle {
+    /// A table of the fields, styled by the class `indexer-metadata`, for the
+    /// HTML renderer; a line for the others.
+    Table,
+    /// A line of fields, like `Author: Ann · Status: draft`.
+    Line,
+}
+
+/// How related chapters are found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RelatedBy {
+    /// Chapters sharing the most tags.
+    Tags,
+    /// Chapters with the most similar words, weighted by TF-IDF.
+    Text,
+}
+
+/// What dead links within the book lead to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeadLinks {
+    /// They aren't checked.
+    Ignore,
+    /// Each is reported.
+    Warn,
+    /// The build fails, listing them.
+    Fail,
+}
+
+/// What is done with unlinked mentions of chapters and people.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnlinkedMentions {
+    /// Nothing.
+    Ignore,
+    /// Each is reported.
+    Warn,
+    /// The first in each chapter is linked to what it names.
+    Link,
+}
+
+/// What is done with the tags suggested for untagged chapters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TagSuggestions {
+    /// No tags are suggested.
+    Ignore,
+    /// The suggestions for each chapter are reported.
+    Warn,
+    /// They are left in an HTML comment at the end of the chapter.
+    Comment,
+}
+
+/// Where mentions link outside the book.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum MentionLinks {
+    /// A URL with `{mention}` standing for the name.
+    Pattern(String),
+    /// A URL per mention.
+    Each(BTreeMap<String, String>),
+}
+
+impl MentionLinks {
+    /// The profile of the mention `name`, ignoring case for a URL per
+    /// mention.
+    pub fn url(&self, name: &str) -> Option<String> {
+        match self {
+            MentionLinks::Pattern(pattern) => Some(pattern.replace("{mention}", name)),
+            MentionLinks::Each(urls) => urls
+                .iter()
+                .find(|(mention, _)| {
+                    mention.trim_start_matches('@').to_lowercase() == name.to_lowercase()
+                })
+                .map(|(_, url)| url.clone()),
+        }
+    }
+}
+
+/// The page of the fediverse handle `handle`, like `user@instance.social`,
+/// on its instance.
+pub(crate) fn handle_url(handle: &str) -> Option<String> {
+    let (user, instance) = handle.split_once('@')?;
+    Some(format!("https://{instance}/@{user}"))
+}
+
+/// How mentions with a profile of `mention_links` link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MentionLinksMode {
+    /// To the profile only.
+    Instead,
+    /// To the mention index, followed by a link to the profile.
+    Alongside,
+}
+
+/// Which keys made only of digits, like `#42`, an entity type takes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NumericKeys {
+    /// Numeric keys are indexed like any other.
+    #[default]
+    Include,
+    /// Numeric keys are not tokens of this type.
+    Skip,
+    /// Only numeric keys are; another type may share the prefix for the rest.
+    Only,
+}
+
+/// An extra kind of prefixed token declared under
+/// `[preprocessor.indexer.entities.<name>]`, e.g. `!decisions` or `+projects`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EntityConfig {
+    pub prefix: char,
+    /// Chapter generated for this index, `<name>.md` by default.
+    pub file: Option<String>,
+    /// Title of the generated chapter, the capitalized name by default.
+    pub title: Option<String>,
+    /// Whether keys may be nested with `/`.
+    #[serde(default)]
+    pub nested: bool,
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    pub allow: Option<Vec<String>>,
+    /// Set to `only` to receive the numeric tokens of a prefix shared with
+    /// another type, e.g. `#42` as an issue while `#rust` stays a tag.
+    #[serde(default)]
+    pub numeric: NumericKeys,
+    /// Whether every key gets its own page, next to `file`.
+    #[serde(default)]
+    pub page_per_entry: bool,
+    /// Text shown on a key's page, keyed by key.
+    #[serde(default)]
+    pub descriptions: BTreeMap<String, String>,
+    /// Keys indexed as another, keyed by alias.
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+}
+
+/// What is known about a mentioned person, declared under
+/// `[preprocessor.indexer.people.<mention>]` or in `people_file`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    /// Full name.
+    pub name: Option<String>,
+    pub role: Option<String>,
+    pub email: Option<String>,
+    pub homepage: Option<String>,
+    /// Picture of the person: a URL, or an image of the source directory.
+    pub avatar: Option<String>,
+    /// Other profile URLs, like a forge account.
+    pub links: Vec<String>,
+    /// Other handles the person is mentioned by, like `robert` for `bob`.
+    pub aliases: Vec<String>,
+    /// Chapters or directories of the source directory where the aliases
+    /// stand for this person, everywhere by default. Two people sharing a
+    /// handle are told apart this way.
+    pub alias_in: Vec<String>,
+}
+
+/// A key indexed as another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias {
+    pub alias: String,
+    pub target: String,
+    /// Chapters or directories the alias is limited to, none for everywhere.
+    pub scope: Vec<String>,
+}
+
+impl Alias {
+    fn unscoped((alias, target): (&String, &String)) -> Self {
+        Alias {
+            alias: alias.clone(),
+            target: target.clone(),
+            scope: Vec::new(),
+        }
+    }
+
+    /// Whether the alias applies in the chapter at `page`, only counting a
+    /// scope that names it.
+    fn scoped_to(&self, page: &str) -> bool {
+        self.scope.iter().any(|scope| {
+            let dir = scope.trim_end_matches('/');
+            page == scope
+                || page
+                    .strip_prefix(dir)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        })
+    }
+}
+
+/// A fully resolved entity type: what to look for and where to index it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityKind {
+    pub name: String,
+    pub prefix: char,
+    pub file: String,
+    pub title: String,
+    /// Keys form a `/`-separated hierarchy; every occurrence also counts for
+    /// each ancestor.
+    pub nested: bool,
+    pub ignore: Vec<String>,
+    pub allow: Option<Vec<String>>,
+    pub numeric: NumericKeys,
+    /// Every listed key gets a page in [`EntityKind::entry_dir`], and `file`
+    /// becomes a list of those pages.
+    pub page_per_entry: bool,
+    pub descriptions: BTreeMap<String, String>,
+    pub profiles: BTreeMap<String, Profile>,
+    pub aliases: Vec<Alias>,
+}
+
+impl EntityKind {
+    /// Directory holding the pages of single keys: `file` without its
+    /// extension, e.g. `tags/` for `tags.md`.
+    pub fn entry_dir(&self) -> &str {
+        self.file.strip_suffix(".md").unwrap_or(&self.file)
+    }
+
+    /// Whether a link to `page` points at one of this type's generated
+    /// chapters.
+    pub fn links_to(&self, page: &str) -> bool {
+        let is = |path: &str, target: &str| {
+            path.strip_suffix(target)
+                .is_some_and(|dir| dir.is_empty() || dir.ends_with('/'))
+        };
+        // Split index pages are named after the index, like `tags-a-f.md`
+        let split = |page: &str| {
+            let (dir, name) = page.rsplit_once('/').unwrap_or(("", page));
+            let file_name = self.file.rsplit('/').next().unwrap_or(&self.file);
+            let stem = file_name.strip_suffix(".md").unwrap_or(file_name);
+            name.strip_prefix(stem)
+                .is_some_and(|rest| rest.starts_with('-') && rest.ends_with(".md"))
+                && is(&format!("{dir}/{file_name}"), &self.file)
+        };
+        is(page, &self.file)
+            || split(page)
+            || (self.page_per_entry
+                && page
+                    .rsplit_once('/')
+                    .is_some_and(|(dir, _)| is(dir, self.entry_dir())))
+    }
+
+    /// The configured description of `key`, matched ignoring case and prefix.
+    pub fn description(&self, key: &str) -> Option<&str> {
+        self.configured(&self.descriptions, key).map(String::as_str)
+    }
+
+    /// The configured profile of `key`, matched ignoring case and prefix.
+    pub fn profile(&self, key: &str) -> Option<&Profile> {
+        self.configured(&self.profiles, key)
+    }
+
+    /// The keys `key` may stand for in the chapter at `page` through an
+    /// alias, matched ignoring case and prefix: those of aliases scoped to
+    /// `page` if any, else those of aliases scoped nowhere.
+    pub fn alias_targets(&self, key: &str, page: &str) -> Vec<&str> {
+        let key = key.to_lowercase();
+        let matching: Vec<&Alias> = self
+            .aliases
+            .iter()
+            .filter(|alias| {
+                let name = alias
+                    .alias
+                    .strip_prefix(self.prefix)
+                    .unwrap_or(&alias.alias);
+                name.to_lowercase() == key
+            })
+            .collect();
+        let scoped: Vec<&Alias> = matching
+            .iter()
+            .copied()
+            .filter(|alias| alias.scoped_to(page))
+            .collect();
+        let chosen = if scoped.is_empty() {
+            matching
+                .into_iter()
+                .filter(|alias| alias.scope.is_empty())
+                .collect()
+        } else {
+            scoped
+        };
+        let mut targets: Vec<&str> = chosen
+            .into_iter()
+            .map(|alias| {
+                alias
+                    .target
+                    .strip_prefix(self.prefix)
+                    .unwrap_or(&alias.target)
+            })
+            .collect();
+        targets.dedup();
+        targets
+    }
+
+    /// The key `key` is indexed as in the chapter at `page`: what its alias
+    /// stands for there, or itself if it isn't one or could be several.
+    /// Below an alias, nested keys move with it, so `js = "javascript"`
+    /// takes `js/react` to `javascript/react`.
+    pub fn canonical(&self, key: &str, page: &str) -> String {
+        let target = |alias: &str| match self.alias_targets(alias, page)[..] {
+            [target] => Some(target),
+            _ => None,
+        };
+        if let Some(target) = target(key) {
+            return target.to_string();
+        }
+        if self.nested {
+            for (end, _) in key.rmatch_indices('/') {
+                if let Some(target) = target(&key[..end]) {
+                    return format!("{target}{}", &key[end..]);
+                }
+            }
+        }
+        key.to_string()
+    }
+
+    fn configured<'a, T>(&self, items: &'a BTreeMap<String, T>, key: &str) -> Option<&'a T> {
+        let key = key.to_lowercase();
+        items
+            .iter()
+            .find(|(item, _)| {
+                item.strip_prefix(self.prefix)
+                    .unwrap_or(item)
+                    .to_lowercase()
+                    == key
+            })
+            .map(|(_, value)| value)
+    }
+
+    /// Whether a token of this type may have `key`, judging by its digits.
+    pub fn takes(&self, key: &str) -> bool {
+        let numeric = key.chars().all(|c| c.is_ascii_digit());
+        match self.numeric {
+            NumericKeys::Include => true,
+            NumericKeys::Skip => !numeric,
+            NumericKeys::Only => numeric,
+        }
+    }
+
+    /// Whether `key` passes the ignore and allow lists. Matching ignores case,
+    /// and listing a nested key also covers everything below it.
+    pub fn accepts(&self, key: &str) -> bool {
+        let key = key.to_lowercase();
+        let listed = |list: &[String]| {
+            list.iter().any(|item| {
+                let item = item
+                    .strip_prefix(self.prefix)
+                    .unwrap_or(item)
+                    .to_lowercase();
+                key == item
+                    || (self.nested
+                        && key
+                            .strip_prefix(&item)
+                            .is_some_and(|rest| rest.starts_with('/')))
+            })
+        };
+        !listed(&self.ignore) && self.allow.as_deref().is_none_or(listed)
+    }
+}
+
+impl Default for IndexerConfig {
+    fn default() -> Self {
+        IndexerConfig {
+            tags: true,
+            mentions: true,
+            tag_prefix: '#',
+            nested_tags: true,
+            page_per_tag: false,
+            tag_descriptions: BTreeMap::new(),
+            tag_aliases: BTreeMap::new(),
+            tag_cloud: false,
+            tag_cloud_file: String::from("tag-cloud.md"),
+            tag_cloud_title: String::from("Tag cloud"),
+            co_occurrence: false,
+            co_occurrence_file: String::from("co-occurrence.md"),
+            co_occurrence_title: String::from("Tags used together"),
+            co_occurrence_pairs: 20,
+            stats: false,
+            stats_file: String::from("stats.md"),
+            stats_title: String::from("Statistics"),
+            graph: false,
+            graph_file: String::from("graph.md"),
+            graph_title: String::from("Knowledge graph"),
+            graph_kinds: None,
+            graph_depth: None,
+            graph_links: true,
+            graph_viewer: false,
+            graph_json: None,
+            graph_dot: None,
+            graph_graphml: None,
+            graph_gexf: None,
+            wikilinks: false,
+            unresolved_wikilinks: UnresolvedLinks::Keep,
+            stub_dir: String::from("stubs"),
+            embeds: false,
+            alias_redirects: false,
+            redirects_file: String::from("moved.md"),
+            redirects_title: String::from("Moved Pages"),
+            zettelkasten: false,
+            zettel_pattern: None,
+            register_file: String::from("register.md"),
+            register_title: String::from("Register"),
+            obsidian: false,
+            embed_style: EmbedStyle::Blockquote,
+            block_references: false,
+            include_snippets: false,
+            snippets_dir: String::from("snippets"),
+            snippets_chapter: None,
+            backlinks: false,
+            related: None,
+            related_by: RelatedBy::Tags,
+            similarity_cache: None,
+            untagged: false,
+            untagged_file: String::from("untagged.md"),
+            untagged_title: String::from("Untagged chapters"),
+            orphans: false,
+            orphans_file: String::from("orphans.md"),
+            orphans_title: String::from("Orphan chapters"),
+            warn_orphans: false,
+            orphans_json: None,
+            glossary: None,
+            glossary_file: String::from("glossary.md"),
+            glossary_title: String::from("Glossary"),
+            acronyms: BTreeMap::new(),
+            detect_acronyms: false,
+            acronym_index: false,
+            acronym_file: String::from("acronyms.md"),
+            acronym_title: String::from("Acronyms"),
+            tasks: false,
+            tasks_file: String::from("tasks.md"),
+            tasks_title: String::from("Tasks"),
+            task_progress: false,
+            kanban: Vec::new(),
+            kanban_file: String::from("board.md"),
+            kanban_title: String::from("Board"),
+            statuses: vec![
+                String::from("draft"),
+                String::from("review"),
+                String::from("published"),
+            ],
+            exclude_statuses: BTreeMap::new(),
+            redact: Vec::new(),
+            private_tag: String::from("private"),
+            redaction_notice: true,
+            profiles: BTreeMap::new(),
+            profile: None,
+            renderer_profiles: BTreeMap::new(),
+            agenda: false,
+            agenda_file: String::from("agenda.md"),
+            agenda_title: String::from("Agenda"),
+            due_marker: String::from("@due"),
+            timeline: false,
+            timeline_file: String::from("timeline.md"),
+            timeline_title: String::from("Timeline"),
+            journal: None,
+            journal_file: String::from("journal.md"),
+            journal_title: String::from("Journal"),
+            reading_list: false,
+            reading_tag: String::from("toread"),
+            reading_list_file: String::from("reading-list.md"),
+            reading_list_title: String::from("Reading list"),
+            quotes: false,
+            quote_tag: None,
+            quotes_file: String::from("quotes.md"),
+            quotes_title: String::from("Quotes"),
+            endnotes: false,
+            endnotes_file: String::from("endnotes.md"),
+            endnotes_title: String::from("Notes"),
+            callouts: false,
+            callout_kinds: None,
+            callouts_file: String::from("callouts.md"),
+            callouts_title: String::from("Callouts"),
+            images: false,
+            image_thumbnails: true,
+            images_file: String::from("images.md"),
+            images_title: String::from("Images"),
+            tables: false,
+            tables_file: String::from("tables.md"),
+            tables_title: String::from("List of Tables"),
+            external_links: false,
+            external_links_file: String::from("external-links.md"),
+            external_links_title: String::from("External Links"),
+            cross_references: false,
+            headings_json: None,
+            symbols: false,
+            symbol_pattern: None,
+            symbols_file: String::from("symbols.md"),
+            symbols_title: String::from("Symbols"),
+            bibliography: None,
+            references_file: String::from("references.md"),
+            references_title: String::from("References"),
+            link_identifiers: false,
+            sources: false,
+            sources_file: String::from("sources.md"),
+            sources_title: String::from("Sources"),
+            issue_repository: None,
+            commit_repository: None,
+            commit_pattern: None,
+            commits: false,
+            commits_file: String::from("commits.md"),
+            commits_title: String::from("Referenced Commits"),
+            ticket_url: None,
+            ticket_pattern: None,
+            tickets: false,
+            tickets_file: String::from("tickets.md"),
+            tickets_title: String::from("Tickets"),
+            interwiki: BTreeMap::new(),
+            interwiki_references: false,
+            interwiki_file: String::from("interwiki.md"),
+            interwiki_title: String::from("External References"),
+            site_url: None,
+            workspace_json: None,
+            workspace: BTreeMap::new(),
+            contacts: false,
+            obfuscate_emails: false,
+            contacts_file: String::from("contacts.md"),
+            contacts_title: String::from("Contacts"),
+            locations: false,
+            location_tag: String::from("loc"),
+            places: BTreeMap::new(),
+            locations_file: String::from("locations.md"),
+            locations_title: String::from("Locations"),
+            location_map: false,
+            location_map_file: String::from("map.md"),
+            location_map_title: String::from("Map"),
+            events: false,
+            events_file: String::from("events.md"),
+            events_title: String::from("Events"),
+            flashcards: false,
+            flashcard_tag: String::from("flashcard"),
+            flashcards_file: String::from("flashcards.md"),
+            flashcards_title: String::from("Flashcards"),
+            flashcards_tsv: None,
+            master_index: false,
+            master_index_file: String::from("general-index.md"),
+            master_index_title: String::from("Index"),
+            skip_numeric_tags: true,
+            ignore: Vec::new(),
+            allow: None,
+            tags_file: String::from("tags.md"),
+            tags_title: String::from("Tags"),
+            mention_prefix: '@',
+            mentions_file: String::from("mentions.md"),
+            mentions_title: String::from("Mentions"),
+            page_per_mention: false,
+            people: BTreeMap::new(),
+            people_file: None,
+            warn_unknown_people: false,
+            mention_links: None,
+            mention_links_mode: MentionLinksMode::Instead,
+            mention_ignore: Vec::new(),
+            mention_allow: None,
+            min_occurrences: 1,
+            link_rare: true,
+            counts: false,
+            count_badges: false,
+            snippets: false,
+            deep_links: false,
+            list_occurrences: false,
+            letter_nav: false,
+            collapse: None,
+            split_entries: None,
+            labels: Labels::default(),
+            placement: Placement::default(),
+            part_indexes: PartIndexes::Off,
+            sort: SortOrder::Alphabetical,
+            page_sort: PageOrder::Path,
+            breadcrumbs: false,
+            case: CaseMode::FirstSeen,
+            escape: '\\',
+            front_matter: FrontMatterMode::Strip,
+            metadata: false,
+            metadata_fields: ["author", "status", "created", "updated", "tags"]
+                .map(String::from)
+                .to_vec(),
+            metadata_labels: BTreeMap::new(),
+            metadata_style: MetadataStyle::Table,
+            queries: false,
+            see_also: Vec::new(),
+            expand_variables: false,
+            variables: BTreeMap::new(),
+            templates: None,
+            dead_links: DeadLinks::Ignore,
+            unlinked_mentions: UnlinkedMentions::Ignore,
+            tag_suggestions: TagSuggestions::Ignore,
+            suggested_tags: 5,
+            entities: BTreeMap::new(),
+        }
+    }
+}
+
+/// Keys of a preprocessor's table that mdBook reads for itself.
+const MDBOOK_KEYS: [&str; 5] = ["command", "renderers", "before", "after", "optional"];
+
+impl IndexerConfig {
+    /// Reads the `[preprocessor.<name>]` table from the book configuration.
+    /// People from `people_file` are added to `people`.
+    pub fn from_context(ctx: &PreprocessorContext, name: &str) -> Result<Self, Error> {
+        let mut config: IndexerConfig = match ctx.config.get_preprocessor(name) {
+            Some(table) => {
+                let mut table = table.clone();
+                // What mdBook reads for itself
+                for key in MDBOOK_KEYS {
+                    table.remove(key);
+                }
+                if table.get("obsidian").and_then(toml::Value::as_bool) == Some(true) {
+                    // What a vault relies on, short of being turned off
+                    for key in ["wikilinks", "embeds", "block_references", "nested_tags"] {
+                        table.entry(key).or_insert(toml::Value::Boolean(true));
+                    }
+                }
+                toml::Value::Table(table)
+                    .try_into()
+                    .with_context(|| format!("invalid [preprocessor.{name}] configuration"))?
+            }
+            None => IndexerConfig::default(),
+        };
+        if let Some(file) = &config.people_file {
+            for (mention, profile) in load_people(file, &ctx.root)? {
+                config.people.entry(mention).or_insert(profile);
+            }
+        }
+        Ok(config)
+    }
+
+    /// What the build for `renderer` leaves out: its profile, `profile` or
+    /// the one `renderer_profiles` gives it, along with the statuses
+    /// `exclude_statuses` gives it and private parts if it is in `redact`.
+    pub fn build_profile(&self, renderer: &str) -> Result<BuildProfile, Error> {
+        let name = self
+            .profile
+            .as_ref()
+            .or_else(|| self.renderer_profiles.get(renderer));
+        let mut build = match name {
+            Some(name) => self
+                .profiles
+                .get(name)
+                .cloned()
+                .with_context(|| format!("profile `{name}` is not one of profiles"))?,
+            None => BuildProfile::default(),
+        };
+        let statuses = self.exclude_statuses.get(renderer).into_iter().flatten();
+        build.exclude_statuses.extend(statuses.cloned());
+        build.redact |= self.redact.iter().any(|redacted| redacted == renderer);
+        Ok(build)
+    }
+
+    /// The repository commits link to, if any.
+    pub fn commit_repository(&self) -> Option<&str> {
+        self.commit_repository
+            .as_deref()
+            .or(self.issue_repository.as_deref())
+            .map(|repository| repository.trim_end_matches('/'))
+    }
+
+    /// Where the ticket `key` links to, if tickets link anywhere.
+    pub fn ticket_url(&self, key: &str) -> Option<String> {
+        let url = self.ticket_url.as_deref()?;
+        if url.contains("{key}") {
+            Some(url.replace("{key}", key))
+        } else {
+            Some(format!("{url}{key}"))
+        }
+    }
+
+    /// Where the page `key` of the `interwiki` source `source` is.
+    pub fn interwiki_url(&self, source: &str, key: &str) -> Option<String> {
+        let url = self.interwiki.get(source)?;
+        if url.contains("{key}") {
+            Some(url.replace("{key}", key))
+        } else {
+            Some(format!("{url}{key}"))
+        }
+    }
+
+    /// The profile outside the book the mention `name` links to, from
+    /// `mention_links`, or for a fediverse handle like
+    /// `user@instance.social`, its page on its instance.
+    pub fn profile_url(&self, name: &str) -> Option<String> {
+        let configured = self
+            .mention_links
+            .as_ref()
+            .and_then(|links| links.url(name));
+        configured.or_else(|| handle_url(name))
+    }
+
+    /// Whether a graph chapter or export is asked for.
+    pub fn builds_graph(&self) -> bool {
+        self.graph
+            || self.graph_json.is_some()
+            || self.graph_dot.is_some()
+            || self.graph_graphml.is_some()
+            || self.graph_gexf.is_some()
+    }
+
+    /// Whether orphans are reported in any way.
+    pub fn reports_orphans(&self) -> bool {
+        self.orphans || self.warn_orphans || self.orphans_json.is_some()
+    }
+
+    /// Every enabled entity type to index: tags and mentions first, then the
+    /// configured extras in name order.
+    pub fn entity_kinds(&self) -> Result<Vec<EntityKind>, Error> {
+        let mut kinds = Vec::new();
+        if self.tags {
+            kinds.push(EntityKind {
+                name: String::from("tags"),
+                prefix: self.tag_prefix,
+                file: self.tags_file.clone(),
+                title: self.tags_title.clone(),
+                nested: self.nested_tags,
+                ignore: self.ignore.clone(),
+                allow: self.allow.clone(),
+                numeric: if self.skip_numeric_tags {
+                    NumericKeys::Skip
+                } else {
+                    NumericKeys::Include
+                },
+                page_per_entry: self.page_per_tag,
+                descriptions: self.tag_descriptions.clone(),
+                profiles: BTreeMap::new(),
+                aliases: self.tag_aliases.iter().map(Alias::unscoped).collect(),
+            });
+        }
+        if self.mentions {
+            kinds.push(EntityKind {
+                name: String::from("mentions"),
+                prefix: self.mention_prefix,
+                file: self.mentions_file.clone(),
+                title: self.mentions_title.clone(),
+                nested: false,
+                ignore: self.mention_ignore.clone(),
+                allow: self.mention_allow.clone(),
+                numeric: NumericKeys::Include,
+                page_per_entry: self.page_per_mention,
+                descriptions: BTreeMap::new(),
+                profiles: self.people.clone(),
+                aliases: self
+                    .people
+                    .iter()
+                    .flat_map(|(mention, profile)| {
+                        profile.aliases.iter().map(|alias| Alias {
+                            alias: alias.clone(),
+                            target: mention.clone(),
+                            scope: profile.alias_in.clone(),
+                        })
+                    })
+                    .collect(),
+            });
+        }
+
+        for (name, entity) in &self.entities {
+            kinds.push(EntityKind {
+                name: name.clone(),
+                prefix: entity.prefix,
+                file: entity.file.clone().unwrap_or_else(|| format!("{name}.md")),
+                title: entity.title.clone().unwrap_or_else(|| capitalize(name)),
+                nested: entity.nested,
+                ignore: entity.ignore.clone(),
+                allow: entity.allow.clone(),
+                numeric: entity.numeric,
+                page_per_entry: entity.page_per_entry,
+                descriptions: entity.descriptions.clone(),
+                profiles: BTreeMap::new(),
+                aliases: entity.aliases.iter().map(Alias::unscoped).collect(),
+            });
+        }
+
+        if self.agenda {
+            // `@due(…)` dates a task instead of mentioning anyone
+            for kind in &mut kinds {
+                if let Some(key) = self.due_marker.strip_prefix(kind.prefix) {
+                    kind.ignore.push(key.to_string());
+                }
+            }
+        }
+
+        if !self.redact.is_empty() || self.profiles.values().any(|profile| profile.redact) {
+            // Indexing what marks a part as private would publish it
+            if let Some(kind) = kinds.iter_mut().find(|kind| kind.name == "tags") {
+                kind.ignore.push(self.private_tag.clone());
+            }
+        }
+
+        for (i, kind) in kinds.iter().enumerate() {
+            // Two types may only share a prefix by splitting numeric keys
+            let clashes = |other: &EntityKind| {
+                other.prefix == kind.prefix
+                    && ((other.numeric == NumericKeys::Only) == (kind.numeric == NumericKeys::Only)
+                        || kinds.iter().filter(|k| k.prefix == kind.prefix).count() > 2)
+            };
+            if let Some(other) = kinds[..i].iter().find(|k| clashes(k)) {
+                anyhow::bail!(
+                    "entity types `{}` and `{}` both use the prefix `{}`",
+                    other.name,
+                    kind.name,
+                    kind.prefix
+                );
+            }
+            // A type taking every key would get the numeric ones first
+            let shadows = |all: &EntityKind, only: &EntityKind| {
+                all.prefix == only.prefix
+                    && all.numeric == NumericKeys::Include
+                    && only.numeric == NumericKeys::Only
+            };
+            if let Some(other) = kinds[..i]
+                .iter()
+                .find(|k| shadows(k, kind) || shadows(kind, k))
+            {
+                let (all, only) = if kind.numeric == NumericKeys::Only {
+                    (other, kind)
+                } else {
+                    (kind, other)
+                };
+                let fix = if all.name == "tags" {
+                    String::from("skip_numeric_tags = true")
+                } else {
+                    format!("numeric = \"skip\" for `{}`", all.name)
+                };
+                anyhow::bail!(
+                    "entity type `{}` only takes numeric keys like `{}42`, but `{}` takes them \
+                     first; set {fix}",
+                    only.name,
+                    kind.prefix,
+                    all.name
+                );
+            }
+            if let Some(other) = kinds[..i].iter().find(|k| k.file == kind.file) {
+                anyhow::bail!(
+                    "entity types `{}` and `{}` both write to `{}`",
+                    other.name,
+                    kind.name,
+                    kind.file
+                );
+            }
+        }
+
+        Ok(kinds)
+    }
+}
+
+/// The people declared in `file`, a TOML or YAML file under `root`, keyed
+/// by mention.
+fn load_people(file: &str, root: &Path) -> Result<BTreeMap<String, Profile>, Error> {
+    let path = root.join(file);
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("couldn't read the people file {}", path.display()))?;
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => {
+            toml::from_str(&text).with_context(|| format!("invalid people file `{file}`"))
+        }
+        Some("yaml" | "yml") => {
+            serde_yaml::from_str(&text).with_context(|| format!("invalid people file `{file}`"))
+        }
+        _ => anyhow::bail!("the people file `{file}` should be a TOML or YAML file"),
+    }
+}
+
+pub(crate) fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
@@ -0,0 +1,203 @@
+use crate::extract::{parser, snippet};
+use mdbook::utils::unique_id_from_content;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::collections::HashMap;
+use std::env;
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A date written in a chapter.
+#[derive(Debug, Clone)]
+pub(crate) struct Dated {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    /// Path of the chapter.
+    pub page: String,
+    /// Id of the heading the date comes under, empty for the top of the
+    /// chapter.
+    pub anchor: String,
+    /// The line the date was found in, empty for the front matter.
+    pub snippet: String,
+}
+
+/// Whether `text` is a date written `YYYY-MM-DD`.
+pub(crate) fn is_iso_date(text: &str) -> bool {
+    let parts: Vec<&str> = text.split('-').collect();
+    let number = |part: &str, len: usize| {
+        (part.len() == len && part.chars().all(|c| c.is_ascii_digit()))
+            .then(|| part.parse::<u32>().ok())
+            .flatten()
+    };
+    match parts[..] {
+        [year, month, day] => {
+            number(year, 4).is_some()
+                && number(month, 2).is_some_and(|month| (1..=12).contains(&month))
+                && number(day, 2).is_some_and(|day| (1..=31).contains(&day))
+        }
+        _ => false,
+    }
+}
+
+/// The dates written `YYYY-MM-DD` in the text and headings of `content`,
+/// the body of the chapter at `page`, in order. Code and HTML are left out.
+pub(crate) fn written(content: &str, page: &str) -> Vec<Dated> {
+    let mut found = Vec::new();
+    let mut counts = HashMap::new();
+    let mut anchor = String::new();
+    let mut in_code = false;
+    // Explicit id, text and dates of the heading being read
+    let mut heading: Option<(Option<String>, String, Vec<Dated>)> = None;
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::MetadataBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock | TagEnd::MetadataBlock(_)) => in_code = false,
+            Event::Start(Tag::Heading { id, .. }) => {
+                heading = Some((id.map(|id| id.into_string()), String::new(), Vec::new()));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((id, text, dates)) = heading.take() {
+                    anchor = id.unwrap_or_else(|| unique_id_from_content(&text, &mut counts));
+                    found.extend(dates.into_iter().map(|dated| Dated {
+                        anchor: anchor.clone(),
+                        ..dated
+                    }));
+                }
+            }
+            Event::Code(text) => {
+                if let Some((_, heading, _)) = &mut heading {
+                    heading.push_str(&text);
+                }
+            }
+            Event::Text(text) if !in_code => {
+                let dates = iso_dates(&text).into_iter().map(|at| Dated {
+                    date: text[at].to_string(),
+                    page: page.to_string(),
+                    anchor: anchor.clone(),
+                    snippet: snippet(content, range.clone()),
+                });
+                match &mut heading {
+                    Some((_, heading, pending)) => {
+                        heading.push_str(&text);
+                        pending.extend(dates);
+                    }
+                    None => found.extend(dates),
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Where `text` has a date written `YYYY-MM-DD`, standing alone.
+fn iso_dates(text: &str) -> Vec<Range<usize>> {
+    const LEN: usize = "YYYY-MM-DD".len();
+    let joined = |c: char| c.is_alphanumeric() || c == '-';
+    let mut dates = Vec::new();
+    let mut at = 0;
+    while at + LEN <= text.len() {
+        let end = at + LEN;
+        if text.is_char_boundary(at)
+            && text.is_char_boundary(end)
+            && is_iso_date(&text[at..end])
+            && !text[..at].chars().next_back().is_some_and(joined)
+            && !text[end..].chars().next().is_some_and(joined)
+        {
+            dates.push(at..end);
+            at = end;
+        } else {
+            at += 1;
+        }
+    }
+    dates
+}
+
+/// The dates `text` gives after `marker`, as in `@due(2025-03-01)`, in
+/// order.
+pub(crate) fn marked(text: &str, marker: &str) -> Vec<String> {
+    let opening = format!("{marker}(");
+    text.match_indices(&opening)
+        .filter_map(|(at, _)| {
+            let rest = &text[at + opening.len()..];
+            let (date, _) = rest.split_once(')')?;
+            let date = date.trim();
+            is_iso_date(date).then(|| date.to_string())
+        })
+        .collect()
+}
+
+/// The date of the build, `YYYY-MM-DD` in UTC: that of `SOURCE_DATE_EPOCH`
+/// when set, for builds to be reproducible, or today's.
+pub(crate) fn today() -> String {
+    let seconds = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.trim().parse::<i64>().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_secs() as i64)
+        });
+    let (year, month, day) = civil(seconds.div_euclid(86_400));
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// The year, month and day of `date`, written `YYYY-MM-DD`.
+pub(crate) fn parse(date: &str) -> Option<(i64, u32, u32)> {
+    if !is_iso_date(date) {
+        return None;
+    }
+    Some((
+        date[..4].parse().ok()?,
+        date[5..7].parse().ok()?,
+        date[8..].parse().ok()?,
+    ))
+}
+
+/// Number of days from 1970-01-01 to the given day, the inverse of
+/// [`civil`].
+pub(crate) fn days(year: i64, month: u32, day: u32) -> i64 {
+    let year = year - i64::from(month <= 2);
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let shifted_month = i64::from((month + 9) % 12);
+    let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Day of the week of the given day, from 0 for Monday to 6 for Sunday.
+pub(crate) fn weekday(year: i64, month: u32, day: u32) -> usize {
+    // 1970-01-01 was a Thursday
+    (days(year, month, day) + 3).rem_euclid(7) as usize
+}
+
+/// Number of days in the given month.
+pub(crate) fn month_len(year: i64, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    (days(next_year, next_month, 1) - days(year, month, 1)) as u32
+}
+
+/// The year, month and day `days` after 1970-01-01, in the proleptic
+/// Gregorian calendar.
+fn civil(days: i64) -> (i64, u32, u32) {
+    // Counted from 0000-03-01, so that leap days end the year
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
@@ -0,0 +1,156 @@
+use crate::extract::parser;
+use crate::paths::{is_relative_link, percent_decode, resolve_link};
+use mdbook::book::{Book, BookItem, Chapter};
+use mdbook::utils::unique_id_from_content;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// A link of the book that leads nowhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DeadLink {
+    /// Chapter the link is written in.
+    pub page: String,
+    pub link: String,
+    /// What is missing, e.g. ``no chapter or file `setup.md` ``.
+    pub reason: String,
+}
+
+/// Every Markdown link or image of `book`, authored or generated, that is
+/// relative but names neither a chapter nor a file of `src_dir`, or an id
+/// its chapter doesn't have. Ids are those the HTML renderer gives headings,
+/// along with explicit `{#id}` attributes and `id`s of raw HTML.
+pub(crate) fn find(book: &Book, src_dir: &Path) -> Vec<DeadLink> {
+    let chapters: HashMap<String, &Chapter> = book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chapter) => Some(chapter),
+            _ => None,
+        })
+        .filter_map(|chapter| {
+            let path = chapter.path.as_ref()?.to_string_lossy().to_string();
+            Some((path, chapter))
+        })
+        .collect();
+    let ids: HashMap<&str, HashSet<String>> = chapters
+        .iter()
+        .map(|(page, chapter)| (page.as_str(), page_ids(&chapter.content)))
+        .collect();
+
+    let mut dead = Vec::new();
+    let mut pages: Vec<&String> = chapters.keys().collect();
+    pages.sort();
+    for page in pages {
+        for link in links(&chapters[page].content) {
+            if !is_relative_link(&link) {
+                continue;
+            }
+            let (path, anchor) = link.split_once('#').unwrap_or((&link, ""));
+            let path = path.split('?').next().unwrap_or(path);
+            let target = if path.is_empty() {
+                Some(page.clone())
+            } else {
+                resolve_link(Path::new(page), path).map(|target| chapter_at(&chapters, target))
+            };
+            let reason = match target {
+                None => Some(format!("`{path}` leaves the source directory")),
+                Some(target) if chapters.contains_key(&target) => {
+                    let anchor = percent_decode(anchor);
+                    (!anchor.is_empty() && !ids[target.as_str()].contains(&anchor))
+                        .then(|| format!("no `#{anchor}` in `{target}`"))
+                }
+                Some(target) if src_dir.join(&target).exists() => None,
+                Some(target) => Some(format!("no chapter or file `{target}`")),
+            };
+            if let Some(reason) = reason {
+                dead.push(DeadLink {
+                    page: page.clone(),
+                    link,
+                    reason,
+                });
+            }
+        }
+    }
+    dead
+}
+
+/// The chapter `target` stands for: itself, or for a link to `README.md` or
+/// an `.html` page, the chapter mdBook serves there.
+fn chapter_at(chapters: &HashMap<String, &Chapter>, target: String) -> String {
+    let served = if let Some(stem) = target.strip_suffix(".html") {
+        format!("{stem}.md")
+    } else if let Some(dir) = target.strip_suffix("README.md") {
+        format!("{dir}index.md")
+    } else {
+        return target;
+    };
+    if !chapters.contains_key(&target) && chapters.contains_key(&served) {
+        served
+    } else {
+        target
+    }
+}
+
+/// Destinations of the Markdown links and images of `content`.
+fn links(content: &str) -> Vec<String> {
+    parser(content)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => {
+                Some(dest_url.into_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The ids a link into `content` may point at.
+fn page_ids(content: &str) -> HashSet<String> {
+    let mut ids = HashSet::new();
+    let mut counts = HashMap::new();
+    let mut heading: Option<(Option<String>, String)> = None;
+    for event in parser(content) {
+        match event {
+            Event::Start(Tag::Heading { id, .. }) => {
+                heading = Some((id.map(|id| id.into_string()), String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, heading)) = &mut heading {
+                    heading.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => match heading.take() {
+                Some((Some(id), _)) => {
+                    ids.insert(id);
+                }
+                Some((None, text)) => {
+                    ids.insert(unique_id_from_content(&text, &mut counts));
+                }
+                None => {}
+            },
+            Event::Html(html) | Event::InlineHtml(html) => ids.extend(html_ids(&html)),
+            _ => {}
+        }
+    }
+    ids
+}
+
+/// Values of the `id` attributes in a piece of HTML.
+fn html_ids(html: &str) -> Vec<String> {
+    let mut ids = Vec::new();
+    let mut rest = html;
+    while let Some(at) = rest.find("id=") {
+        let before = rest[..at].chars().next_back();
+        rest = &rest[at + 3..];
+        if before.is_some_and(|c| !c.is_whitespace()) {
+            continue;
+        }
+        let Some(quote) = rest.chars().next().filter(|c| matches!(c, '"' | '\'')) else {
+            continue;
+        };
+        if let Some(end) = rest[1..].find(quote) {
+            ids.push(rest[1..1 + end].to_string());
+            rest = &rest[1 + end..];
+        }
+    }
+    ids
+}
@@ -0,0 +1,97 @@
+use crate::autolinks;
+use crate::extract::parser;
+use pulldown_cmark::{Event, Tag};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b")
+        .expect("the email pattern is valid")
+});
+
+/// An address [`obfuscate`] spelled for a renderer other than HTML.
+static SPELLED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b[A-Za-z0-9_%+-]+(?: \[dot\] [A-Za-z0-9_%+-]+)* \[at\] [A-Za-z0-9-]+(?: \[dot\] [A-Za-z0-9-]+)+\b",
+    )
+    .expect("the spelled email pattern is valid")
+});
+
+/// An address [`obfuscate`] encoded for the HTML renderer.
+static ENCODED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<a href="&#109;&#97;&#105;&#108;&#116;&#111;&#58;((?:&#\d+;)+)">(?:&#\d+;)+</a>"#)
+        .expect("the encoded email pattern is valid")
+});
+
+/// A character reference of an encoded address.
+static REFERENCE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"&#(\d+);").expect("the reference pattern is valid"));
+
+/// The addresses written out in `content`, outside code, HTML and links, but
+/// for those right after one of `prefixes`, like fediverse handles.
+pub(crate) fn find(content: &str, prefixes: &[char]) -> Vec<(Range<usize>, String)> {
+    autolinks::find(content, &EMAIL, prefixes)
+}
+
+/// The addresses `mailto:` links of `content` point at.
+pub(crate) fn mailto(content: &str) -> Vec<String> {
+    parser(content)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                let address = dest_url.strip_prefix("mailto:")?;
+                let address = address.split('?').next().unwrap_or(address);
+                EMAIL.is_match(address).then(|| address.to_string())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// The addresses an earlier run obfuscated in `content`, outside code, with
+/// where they are spelled.
+pub(crate) fn obfuscated(content: &str) -> Vec<(Range<usize>, String)> {
+    let code: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| {
+            matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Code(_)).then_some(range)
+        })
+        .collect();
+    let spelled = SPELLED.find_iter(content).map(|found| {
+        let address = found
+            .as_str()
+            .replace(" [at] ", "@")
+            .replace(" [dot] ", ".");
+        (found.range(), address)
+    });
+    let encoded = ENCODED.captures_iter(content).filter_map(|captures| {
+        let address = REFERENCE
+            .captures_iter(&captures[1])
+            .map(|reference| reference[1].parse().ok().and_then(char::from_u32))
+            .collect::<Option<String>>()?;
+        Some((captures.get(0)?.range(), address))
+    });
+    let mut found: Vec<(Range<usize>, String)> = spelled
+        .chain(encoded)
+        .filter(|(range, address)| {
+            EMAIL.is_match(address) && !code.iter().any(|r| r.contains(&range.start))
+        })
+        .collect();
+    found.sort_by_key(|(range, _)| range.start);
+    found
+}
+
+/// `address` spelled so that harvesters don't read it: as HTML character
+/// references in a `mailto:` link for the HTML renderer, else as in
+/// `alice [at] example [dot] com`.
+pub(crate) fn obfuscate(address: &str, html: bool) -> String {
+    if html {
+        let encoded: String = address
+            .chars()
+            .map(|c| format!("&#{};", u32::from(c)))
+            .collect();
+        format!("<a href=\"&#109;&#97;&#105;&#108;&#116;&#111;&#58;{encoded}\">{encoded}</a>")
+    } else {
+        address.replace('@', " [at] ").replace('.', " [dot] ")
+    }
+}
@@ -0,0 +1,204 @@
+use crate::config::{EmbedStyle, UnresolvedLinks};
+use crate::extract::parser;
+use crate::generate::ChapterInfo;
+use crate::paths::{is_relative_link, relative_link, resolve_link};
+use crate::wikilinks::resolve;
+use mdbook::utils::normalize_id;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Chapters and settings embeds are expanded from.
+pub(crate) struct Embedder<'a> {
+    /// Body of every chapter, by path, as written.
+    pub sources: &'a HashMap<String, String>,
+    pub chapters: &'a HashMap<String, ChapterInfo>,
+    pub style: EmbedStyle,
+    pub unresolved: UnresolvedLinks,
+}
+
+impl Embedder<'_> {
+    /// `content` of the chapter at `page` with every line made of a
+    /// `![[Target]]` or `![[Target#Heading]]` embed replaced by the chapter,
+    /// or the section below that heading, it names. Embeds are expanded
+    /// recursively; one that would embed a chapter into itself is reported
+    /// and left alone, like an embed naming nothing.
+    pub fn expand(&self, content: &str, page: &str) -> String {
+        self.expand_within(content, page, &mut vec![(page.to_string(), None)])
+    }
+
+    /// Expands the embeds of `content`, from the chapter at `page`, that
+    /// don't name one of the chapters or sections of `stack`.
+    fn expand_within(
+        &self,
+        content: &str,
+        page: &str,
+        stack: &mut Vec<(String, Option<String>)>,
+    ) -> String {
+        let code: Vec<_> = parser(content)
+            .into_offset_iter()
+            .filter_map(|(event, range)| {
+                matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Html(_)).then_some(range)
+            })
+            .collect();
+
+        let mut expanded = String::with_capacity(content.len());
+        let mut offset = 0;
+        for line in content.split_inclusive('\n') {
+            let start = offset;
+            offset += line.len();
+            let embed = line
+                .trim()
+                .strip_prefix("![[")
+                .and_then(|rest| rest.strip_suffix("]]"))
+                .filter(|inner| !inner.contains(['[', ']']));
+            let Some(inner) = embed.filter(|_| !code.iter().any(|r| r.contains(&start))) else {
+                expanded.push_str(line);
+                continue;
+            };
+
+            let target = inner.split_once('|').map_or(inner, |(target, _)| target);
+            let (target, heading) = match target.split_once('#') {
+                Some((target, heading)) => (target.trim(), Some(heading.trim())),
+                None => (target.trim(), None),
+            };
+            let found = if target.is_empty() {
+                Some(page)
+            } else {
+                resolve(target, self.chapters)
+            };
+            let Some(found) = found else {
+                eprintln!("Warning: the embed ![[{inner}]] in {page} names no chapter");
+                match self.unresolved {
+                    UnresolvedLinks::Keep | UnresolvedLinks::Stub => expanded.push_str(line),
+                    UnresolvedLinks::Text => expanded.push_str(&format!("{}\n", inner.trim())),
+                }
+                continue;
+            };
+            let source = self.sources.get(found).map_or("", String::as_str);
+            let section = match heading {
+                Some(heading) => section(source, heading),
+                None => Some(source),
+            };
+            let embedding = (found.to_string(), heading.map(String::from));
+            let Some(section) = section.filter(|_| !stack.contains(&embedding)) else {
+                eprintln!("Warning: the embed ![[{inner}]] in {page} can't be expanded");
+                expanded.push_str(line);
+                continue;
+            };
+
+            stack.push(embedding);
+            let nested = self.expand_within(section, found, stack);
+            stack.pop();
+            let nested = rebase_links(&nested, found, page);
+            expanded.push_str(&self.wrap(nested.trim_end()));
+        }
+        expanded
+    }
+
+    fn wrap(&self, content: &str) -> String {
+        match self.style {
+            EmbedStyle::Plain => format!("\n{content}\n\n"),
+            EmbedStyle::Blockquote => {
+                let quoted: Vec<String> = content
+                    .lines()
+                    .map(|line| {
+                        if line.is_empty() {
+                            String::from(">")
+                        } else {
+                            format!("> {line}")
+                        }
+                    })
+                    .collect();
+                format!("\n{}\n\n", quoted.join("\n"))
+            }
+        }
+    }
+}
+
+/// The part of `content` from the heading named `heading`, matched by id
+/// or text, up to the next heading of the same or a higher level.
+pub(crate) fn section<'a>(content: &'a str, heading: &str) -> Option<&'a str> {
+    let wanted = normalize_id(heading);
+    let mut found = None;
+    let mut current = None;
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                if let Some((found_level, start)) = found {
+                    if level <= found_level {
+                        return Some(&content[start..range.start]);
+                    }
+                } else if id.as_deref() == Some(heading) {
+                    found = Some((level, range.start));
+                } else {
+                    current = Some((level, range.start, String::new()));
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, title)) = &mut current {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, start, title)) = current.take() {
+                    if found.is_none() && normalize_id(&title) == wanted {
+                        found = Some((level, start));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    found.map(|(_, start)| &content[start..])
+}
+
+/// `content` written in the chapter at `from` with its relative links and
+/// images changed to work from the chapter at `to`.
+pub(crate) fn rebase_links(content: &str, from: &str, to: &str) -> String {
+    if from == to {
+        return content.to_string();
+    }
+    let mut replacements = Vec::new();
+    for (event, range) in parser(content).into_offset_iter() {
+        let dest = match event {
+            Event::Start(Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. }) => dest_url,
+            _ => continue,
+        };
+        if dest.is_empty() || !is_relative_link(&dest) {
+            continue;
+        }
+        let Some(at) = content[range.clone()].find(&format!("]({dest}")) else {
+            continue;
+        };
+        let (path, fragment) = match dest.find('#') {
+            Some(i) => (&dest[..i], &dest[i..]),
+            None => (&dest[..], ""),
+        };
+        let target = if path.is_empty() {
+            from.to_string()
+        } else {
+            let Some(target) = resolve_link(Path::new(from), path) else {
+                continue;
+            };
+            target
+        };
+        let rebased = relative_link(Path::new(to), Path::new(&target));
+        let start = range.start + at + 2;
+        replacements.push((start..start + dest.len(), format!("{rebased}{fragment}")));
+    }
+
+    let mut rebased = String::with_capacity(content.len());
+    let mut last = 0;
+    replacements.sort_by_key(|(range, _)| range.start);
+    for (range, replacement) in replacements {
+        if range.start < last {
+            continue;
+        }
+        rebased.push_str(&content[last..range.start]);
+        rebased.push_str(&replacement);
+        last = range.end;
+    }
+    rebased.push_str(&content[last..]);
+    rebased
+}
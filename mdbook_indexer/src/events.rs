@@ -0,0 +1,112 @@
+use crate::autolinks;
+use crate::dates::is_iso_date;
+use crate::extract::parser;
+use crate::generate::slug;
+use pulldown_cmark::Tag;
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// `@event(2025-06-10, RustConf)`.
+static EVENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"@event\(\s*\d{4}-\d{2}-\d{2}\s*,[^)\n]+\)").expect("the event pattern is valid")
+});
+
+/// A marker an earlier run showed, after the anchor of its event:
+/// `<a id="event-2025-06-10-rustconf"></a>RustConf (2025-06-10)`.
+static SHOWN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"<a id="(event-[^"]+)"></a>([^\n]+?) \((\d{4}-\d{2}-\d{2})\)"#)
+        .expect("the shown event pattern is valid")
+});
+
+/// An event a chapter describes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Event {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    /// As written, in Markdown.
+    pub name: String,
+    /// Path of the chapter.
+    pub page: String,
+    /// Id of the anchor the marker leaves in the chapter.
+    pub anchor: String,
+}
+
+/// The anchor of the event `name` held on `date`.
+fn anchor(date: &str, name: &str) -> String {
+    format!("event-{date}-{}", slug(name))
+}
+
+/// `content`, the body of the chapter at `page`, with every event marker
+/// outside code, HTML and links shown as its name and date, after an anchor
+/// for the first marker of each event. Returns the events, in order, those
+/// an earlier run showed included.
+pub(crate) fn rewrite(content: &str, page: &str) -> (String, Vec<Event>) {
+    let code: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| {
+            matches!(
+                event,
+                pulldown_cmark::Event::Start(Tag::CodeBlock(_)) | pulldown_cmark::Event::Code(_)
+            )
+            .then_some(range)
+        })
+        .collect();
+    let shown: Vec<(usize, Event)> = SHOWN
+        .captures_iter(content)
+        .filter_map(|captures| {
+            let start = captures.get(0)?.start();
+            let (date, name) = (&captures[3], captures[2].trim());
+            let event = Event {
+                date: date.to_string(),
+                name: name.to_string(),
+                page: page.to_string(),
+                anchor: anchor(date, name),
+            };
+            let is_shown = event.anchor == captures[1] && !code.iter().any(|r| r.contains(&start));
+            is_shown.then_some((start, event))
+        })
+        .collect();
+
+    let mut rewritten = String::with_capacity(content.len());
+    let mut found: Vec<(usize, Event)> = Vec::new();
+    let mut last = 0;
+    for (range, marker) in autolinks::find(content, &EVENT, &[]) {
+        let Some((date, name)) = marker
+            .strip_prefix("@event(")
+            .and_then(|inner| inner.strip_suffix(')'))
+            .and_then(|inner| inner.split_once(','))
+        else {
+            continue;
+        };
+        let (date, name) = (date.trim(), name.trim());
+        if !is_iso_date(date) || name.is_empty() {
+            continue;
+        }
+        rewritten.push_str(&content[last..range.start]);
+        let anchor = anchor(date, name);
+        let anchored = shown
+            .iter()
+            .chain(&found)
+            .any(|(_, event)| event.anchor == anchor);
+        if !anchored {
+            rewritten.push_str(&format!("<a id=\"{anchor}\"></a>"));
+            let event = Event {
+                date: date.to_string(),
+                name: name.to_string(),
+                page: page.to_string(),
+                anchor,
+            };
+            found.push((range.start, event));
+        }
+        rewritten.push_str(&format!("{name} ({date})"));
+        last = range.end;
+    }
+    rewritten.push_str(&content[last..]);
+    found.extend(shown);
+    found.sort_by_key(|(start, _)| *start);
+    (
+        rewritten,
+        found.into_iter().map(|(_, event)| event).collect(),
+    )
+}
@@ -0,0 +1,66 @@
+use crate::extract::parser;
+use pulldown_cmark::{Event, Tag};
+use std::collections::BTreeMap;
+
+/// An `http(s)` link leaving the book, with every chapter linking to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ExternalLink {
+    /// The URL, without its fragment.
+    pub url: String,
+    /// Paths of the chapters, in reading order.
+    pub pages: Vec<String>,
+}
+
+/// The host `url` points at, in lowercase and without `www.`.
+fn domain(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    let host = host.split(':').next().unwrap_or(host).to_lowercase();
+    host.strip_prefix("www.")
+        .map(str::to_string)
+        .unwrap_or(host)
+}
+
+/// The `http(s)` destinations of the Markdown links of `content`,
+/// autolinks included, without their fragment.
+fn urls(content: &str) -> Vec<String> {
+    parser(content)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. }) => Some(dest_url),
+            _ => None,
+        })
+        .filter(|dest| {
+            let scheme = dest
+                .split_once("://")
+                .map(|(scheme, _)| scheme.to_lowercase());
+            matches!(scheme.as_deref(), Some("http" | "https"))
+        })
+        .map(|dest| dest.split('#').next().unwrap_or_default().to_string())
+        .collect()
+}
+
+/// The external links of `chapters`, given by path and content in reading
+/// order, each once, by domain.
+pub(crate) fn collect<'a>(
+    chapters: impl Iterator<Item = (String, &'a str)>,
+) -> BTreeMap<String, Vec<ExternalLink>> {
+    let mut domains: BTreeMap<String, Vec<ExternalLink>> = BTreeMap::new();
+    for (page, content) in chapters {
+        for url in urls(content) {
+            let links = domains.entry(domain(&url)).or_default();
+            match links.iter_mut().find(|link| link.url == url) {
+                Some(link) if !link.pages.contains(&page) => link.pages.push(page.clone()),
+                Some(_) => {}
+                None => links.push(ExternalLink {
+                    url,
+                    pages: vec![page.clone()],
+                }),
+            }
+        }
+    }
+    for links in domains.values_mut() {
+        links.sort_by(|a, b| a.url.cmp(&b.url));
+    }
+    domains
+}
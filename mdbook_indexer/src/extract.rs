@@ -0,0 +1,561 @@
+use crate::config::{handle_url, EntityKind, MentionLinks};
+use crate::generate::{METADATA_MARKER, PROGRESS_MARKER};
+use crate::paths::is_relative_link;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// A bare URL, like `https://example.com/#intro` or `www.example.com`.
+static URL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(?:[a-z][a-z0-9+.-]*://|www\.)[^\s<>]*").expect("the URL pattern is valid")
+});
+
+/// A prefixed token found in a chapter's Markdown source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Token {
+    /// Position of the entity kind whose prefix introduced the token.
+    pub kind: usize,
+    /// Byte range of the whole token, prefix included, in the source.
+    pub range: Range<usize>,
+    /// The token without its prefix.
+    pub key: String,
+    pub form: Form,
+}
+
+/// How a token appears in the source.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Form {
+    /// Plain text, to be rewritten into a link.
+    Bare,
+    /// Already a link to its index entry; left as is.
+    Linked,
+    /// Preceded by the escape character; `range` covers the escape too.
+    Escaped,
+}
+
+/// Locates the tokens of the configured entity kinds in chapter sources.
+pub(crate) struct Scanner<'a> {
+    pub kinds: &'a [EntityKind],
+    /// Character opting the following token out of indexing.
+    pub escape: char,
+    /// Profiles mentions may link to instead of their index entry.
+    pub mention_links: Option<&'a MentionLinks>,
+}
+
+/// Parser configured with the same extensions the mdBook renderers enable,
+/// so code blocks are recognized exactly where they will be rendered.
+pub(crate) fn parser(content: &str) -> Parser<'_> {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_HEADING_ATTRIBUTES;
+    Parser::new_ext(content, options)
+}
+
+impl Scanner<'_> {
+    /// Finds every token introduced by one of the `kinds` prefixes in `content`,
+    /// in source order. Fenced and indented code blocks, inline code spans and
+    /// headings are skipped: a heading's `#` markers are never tokens, and
+    /// rewriting its text would change the anchor the renderer derives from it.
+    ///
+    /// Text inside links, images and bare URLs is never rewritten. A link
+    /// that already points at an index page, like `[#foo](tags.md#foo)`, or
+    /// at the profile of the mention it reads, is reported as a
+    /// [`Form::Linked`] token so repeated runs index the same entries without
+    /// nesting links. Keys rejected by their kind's ignore or allow list are
+    /// dropped and their text left alone.
+    ///
+    /// Authors can opt out with HTML comments: nothing between
+    /// `<!-- indexer:off -->` and `<!-- indexer:on -->` is touched, and a
+    /// chapter containing `<!-- indexer:skip -->` yields no tokens at all.
+    /// The metadata and task progress lines shown below a title are left
+    /// alone, as their links are only copies.
+    pub fn find_tokens(&self, content: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        let mut enabled = true;
+        let mut in_code_block = false;
+        let mut in_heading = false;
+        let mut link: Option<OpenLink> = None;
+        let mut image_depth = 0;
+        // The parser splits text at characters like `[`, so adjacent text
+        // events are scanned as one run.
+        let mut run: Option<Range<usize>> = None;
+
+        for (event, range) in parser(content).into_offset_iter() {
+            // Entities and escapes decode to something other than their source
+            let scannable = matches!(&event, Event::Text(text)
+                if enabled
+                    && link.is_none()
+                    && image_depth == 0
+                    && !in_code_block
+                    && !in_heading
+                    && **text == content[range.clone()]);
+            if scannable {
+                match &mut run {
+                    Some(run) if run.end == range.start => run.end = range.end,
+                    _ => {
+                        if let Some(run) = run.replace(range) {
+                            self.scan_text(content, run, &mut tokens);
+                        }
+                    }
+                }
+                continue;
+            }
+            if let Some(run) = run.take() {
+                self.scan_text(content, run, &mut tokens);
+            }
+
+            match event {
+                Event::Start(Tag::CodeBlock(_)) => in_code_block = true,
+                Event::End(TagEnd::CodeBlock) => in_code_block = false,
+                Event::Start(Tag::Heading { .. }) => in_heading = true,
+                Event::End(TagEnd::Heading(_)) => in_heading = false,
+                Event::Start(Tag::Image { .. }) => image_depth += 1,
+                Event::End(TagEnd::Image) => image_depth -= 1,
+                Event::Start(Tag::Link { dest_url, .. }) => {
+                    link = Some(OpenLink {
+                        dest: dest_url.to_string(),
+                        range,
+                        text: String::new(),
+                    });
+                }
+                Event::End(TagEnd::Link) => {
+                    let token = link
+                        .take()
+                        .and_then(|link| link.into_token(self.kinds, self.mention_links));
+                    if let Some(token) = token.filter(|_| enabled && !in_heading) {
+                        tokens.push(token);
+                    }
+                }
+                Event::Html(html) | Event::InlineHtml(html) => match directive(&html) {
+                    Some(Directive::Off) => enabled = false,
+                    Some(Directive::On) => enabled = true,
+                    Some(Directive::Skip) => return Vec::new(),
+                    None => {}
+                },
+                Event::Text(text) => {
+                    if let Some(link) = link.as_mut().filter(|_| image_depth == 0) {
+                        link.text.push_str(&text);
+                    }
+                }
+                _ => {}
+            }
+        }
+        if let Some(run) = run {
+            self.scan_text(content, run, &mut tokens);
+        }
+
+        let shown: Vec<Range<usize>> = [METADATA_MARKER, PROGRESS_MARKER]
+            .iter()
+            .flat_map(|marker| content.match_indices(marker))
+            .map(|(at, _)| {
+                let start = content[..at].rfind('\n').map_or(0, |i| i + 1);
+                let end = content[at..].find('\n').map_or(content.len(), |i| at + i);
+                start..end
+            })
+            .collect();
+        tokens.retain(|token| {
+            !shown.iter().any(|line| line.contains(&token.range.start))
+                && (token.form == Form::Escaped || self.kinds[token.kind].accepts(&token.key))
+        });
+        tokens
+    }
+
+    fn scan_text(&self, content: &str, range: Range<usize>, tokens: &mut Vec<Token>) {
+        let text = &content[range.clone()];
+        let urls: Vec<Range<usize>> = URL.find_iter(text).map(|url| url.range()).collect();
+        let mut offset = 0;
+
+        while let Some(c) = text[offset..].chars().next() {
+            let name_start = offset + c.len_utf8();
+            if let Some(url) = urls.iter().find(|url| url.contains(&offset)) {
+                offset = url.end;
+                continue;
+            }
+            let mut candidates = self.kinds.iter().filter(|kind| kind.prefix == c);
+            let Some(first) = candidates.next() else {
+                offset = name_start;
+                continue;
+            };
+            let nested = first.nested || candidates.any(|kind| kind.nested);
+            let Some((key, end)) = read_key(text, name_start, nested) else {
+                offset = name_start;
+                continue;
+            };
+
+            let mut start = range.start + offset;
+            let mut form = Form::Bare;
+            if let Some(escape_start) = self.escape_before(content, start) {
+                start = escape_start;
+                form = Form::Escaped;
+            }
+            // Escapes are honored whichever type would have taken the key
+            let kind = kind_for(self.kinds, c, &key).or_else(|| {
+                let any = self.kinds.iter().position(|kind| kind.prefix == c);
+                any.filter(|_| form == Form::Escaped)
+            });
+            if let Some(kind) = kind.filter(|_| is_boundary(content, start)) {
+                // A fediverse handle, like `@user@instance.social`, is one mention
+                let (key, end) = match handle_len(&text[end..]) {
+                    len if len > 0 && self.kinds[kind].name == "mentions" => {
+                        (format!("{key}{}", &text[end..end + len]), end + len)
+                    }
+                    _ => (key, end),
+                };
+                tokens.push(Token {
+                    kind,
+                    range: start..range.start + end,
+                    key,
+                    form,
+                });
+                offset = end;
+                continue;
+            }
+            offset = end;
+        }
+    }
+
+    /// Start of the escape character directly in front of `pos`, unless that
+    /// escape is itself escaped (`\\#tag`).
+    fn escape_before(&self, content: &str, pos: usize) -> Option<usize> {
+        let run = content[..pos]
+            .chars()
+            .rev()
+            .take_while(|&c| c == self.escape)
+            .count();
+        let escaped = if self.escape == '\\' {
+            run % 2 == 1
+        } else {
+            run > 0
+        };
+        escaped.then(|| pos - self.escape.len_utf8())
+    }
+
+    /// Source text rendering as the literal, unlinked token. Markdown already
+    /// treats `\` before ASCII punctuation as an escape; anything else is
+    /// spelled so that the prefix no longer starts a token on later runs.
+    pub fn literal(&self, content: &str, token: &Token) -> String {
+        let prefix = self.kinds[token.kind].prefix;
+        let source = &content[token.range.clone()];
+        let rest = &source[source.find(prefix).unwrap_or_default() + prefix.len_utf8()..];
+        if prefix.is_ascii_punctuation() {
+            format!("\\{}{}", prefix, rest)
+        } else {
+            format!("&#{};{}", u32::from(prefix), rest)
+        }
+    }
+}
+
+/// Destinations of the links in `content` that may point at another chapter:
+/// no URLs with a scheme, and no links within the page.
+pub(crate) fn link_targets(content: &str) -> Vec<String> {
+    parser(content)
+        .filter_map(|event| match event {
+            Event::Start(Tag::Link { dest_url, .. }) => Some(dest_url.into_string()),
+            _ => None,
+        })
+        .filter(|dest| is_relative_link(dest) && !dest.starts_with('#'))
+        .collect()
+}
+
+/// Longest snippet kept, in characters.
+const SNIPPET_LEN: usize = 160;
+
+/// Plain text of the source line holding the token at `range`, for showing
+/// an occurrence in context. Markup and count badges are dropped and long
+/// lines are cut around the token.
+pub(crate) fn snippet(content: &str, range: Range<usize>) -> String {
+    let start = content[..range.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = content[range.end..]
+        .find('\n')
+        .map_or(content.len(), |i| range.end + i);
+
+    let mut text = String::new();
+    let mut in_badge = false;
+    for event in parser(&content[start..end]) {
+        match event {
+            // Count badges of an earlier run aren't part of the text
+            Event::InlineHtml(html) if html.starts_with("<sup class=\"indexer-count\">") => {
+                in_badge = true;
+            }
+            Event::InlineHtml(html) if in_badge && &*html == "</sup>" => in_badge = false,
+            Event::Text(_) | Event::Code(_) if in_badge => {}
+            Event::Text(part) | Event::Code(part) => text.push_str(&part),
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    let chars: Vec<char> = collapse_whitespace(&text).chars().collect();
+    if chars.len() <= SNIPPET_LEN {
+        return chars.into_iter().collect();
+    }
+
+    // Center the cut on the token as it reads in plain text
+    let token = parser(&content[range.clone()])
+        .filter_map(|event| match event {
+            Event::Text(part) => Some(part.into_string()),
+            _ => None,
+        })
+        .collect::<String>();
+    let plain: String = chars.iter().collect();
+    let at = plain
+        .find(token.as_str())
+        .map_or(0, |i| plain[..i].chars().count());
+    let from = at
+        .saturating_sub(SNIPPET_LEN / 2)
+        .min(chars.len() - SNIPPET_LEN);
+    let mut cut: String = chars[from..from + SNIPPET_LEN].iter().collect();
+    if from > 0 {
+        cut.insert(0, '…');
+    }
+    if from + SNIPPET_LEN < chars.len() {
+        cut.push('…');
+    }
+    cut
+}
+
+/// An `<!-- indexer:... -->` comment controlling extraction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Directive {
+    Off,
+    On,
+    Skip,
+}
+
+fn directive(html: &str) -> Option<Directive> {
+    let comment = html.trim().strip_prefix("<!--")?.strip_suffix("-->")?;
+    match comment.trim().strip_prefix("indexer:")? {
+        "off" => Some(Directive::Off),
+        "on" => Some(Directive::On),
+        "skip" => Some(Directive::Skip),
+        _ => None,
+    }
+}
+
+/// A link whose text is still being collected.
+struct OpenLink {
+    dest: String,
+    range: Range<usize>,
+    text: String,
+}
+
+impl OpenLink {
+    /// The token this link stands for, if its text is a single token and it
+    /// points at that token's index page, or at the profile `mention_links`
+    /// gives a mention, a fediverse handle's on its instance by default.
+    fn into_token(
+        self,
+        kinds: &[EntityKind],
+        mention_links: Option<&MentionLinks>,
+    ) -> Option<Token> {
+        let mut chars = self.text.chars();
+        let prefix = chars.next()?;
+        let key = chars.as_str();
+        let kind = kind_for(kinds, prefix, key)?;
+        let page = self.dest.split('#').next().unwrap_or_default();
+
+        let is_mention = kinds[kind].name == "mentions";
+        let is_handle = is_mention
+            && key.find('@').is_some_and(|at| {
+                is_key(&key[..at], false) && handle_len(&key[at..]) == key.len() - at
+            });
+        let is_token = is_key(key, kinds[kind].nested) || is_handle;
+        // The profile is that of the key the mention stands for
+        let profile = |key: &str| {
+            let configured = mention_links.and_then(|links| links.url(key));
+            configured.or_else(|| handle_url(key).filter(|_| is_handle))
+        };
+        let is_profile = is_mention
+            && [key.to_string(), kinds[kind].canonical(key, "")]
+                .iter()
+                .any(|key| profile(key).as_deref() == Some(self.dest.as_str()));
+        (is_token && (kinds[kind].links_to(page) || is_profile)).then(|| Token {
+            kind,
+            range: self.range,
+            key: key.to_string(),
+            form: Form::Linked,
+        })
+    }
+}
+
+/// The entity type a token with `prefix` and `key` belongs to, if any.
+fn kind_for(kinds: &[EntityKind], prefix: char, key: &str) -> Option<usize> {
+    kinds
+        .iter()
+        .position(|kind| kind.prefix == prefix && kind.takes(key))
+}
+
+/// Reads the key starting at `start`: either a bracketed phrase such as
+/// `[machine learning]` or `{machine learning}`, or a single word. Nested keys
+/// may chain either form with `/`. Returns the key, with whitespace collapsed,
+/// and the offset just past it.
+fn read_key(text: &str, start: usize, nested: bool) -> Option<(String, usize)> {
+    let rest = &text[start..];
+    let close = match rest.chars().next() {
+        Some('[') => Some(']'),
+        Some('{') => Some('}'),
+        _ => None,
+    };
+
+    if let Some(close) = close {
+        let len = rest.find(close)?;
+        let phrase = &rest[1..len];
+        let segments: Vec<String> = if nested {
+            phrase.split('/').map(collapse_whitespace).collect()
+        } else {
+            vec![collapse_whitespace(phrase)]
+        };
+        if segments.iter().any(String::is_empty) {
+            return None;
+        }
+        return Some((segments.join("/"), start + len + 1));
+    }
+
+    let mut len = 0;
+    loop {
+        let word = word_len(&rest[len..]);
+        if word == 0 {
+            break;
+        }
+        len += word;
+        // A separator only counts when another segment follows it
+        let continues = rest[len..]
+            .strip_prefix('/')
+            .is_some_and(|after| word_len(after) > 0);
+        if !nested || !continues {
+            break;
+        }
+        len += '/'.len_utf8();
+    }
+    (len > 0).then(|| (rest[..len].to_string(), start + len))
+}
+
+pub(crate) fn collapse_whitespace(phrase: &str) -> String {
+    phrase.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// What the lines after the one holding `at` open with to stay in the
+/// containers of the block starting there: the `>` of block quotes, and
+/// spaces in place of list markers.
+pub(crate) fn container_prefix(content: &str, at: usize) -> String {
+    let line = content[..at].rfind('\n').map_or(0, |i| i + 1);
+    content[line..at]
+        .chars()
+        .map(|c| {
+            if c == '>' || c.is_whitespace() {
+                c
+            } else {
+                ' '
+            }
+        })
+        .collect()
+}
+
+/// `text` with `prefix` opening every line but the first, so that it stays
+/// in the containers of wherever its first line is put. Blank lines keep the
+/// `>` of block quotes only.
+pub(crate) fn contained(text: &str, prefix: &str) -> String {
+    let mut lines = text.split('\n');
+    let mut contained = lines.next().unwrap_or_default().to_string();
+    for line in lines {
+        contained.push('\n');
+        if line.is_empty() {
+            contained.push_str(prefix.trim_end());
+        } else {
+            contained.push_str(prefix);
+            contained.push_str(line);
+        }
+    }
+    contained
+}
+
+/// Whether `key` is something [`read_key`] can produce.
+fn is_key(key: &str, nested: bool) -> bool {
+    let is_phrase = |segment: &str| {
+        segment
+            .split(' ')
+            .all(|word| !word.is_empty() && word_len(word) == word.len())
+    };
+    if nested {
+        key.split('/').all(is_phrase)
+    } else {
+        is_phrase(key)
+    }
+}
+
+/// Length of the instance of a fediverse handle `text` starts with, like
+/// `@instance.social` after `@user`, or 0.
+fn handle_len(text: &str) -> usize {
+    let Some(rest) = text.strip_prefix('@') else {
+        return 0;
+    };
+    let len = rest
+        .find(|c: char| !(c.is_alphanumeric() || c == '-' || c == '.'))
+        .unwrap_or(rest.len());
+    let instance = rest[..len].trim_end_matches(['.', '-']);
+    let labels: Vec<&str> = instance.split('.').collect();
+    let is_domain = labels.len() > 1 && labels.iter().all(|label| !label.is_empty());
+    if is_domain {
+        1 + instance.len()
+    } else {
+        0
+    }
+}
+
+/// Length of the word `text` starts with. Words are made of letters, digits,
+/// marks and symbols of any script, with `-` and `_` allowed inside, so
+/// `#日本語。` and `#state-of-the-art,` both end where expected.
+fn word_len(text: &str) -> usize {
+    if text.starts_with('-') {
+        return 0;
+    }
+    let len = text.find(|c| !is_name_char(c)).unwrap_or(text.len());
+    text[..len].trim_end_matches('-').len()
+}
+
+/// A token only starts at the beginning of a word, or anywhere in scripts
+/// written without spaces between words, but not right after `/` or `:`,
+/// where it would be part of a path or address.
+fn is_boundary(content: &str, pos: usize) -> bool {
+    content[..pos]
+        .chars()
+        .next_back()
+        .is_none_or(|c| (!is_name_char(c) || is_unspaced_script(c)) && c != '/' && c != ':')
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric()
+        || c == '_'
+        || c == '-'
+        || (!c.is_ascii() && !c.is_whitespace() && !is_unicode_punctuation(c))
+}
+
+/// The punctuation blocks of Unicode, which end a word like ASCII punctuation.
+fn is_unicode_punctuation(c: char) -> bool {
+    matches!(c,
+        '\u{00A1}'..='\u{00BF}'
+        | '\u{00D7}'
+        | '\u{00F7}'
+        | '\u{2000}'..='\u{200B}'
+        | '\u{200E}'..='\u{206F}'
+        | '\u{2E00}'..='\u{2E7F}'
+        | '\u{3000}'..='\u{3004}'
+        | '\u{3008}'..='\u{3020}'
+        | '\u{FE30}'..='\u{FE4F}'
+        | '\u{FF01}'..='\u{FF0F}'
+        | '\u{FF1A}'..='\u{FF20}'
+        | '\u{FF3B}'..='\u{FF40}'
+        | '\u{FF5B}'..='\u{FF65}')
+}
+
+/// Han and kana, where a tag can directly follow the previous word.
+fn is_unspaced_script(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2FA1F}')
+}
@@ -0,0 +1,211 @@
+use crate::extract::parser;
+use pulldown_cmark::{html, Event, Tag};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// A list item, with its indentation and the text after its marker.
+static ITEM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\s*(?:[-*+]|\d+[.)])\s+)(.*)$").expect("the list item pattern is valid")
+});
+
+/// The anchor an earlier run left at a card.
+static ANCHOR: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"^<a id="card-\d+"></a>"#).expect("the anchor pattern is valid"));
+
+/// Left in place of the tag of a list item that is a card, so later runs
+/// still find it.
+pub(crate) const MARKER: &str = "<!-- indexer:flashcard -->";
+
+/// A question and its answer, from a chapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Card {
+    /// In Markdown, like the answer.
+    pub question: String,
+    pub answer: String,
+    /// Path of the chapter.
+    pub page: String,
+    /// Id of the anchor left at the question in the chapter.
+    pub anchor: String,
+}
+
+/// `content`, the body of the chapter at `page`, with an anchor at every
+/// card outside code and `tag`, like `#flashcard`, replaced by [`MARKER`]
+/// in the list items it marks. Returns the cards, in order.
+///
+/// A card is either a paragraph opening with `Q:`, its question, with a line
+/// opening with `A:`, its answer to the end of the paragraph, or a list item
+/// holding `tag`: its text is the question and its nested lines the answer,
+/// unless it reads `question :: answer`. The anchors and markers of an
+/// earlier run are read as such.
+pub(crate) fn rewrite(content: &str, page: &str, tag: &str) -> (String, Vec<Card>) {
+    let code: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| {
+            matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Html(_)).then_some(range)
+        })
+        .collect();
+    let tagged = Regex::new(&format!(r"(?i)(?:^|\s){}(?:\s|$)", regex::escape(tag)))
+        .expect("an escaped tag makes a valid pattern");
+
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut starts = Vec::with_capacity(lines.len());
+    let mut offset = 0;
+    for line in &lines {
+        starts.push(offset);
+        offset += line.len();
+    }
+    let in_code = |i: usize| code.iter().any(|r| r.contains(&starts[i]));
+
+    let mut rewritten = String::with_capacity(content.len());
+    let mut cards = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        let text = line.trim_end_matches(['\n', '\r']);
+        let newline = &line[text.len()..];
+        if in_code(i) {
+            rewritten.push_str(line);
+            i += 1;
+            continue;
+        }
+        let anchor = format!("card-{}", cards.len() + 1);
+        let html_anchor = format!("<a id=\"{anchor}\"></a>");
+
+        let indent = &text[..text.len() - text.trim_start().len()];
+        let line_text = unanchored(text.trim_start());
+        if let Some(question) = line_text.strip_prefix("Q:") {
+            // The paragraph runs to a blank line or the next question
+            let end = (i + 1..lines.len())
+                .find(|&j| {
+                    let next = lines[j].trim();
+                    next.is_empty() || unanchored(next).starts_with("Q:") || in_code(j)
+                })
+                .unwrap_or(lines.len());
+            let answer_at = (i + 1..end).find(|&j| lines[j].trim_start().starts_with("A:"));
+            if let Some(answer_at) = answer_at {
+                let mut question = vec![question.trim()];
+                question.extend(lines[i + 1..answer_at].iter().map(|l| l.trim()));
+                let first = lines[answer_at].trim_start();
+                let mut answer = vec![first["A:".len()..].trim()];
+                answer.extend(lines[answer_at + 1..end].iter().map(|l| l.trim()));
+                cards.push(Card {
+                    question: question.join("\n").trim().to_string(),
+                    answer: answer.join("\n").trim().to_string(),
+                    page: page.to_string(),
+                    anchor,
+                });
+                rewritten.push_str(&format!("{indent}{html_anchor}{line_text}"));
+                rewritten.push_str(newline);
+                for line in &lines[i + 1..end] {
+                    rewritten.push_str(line);
+                }
+                i = end;
+                continue;
+            }
+        }
+
+        let item = ITEM.captures(text).filter(|c| {
+            let text = unanchored(c.get(2).map_or("", |text| text.as_str()));
+            text.starts_with(MARKER) || tagged.is_match(text)
+        });
+        let Some(item) = item else {
+            rewritten.push_str(line);
+            i += 1;
+            continue;
+        };
+        let opening = &item[1];
+        let text = unanchored(&item[2]);
+        let written = tagged.replace_all(text.strip_prefix(MARKER).unwrap_or(text), " ");
+        let written = written.trim();
+        // Nested lines are those indented past the marker, blank ones between
+        let column = opening.len();
+        let mut end = i + 1;
+        let mut last = i;
+        while end < lines.len() && !in_code(end) {
+            let next = lines[end].trim_end_matches(['\n', '\r']);
+            if next.trim().is_empty() {
+                end += 1;
+                continue;
+            }
+            if next.len() - next.trim_start().len() < column {
+                break;
+            }
+            last = end;
+            end += 1;
+        }
+        let nested: Vec<&str> = lines[i + 1..=last]
+            .iter()
+            .map(|line| {
+                let line = line.trim_end_matches(['\n', '\r']);
+                line.get(column..).unwrap_or_else(|| line.trim_start())
+            })
+            .collect();
+        let (question, answer) = match written.split_once(" :: ") {
+            Some((question, answer)) => (question.trim(), answer.trim().to_string()),
+            None => (written, nested.join("\n").trim().to_string()),
+        };
+        if !question.is_empty() && !answer.is_empty() {
+            cards.push(Card {
+                question: question.to_string(),
+                answer,
+                page: page.to_string(),
+                anchor,
+            });
+            rewritten.push_str(&format!("{opening}{html_anchor}{MARKER}{written}{newline}"));
+        } else {
+            rewritten.push_str(&format!("{opening}{written}{newline}"));
+        }
+        i += 1;
+    }
+    (rewritten, cards)
+}
+
+/// `text` without the anchor an earlier run left at its start.
+fn unanchored(text: &str) -> &str {
+    ANCHOR
+        .find(text)
+        .map_or(text, |anchor| &text[anchor.end()..])
+}
+
+/// `markdown` in HTML, on one line when it is a single paragraph.
+pub(crate) fn to_html(markdown: &str) -> String {
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, parser(markdown));
+    let rendered = rendered.trim_end();
+    match rendered
+        .strip_prefix("<p>")
+        .and_then(|inner| inner.strip_suffix("</p>"))
+    {
+        Some(inner) if !inner.contains("<p>") => inner.to_string(),
+        _ => rendered.to_string(),
+    }
+}
+
+/// `cards` as a file Anki imports into `deck`: tab-separated fields in
+/// HTML, the question, the answer and tags, here the path of each card's
+/// chapter as a hierarchical tag like `guides::setup`.
+pub(crate) fn tsv<'a>(cards: impl IntoIterator<Item = &'a Card>, deck: &str) -> String {
+    let mut tsv = format!("#separator:tab\n#html:true\n#deck:{deck}\n#tags column:3\n");
+    for card in cards {
+        let page = card.page.strip_suffix(".md").unwrap_or(&card.page);
+        let tag = page.replace('/', "::").replace(char::is_whitespace, "_");
+        tsv.push_str(&format!(
+            "{}\t{}\t{}\n",
+            field(&to_html(&card.question)),
+            field(&to_html(&card.answer)),
+            field(&tag)
+        ));
+    }
+    tsv
+}
+
+/// `text` as a field of a tab-separated line, quoted if it spans lines or
+/// holds a tab or a quote.
+fn field(text: &str) -> String {
+    if text.contains(['\n', '\t', '"']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_string()
+    }
+}
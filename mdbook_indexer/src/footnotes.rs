@@ -0,0 +1,61 @@
+use crate::extract::{collapse_whitespace, parser};
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+/// A footnote of the book, with every chapter defining it the same way.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Note {
+    /// Plain text of the definition.
+    pub text: String,
+    /// Paths of the chapters, in reading order.
+    pub pages: Vec<String>,
+}
+
+/// The plain text of the footnotes `content` defines, as in
+/// `[^1]: Some note.`, in order.
+fn definitions(content: &str) -> Vec<String> {
+    let mut definitions = Vec::new();
+    let mut current: Option<String> = None;
+    for event in parser(content) {
+        match event {
+            Event::Start(Tag::FootnoteDefinition(_)) => current = Some(String::new()),
+            Event::End(TagEnd::FootnoteDefinition) => {
+                if let Some(text) = current.take() {
+                    definitions.push(collapse_whitespace(&text));
+                }
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(definition) = &mut current {
+                    definition.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak | Event::End(TagEnd::Paragraph) => {
+                if let Some(definition) = &mut current {
+                    definition.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+    definitions.retain(|text| !text.is_empty());
+    definitions
+}
+
+/// The notes of `chapters`, given by path and content in reading order,
+/// numbered from 1 in the order they are first defined. Chapters defining a
+/// note in the same words share it.
+pub(crate) fn collect<'a>(chapters: impl Iterator<Item = (String, &'a str)>) -> Vec<Note> {
+    let mut notes: Vec<Note> = Vec::new();
+    for (page, content) in chapters {
+        for text in definitions(content) {
+            match notes.iter_mut().find(|note| note.text == text) {
+                Some(note) if !note.pages.contains(&page) => note.pages.push(page.clone()),
+                Some(_) => {}
+                None => notes.push(Note {
+                    text,
+                    pages: vec![page.clone()],
+                }),
+            }
+        }
+    }
+    notes
+}
@@ -0,0 +1,118 @@
+use crate::config::EntityKind;
+use crate::dates::is_iso_date;
+use crate::extract::collapse_whitespace;
+use serde_yaml::{Mapping, Value};
+
+/// A YAML block opening a chapter, between `---` lines:
+///
+/// ```markdown
+/// ---
+/// tags: [rust, async]
+/// ---
+/// ```
+#[derive(Debug, Clone)]
+pub(crate) struct FrontMatter {
+    /// Length of the block in the source, delimiters included.
+    pub len: usize,
+    pub data: Mapping,
+}
+
+impl FrontMatter {
+    /// Reads the front matter at the very start of `content`. A block that
+    /// isn't a YAML mapping is left to Markdown, where it is just a thematic
+    /// break followed by text.
+    pub fn parse(content: &str) -> Option<Self> {
+        let mut lines = content.split_inclusive('\n');
+        if lines.next()?.trim_end() != "---" {
+            return None;
+        }
+
+        let start = content.find('\n')? + 1;
+        let mut end = start;
+        for line in lines {
+            if matches!(line.trim_end(), "---" | "...") {
+                let data = match serde_yaml::from_str(&content[start..end]) {
+                    Ok(Value::Mapping(data)) => data,
+                    Ok(Value::Null) => Mapping::new(),
+                    _ => return None,
+                };
+                return Some(FrontMatter {
+                    len: end + line.len(),
+                    data,
+                });
+            }
+            end += line.len();
+        }
+        None
+    }
+
+    /// Keys of `kind` declared under its name, e.g. `tags: [rust, async]` or
+    /// `mentions: alice`. The prefix may be written or left out, and keys
+    /// rejected by the kind's ignore or allow list are dropped.
+    pub fn keys(&self, kind: &EntityKind) -> Vec<String> {
+        let values = match self.data.get(kind.name.as_str()) {
+            Some(Value::Sequence(values)) => values.iter().collect(),
+            Some(value) => vec![value],
+            None => Vec::new(),
+        };
+        values
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::String(key) => Some(key.clone()),
+                Value::Number(key) => Some(key.to_string()),
+                _ => None,
+            })
+            .filter_map(|key| normalize_key(&key, kind))
+            .filter(|key| kind.accepts(key))
+            .collect()
+    }
+
+    /// The texts given under `key`, like `aliases: [old-name]`.
+    pub fn list(&self, key: &str) -> Vec<String> {
+        let values = match self.data.get(key) {
+            Some(Value::Sequence(values)) => values.iter().collect(),
+            Some(value) => vec![value],
+            None => Vec::new(),
+        };
+        values
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::String(text) => Some(text.trim().to_string()),
+                _ => None,
+            })
+            .filter(|text| !text.is_empty())
+            .collect()
+    }
+
+    /// The text or number given under `key`, like `id: 202501151230`.
+    pub fn text(&self, key: &str) -> Option<String> {
+        match self.data.get(key)? {
+            Value::String(text) => Some(text.trim().to_string()),
+            Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        }
+    }
+
+    /// The date given under `key`, like `due: 2025-03-01`, if it is one.
+    pub fn date(&self, key: &str) -> Option<String> {
+        match self.data.get(key) {
+            Some(Value::String(date)) if is_iso_date(date.trim()) => Some(date.trim().to_string()),
+            _ => None,
+        }
+    }
+}
+
+/// `key` as an inline token would spell it, or `None` if nothing is left.
+fn normalize_key(key: &str, kind: &EntityKind) -> Option<String> {
+    let key = key.trim();
+    let key = key.strip_prefix(kind.prefix).unwrap_or(key);
+    let segments: Vec<String> = if kind.nested {
+        key.split('/').map(collapse_whitespace).collect()
+    } else {
+        vec![collapse_whitespace(key)]
+    };
+    if segments.iter().any(String::is_empty) {
+        return None;
+    }
+    Some(segments.join("/"))
+}
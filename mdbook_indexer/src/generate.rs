@@ -0,0 +1,2455 @@
+use crate::citations::{self, Reference};
+use crate::config::{
+    capitalize, EntityKind, IndexerConfig, Labels, MetadataStyle, PageOrder, Profile, SortOrder,
+};
+use crate::dates::{self, Dated};
+use crate::emails;
+use crate::events::Event;
+use crate::external::ExternalLink;
+use crate::flashcards::{self, Card};
+use crate::footnotes::Note;
+use crate::glossary::Definition;
+use crate::graph::Graph;
+use crate::images::Image;
+use crate::index::{Entry, Index, Occurrence};
+use crate::locations::Location;
+use crate::paths::{is_relative_link, link_to, percent_encode, relative_link};
+use crate::quotes::{Callout, Quote};
+use crate::reading::Reading;
+use crate::tables::{self, Table};
+use crate::tasks::{Column, Due, Task};
+use crate::templates::Templates;
+use crate::wikilinks::Stub;
+use crate::workspace::Sibling;
+use mdbook::book::{BookItem, Chapter};
+use mdbook::errors::Error;
+use mdbook::utils::unique_id_from_content;
+use serde_json::{json, Value};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::time::SystemTime;
+
+/// First line of every generated chapter.
+pub(crate) const GENERATED_MARKER: &str = "<!-- indexer:generated -->";
+
+/// Opens the list of backlinks appended to a chapter.
+pub(crate) const BACKLINKS_MARKER: &str = "<!-- indexer:backlinks -->";
+
+/// Script of the interactive graph view.
+const GRAPH_VIEWER: &str = include_str!("graph.js");
+
+/// Draws the places of the map chapter.
+const MAP_VIEWER: &str = include_str!("map.js");
+
+/// Where the map chapter loads Leaflet from.
+const LEAFLET: &str = "https://unpkg.com/leaflet@1.9.4/dist";
+
+/// Opens the list of related chapters appended to a chapter.
+pub(crate) const RELATED_MARKER: &str = "<!-- indexer:related -->";
+
+/// Opens the list of chapters appended by the `see_also` rules.
+pub(crate) const SEE_ALSO_MARKER: &str = "<!-- indexer:see-also -->";
+
+/// Opens the comment suggesting tags appended to an untagged chapter.
+pub(crate) const SUGGESTIONS_MARKER: &str = "<!-- indexer:suggestions -->";
+
+/// Opens the links to the previous and next daily notes.
+pub(crate) const JOURNAL_MARKER: &str = "<!-- indexer:journal -->";
+
+/// Starts the line a chapter's task progress is shown in.
+pub(crate) const PROGRESS_MARKER: &str = "<!-- indexer:progress -->";
+
+/// Marks the line a chapter's metadata is shown in.
+pub(crate) const METADATA_MARKER: &str = "<!-- indexer:metadata -->";
+
+/// Where the lists appended to `content` by an earlier run start, if any.
+pub(crate) fn footer_start(content: &str) -> Option<usize> {
+    [
+        BACKLINKS_MARKER,
+        RELATED_MARKER,
+        SEE_ALSO_MARKER,
+        SUGGESTIONS_MARKER,
+        JOURNAL_MARKER,
+    ]
+    .iter()
+    .filter_map(|marker| content.find(marker))
+    .min()
+}
+
+/// Second line of the stub chapters of missing wikilink targets.
+pub(crate) const STUB_MARKER: &str = "<!-- indexer:stub -->";
+
+pub(crate) fn is_generated(chapter: &Chapter) -> bool {
+    chapter.content.starts_with(GENERATED_MARKER)
+}
+
+pub(crate) fn is_stub(chapter: &Chapter) -> bool {
+    chapter
+        .content
+        .strip_prefix(GENERATED_MARKER)
+        .is_some_and(|rest| rest.trim_start().starts_with(STUB_MARKER))
+}
+
+/// What generated chapters need to know about a scanned chapter.
+#[derive(Debug, Clone)]
+pub(crate) struct ChapterInfo {
+    pub name: String,
+    /// Names of the enclosing chapters, outermost first.
+    pub parents: Vec<String>,
+    /// Position of the chapter in the book's reading order.
+    pub position: usize,
+    /// Title of the part the chapter is in, if any.
+    pub part: Option<String>,
+    /// Modification time of the chapter's source file, if it has one.
+    pub modified: Option<SystemTime>,
+    /// Zettelkasten ID of the note, with `zettelkasten` on.
+    pub id: Option<String>,
+    /// Former names from `aliases` in its front matter.
+    pub aliases: Vec<String>,
+}
+
+/// Builds the generated chapters from the collected indexes.
+pub(crate) struct Generator<'a> {
+    pub config: &'a IndexerConfig,
+    /// The scanned chapters, by path.
+    pub chapters: &'a HashMap<String, ChapterInfo>,
+    /// Name of the renderer the book is built for.
+    pub renderer: &'a str,
+    pub templates: &'a Templates,
+    /// The other books of the workspace.
+    pub siblings: &'a [Sibling],
+}
+
+/// One page of entries in the chapter of a kind: all of them, or those of a
+/// range of initials once the chapter is split.
+struct IndexPage {
+    /// Name of the page in the table of contents.
+    name: String,
+    /// Title heading the page.
+    title: String,
+    file: String,
+    /// Keys of the listed entries on the page, in listing order.
+    keys: Vec<String>,
+}
+
+/// Number of sizes in a tag cloud.
+const CLOUD_SIZES: usize = 5;
+
+/// Number of keys per kind, and of chapters, ranked by the statistics.
+const STATS_TOP_ENTRIES: usize = 20;
+const STATS_TOP_CHAPTERS: usize = 10;
+
+impl Generator<'_> {
+    /// Whether `entry` gets a section, or a page, in its generated chapter.
+    pub fn is_listed(&self, entry: &Entry) -> bool {
+        entry.count() >= self.config.min_occurrences
+    }
+
+    /// Decides where links to each entry go. Listed entries get their own page
+    /// when the kind asks for one, and otherwise the id the HTML renderer
+    /// derives from their heading, replaying the headings of the generated
+    /// chapter in order so duplicates are numbered the same way. Everything
+    /// else links to the top of the index chapter.
+    pub fn assign_targets(&self, kind: &EntityKind, index: &mut Index) {
+        // Slugs are handed out alphabetically so they don't depend on `sort`
+        let mut slugs = HashSet::new();
+        for entry in index.entries_mut() {
+            entry.file = kind.file.clone();
+            entry.anchor.clear();
+            if kind.page_per_entry && self.is_listed(entry) {
+                let slug = unique_slug(&entry.display, &mut slugs);
+                entry.file = format!("{}/{}.md", kind.entry_dir(), slug);
+            }
+        }
+        if kind.page_per_entry {
+            return;
+        }
+
+        for page in self.index_pages(kind, index) {
+            let mut ids = HashMap::new();
+            unique_id_from_content(&page.title, &mut ids);
+            for key in &page.keys {
+                let Some(entry) = index.get_mut(key) else {
+                    continue;
+                };
+                let heading = format!("{}{}", kind.prefix, entry.display);
+                entry.file = page.file.clone();
+                entry.anchor = unique_id_from_content(&heading, &mut ids);
+            }
+        }
+    }
+
+    /// The pages listing the entries of `kind`: the index chapter alone, or
+    /// with `split_entries` exceeded, pages of whole initials packed in
+    /// alphabetical order up to that many entries each.
+    fn index_pages(&self, kind: &EntityKind, index: &Index) -> Vec<IndexPage> {
+        let listed: Vec<(&str, &Entry)> = index
+            .keyed()
+            .filter(|(_, entry)| self.is_listed(entry))
+            .collect();
+        let max = match self.config.split_entries {
+            Some(max) if listed.len() > max && !kind.page_per_entry => max,
+            _ => {
+                return vec![IndexPage {
+                    name: kind.title.clone(),
+                    title: kind.title.clone(),
+                    file: kind.file.clone(),
+                    keys: listed.iter().map(|(key, _)| key.to_string()).collect(),
+                }]
+            }
+        };
+
+        // Nested keys share the initial of their parent, so trees stay whole
+        let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+        for (key, entry) in listed {
+            let initial = initial(&entry.display, &self.config.labels);
+            match groups.iter_mut().find(|(group, _)| *group == initial) {
+                Some((_, keys)) => keys.push(key.to_string()),
+                None => groups.push((initial, vec![key.to_string()])),
+            }
+        }
+        let other = &self.config.labels.other;
+        groups.sort_by_cached_key(|(initial, _)| {
+            let rank = match initial.chars().next() {
+                _ if initial == other => 2,
+                Some(c) if c.is_alphabetic() => 1,
+                _ => 0,
+            };
+            (rank, initial.to_lowercase())
+        });
+
+        let mut pages = Vec::new();
+        let mut initials: Vec<String> = Vec::new();
+        let mut keys = Vec::new();
+        for (initial, group) in groups {
+            if !keys.is_empty() && keys.len() + group.len() > max {
+                pages.push(self.split_page(kind, &initials, keys));
+                initials.clear();
+                keys = Vec::new();
+            }
+            initials.push(initial);
+            keys.extend(group);
+        }
+        if !keys.is_empty() {
+            pages.push(self.split_page(kind, &initials, keys));
+        }
+        pages
+    }
+
+    /// The page of a split index listing the entries of `initials`, named
+    /// after the first and last of them, e.g. `tags-a-f.md` for `A–F`.
+    fn split_page(&self, kind: &EntityKind, initials: &[String], keys: Vec<String>) -> IndexPage {
+        let first = &initials[0];
+        let last = &initials[initials.len() - 1];
+        let (name, file) = if first == last {
+            (
+                first.clone(),
+                format!("{}-{}.md", kind.entry_dir(), slug(first)),
+            )
+        } else {
+            (
+                format!("{first}–{last}"),
+                format!("{}-{}-{}.md", kind.entry_dir(), slug(first), slug(last)),
+            )
+        };
+        IndexPage {
+            title: format!("{}: {}", kind.title, name),
+            name,
+            file,
+            keys,
+        }
+    }
+
+    /// Orders the entries of `index` and the pages of each entry as set by
+    /// `sort` and `page_sort`. Nested entries stay right after their parent,
+    /// and with `letter_nav` on, entries stay grouped by initial.
+    pub fn arrange(&self, kind: &EntityKind, index: &mut Index) {
+        for entry in index.entries_mut() {
+            self.sort_pages(entry);
+        }
+        if self.config.sort == SortOrder::Alphabetical {
+            return;
+        }
+
+        let mut entries: Vec<(&str, &Entry)> = index.keyed().collect();
+        entries.sort_by(|(a_key, a), (b_key, b)| {
+            let letters = if self.config.letter_nav {
+                a_key.chars().next().cmp(&b_key.chars().next())
+            } else {
+                Ordering::Equal
+            };
+            let rank = match self.config.sort {
+                SortOrder::Alphabetical => Ordering::Equal,
+                SortOrder::Count => b.count().cmp(&a.count()),
+                SortOrder::Recent => self.last_modified(b).cmp(&self.last_modified(a)),
+            };
+            letters
+                .then(rank)
+                .then_with(|| a_key.split('/').cmp(b_key.split('/')))
+        });
+        let keys: Vec<&str> = entries.into_iter().map(|(key, _)| key).collect();
+
+        let order = if kind.nested {
+            // Walk the tree, siblings keeping their sorted order
+            let mut children: HashMap<&str, Vec<&str>> = HashMap::new();
+            let mut roots = Vec::new();
+            for &key in &keys {
+                match key.rsplit_once('/') {
+                    Some((parent, _)) if index.get(parent).is_some() => {
+                        children.entry(parent).or_default().push(key)
+                    }
+                    _ => roots.push(key),
+                }
+            }
+            let mut order = Vec::with_capacity(keys.len());
+            let mut stack: Vec<&str> = roots.into_iter().rev().collect();
+            while let Some(key) = stack.pop() {
+                order.push(key.to_string());
+                if let Some(below) = children.get(key) {
+                    stack.extend(below.iter().rev());
+                }
+            }
+            order
+        } else {
+            keys.into_iter().map(String::from).collect()
+        };
+        index.set_order(order);
+    }
+
+    fn sort_pages(&self, entry: &mut Entry) {
+        // Occurrences start out by page; stable sorts keep each page together
+        let occurrences = &mut entry.occurrences;
+        match self.config.page_sort {
+            PageOrder::Path => {}
+            PageOrder::Title => occurrences.sort_by_cached_key(|o| self.page_title(&o.page)),
+            PageOrder::Book => occurrences.sort_by_key(|o| {
+                self.chapters
+                    .get(&o.page)
+                    .map_or(usize::MAX, |chapter| chapter.position)
+            }),
+            PageOrder::Count => {
+                let mut counts: HashMap<String, usize> = HashMap::new();
+                for occurrence in occurrences.iter() {
+                    *counts.entry(occurrence.page.clone()).or_default() += 1;
+                }
+                occurrences.sort_by_key(|o| Reverse(counts[&o.page]));
+            }
+            PageOrder::Recent => occurrences.sort_by_key(|o| Reverse(self.modified(&o.page))),
+        }
+    }
+
+    fn modified(&self, page: &str) -> Option<SystemTime> {
+        self.chapters.get(page).and_then(|chapter| chapter.modified)
+    }
+
+    /// When the most recently changed chapter referencing `entry` was saved.
+    fn last_modified(&self, entry: &Entry) -> Option<SystemTime> {
+        entry
+            .occurrences
+            .iter()
+            .filter_map(|occurrence| self.modified(&occurrence.page))
+            .max()
+    }
+
+    /// The chapter generated for `kind`, with one sub-chapter per listed entry
+    /// when the kind has a page per entry, or per range of initials when the
+    /// index is split.
+    pub fn index_chapter(&self, kind: &EntityKind, index: &Index) -> Result<Chapter, Error> {
+        if !kind.page_per_entry {
+            let pages = self.index_pages(kind, index);
+            if let [page] = &pages[..] {
+                if page.file == kind.file {
+                    let content = self.generate_index(kind, index, page)?;
+                    return Ok(Chapter::new(&kind.title, content, &kind.file, Vec::new()));
+                }
+            }
+
+            let mut content = format!("{GENERATED_MARKER}\n# {}\n\n", kind.title);
+            for page in &pages {
+                let link = relative_link(Path::new(&kind.file), Path::new(&page.file));
+                content.push_str(&format!("- [{}]({})\n", page.name, link));
+            }
+            let mut chapter = Chapter::new(&kind.title, content, &kind.file, Vec::new());
+            let parent_names = vec![chapter.name.clone()];
+            for page in &pages {
+                let content = self.generate_index(kind, index, page)?;
+                chapter.sub_items.push(BookItem::Chapter(Chapter::new(
+                    &page.name,
+                    content,
+                    &page.file,
+                    parent_names.clone(),
+                )));
+            }
+            return Ok(chapter);
+        }
+
+        let content = self.generate_overview(kind, index);
+        let mut chapter = Chapter::new(&kind.title, content, &kind.file, Vec::new());
+        let parent_names = vec![chapter.name.clone()];
+        chapter.sub_items = index
+            .entries()
+            .filter(|entry| self.is_listed(entry))
+            .map(|entry| {
+                let content = self.generate_entry_page(kind, index, entry);
+                let name = format!("{}{}", kind.prefix, entry.display);
+                BookItem::Chapter(Chapter::new(
+                    &name,
+                    content,
+                    &entry.file,
+                    parent_names.clone(),
+                ))
+            })
+            .collect();
+        Ok(chapter)
+    }
+
+    /// The tag cloud: every listed entry of `kind` sized by its number of
+    /// occurrences. HTML gets the entries in alphabetical order, with a class
+    /// and an inline size each; other renderers get a list with one item per
+    /// size, the biggest first.
+    pub fn cloud_chapter(&self, kind: &EntityKind, index: &Index) -> Chapter {
+        let file = &self.config.tag_cloud_file;
+        let title = &self.config.tag_cloud_title;
+        let mut entries: Vec<&Entry> = index
+            .entries()
+            .filter(|entry| self.is_listed(entry))
+            .collect();
+        entries.sort_by_cached_key(|entry| entry.display.to_lowercase());
+        let counts = entries.iter().map(|entry| entry.count());
+        let range = (counts.clone().min().unwrap_or(1), counts.max().unwrap_or(1));
+
+        let html = self.renderer == "html";
+        let mut cloud = Vec::new();
+        let mut sizes = vec![Vec::new(); CLOUD_SIZES];
+        for entry in entries {
+            let size = cloud_size(entry.count(), range);
+            let link = link_to(Path::new(file), Path::new(&entry.file), &entry.anchor);
+            let name = format!("{}{}", kind.prefix, entry.display);
+            if html {
+                cloud.push(format!(
+                    "<a class=\"indexer-cloud indexer-cloud-{}\" href=\"{}\" \
+                     style=\"font-size: {:.1}em\" title=\"{}\">{}</a>",
+                    size + 1,
+                    escape_html(&link),
+                    0.8 + 0.3 * size as f32,
+                    entry.count(),
+                    escape_html(&name)
+                ));
+            } else {
+                sizes[size].push(format!("[{name}]({link})"));
+            }
+        }
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        if html {
+            if !cloud.is_empty() {
+                md.push_str(&format!(
+                    "<p class=\"indexer-cloud\">\n{}\n</p>\n",
+                    cloud.join("\n")
+                ));
+            }
+        } else {
+            for size in sizes.iter().rev().filter(|size| !size.is_empty()) {
+                md.push_str(&format!("- {}\n", size.join(" · ")));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The pairs of listed tags found in the most chapters together, as a
+    /// table. With nested keys, a tag isn't paired with its own ancestors.
+    pub fn co_occurrence_chapter(&self, kind: &EntityKind, index: &Index) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.co_occurrence_file;
+        let title = &self.config.co_occurrence_title;
+        let tags: Vec<(&str, &Entry, HashSet<&str>)> = index
+            .keyed()
+            .filter(|(_, entry)| self.is_listed(entry))
+            .map(|(key, entry)| {
+                let pages = entry
+                    .occurrences
+                    .iter()
+                    .map(|occurrence| occurrence.page.as_str())
+                    .filter(|page| !page.is_empty() && self.chapters.contains_key(*page))
+                    .collect();
+                (key, entry, pages)
+            })
+            .collect();
+        let nests = |parent: &str, child: &str| {
+            kind.nested
+                && child
+                    .strip_prefix(parent)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        };
+
+        let mut pairs = Vec::new();
+        for (i, (a_key, a, a_pages)) in tags.iter().enumerate() {
+            for (b_key, b, b_pages) in &tags[i + 1..] {
+                if nests(a_key, b_key) || nests(b_key, a_key) {
+                    continue;
+                }
+                let shared = a_pages.intersection(b_pages).count();
+                if shared > 0 {
+                    pairs.push((shared, (*a_key, *a), (*b_key, *b)));
+                }
+            }
+        }
+        pairs.sort_by_key(|&(shared, (a, _), (b, _))| (Reverse(shared), a, b));
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        if !pairs.is_empty() {
+            md.push_str(&format!(
+                "| {} | {} |\n|---|---:|\n",
+                labels.together, labels.shared_chapters
+            ));
+        }
+        let link = |entry: &Entry| {
+            let href = link_to(Path::new(file), Path::new(&entry.file), &entry.anchor);
+            format!(
+                "[{}{}]({})",
+                kind.prefix,
+                escape_markdown(&entry.display),
+                href
+            )
+        };
+        for (shared, (_, a), (_, b)) in pairs.into_iter().take(self.config.co_occurrence_pairs) {
+            md.push_str(&format!("| {} · {} | {} |\n", link(a), link(b), shared));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The statistics chapter: entries and occurrences of each kind, the
+    /// average number of distinct keys per chapter, the most used keys, and
+    /// the chapters with the most and the fewest indexed tokens.
+    pub fn stats_chapter(&self, kinds: &[EntityKind], indexes: &[Index]) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.stats_file;
+        let title = &self.config.stats_title;
+        let from = Path::new(file);
+        // Drafts have no page to count
+        let mut links: HashMap<&str, usize> = self
+            .chapters
+            .keys()
+            .filter(|page| !page.is_empty())
+            .map(|page| (page.as_str(), 0))
+            .collect();
+        let chapters = links.len();
+
+        let mut md = format!(
+            "{GENERATED_MARKER}\n# {title}\n\n## {}\n\n| | {} | {} | {} |\n|---|---:|---:|---:|\n",
+            labels.totals, labels.entries, labels.occurrences, labels.per_chapter
+        );
+        for (kind, index) in kinds.iter().zip(indexes) {
+            let mut total = 0;
+            let mut written = 0;
+            for (key, entry) in index.keyed() {
+                for (page, count) in written_counts(kind, index, key, entry) {
+                    total += count;
+                    written += 1;
+                    if let Some(links) = links.get_mut(page) {
+                        *links += count;
+                    }
+                }
+            }
+            let average = if chapters == 0 {
+                0.0
+            } else {
+                written as f64 / chapters as f64
+            };
+            md.push_str(&format!(
+                "| {} | {} | {} | {:.1} |\n",
+                escape_markdown(&kind.title),
+                index.entries().count(),
+                total,
+                average
+            ));
+        }
+
+        md.push_str(&format!("\n## {}\n", labels.most_used));
+        for (kind, index) in kinds.iter().zip(indexes) {
+            let mut entries: Vec<&Entry> = index.entries().collect();
+            if entries.is_empty() {
+                continue;
+            }
+            entries.sort_by(|a, b| b.count().cmp(&a.count()).then(a.display.cmp(&b.display)));
+            md.push_str(&format!("\n### {}\n\n", kind.title));
+            for entry in entries.into_iter().take(STATS_TOP_ENTRIES) {
+                let link = link_to(from, Path::new(&entry.file), &entry.anchor);
+                md.push_str(&format!(
+                    "1. [{}{}]({}) ({})\n",
+                    kind.prefix,
+                    entry.display,
+                    link,
+                    entry.count()
+                ));
+            }
+        }
+
+        let mut pages: Vec<(&str, usize)> = links.into_iter().collect();
+        let position = |page: &str| self.chapters.get(page).map(|chapter| chapter.position);
+        pages.sort_by(|(a, a_links), (b, b_links)| {
+            b_links.cmp(a_links).then(position(a).cmp(&position(b)))
+        });
+        let most = pages.iter().take(STATS_TOP_CHAPTERS);
+        md.push_str(&self.chapter_ranking(&labels.most_linked, from, most));
+        pages.sort_by(|(a, a_links), (b, b_links)| {
+            a_links.cmp(b_links).then(position(a).cmp(&position(b)))
+        });
+        let least = pages.iter().take(STATS_TOP_CHAPTERS);
+        md.push_str(&self.chapter_ranking(&labels.least_linked, from, least));
+
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The combined index: the listed entries of every kind interleaved
+    /// alphabetically under a heading per initial, each followed by the
+    /// chapters referencing it.
+    pub fn master_index_chapter(&self, kinds: &[EntityKind], indexes: &[Index]) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.master_index_file;
+        let title = &self.config.master_index_title;
+        let from = Path::new(file);
+        let mut entries: Vec<(&EntityKind, &Entry)> = kinds
+            .iter()
+            .zip(indexes)
+            .flat_map(|(kind, index)| index.entries().map(move |entry| (kind, entry)))
+            .filter(|(_, entry)| self.is_listed(entry))
+            .collect();
+        // Kinds stay in configuration order for keys spelled alike
+        entries.sort_by_cached_key(|(_, entry)| {
+            (
+                initial(&entry.display, labels),
+                entry.display.to_lowercase(),
+            )
+        });
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        let mut letter = None;
+        for (kind, entry) in entries {
+            let initial = initial(&entry.display, labels);
+            if letter.as_ref() != Some(&initial) {
+                let id = format!("letter-{}", slug(&initial));
+                md.push_str(&format!("\n## {initial} {{#{id}}}\n\n"));
+                letter = Some(initial);
+            }
+            let link = link_to(from, Path::new(&entry.file), &entry.anchor);
+            let pages: Vec<String> = entry
+                .pages()
+                .map(|occurrences| {
+                    let first = &occurrences[0];
+                    let link = link_to(from, Path::new(&first.page), &first.anchor);
+                    let count = self.count_suffix(occurrences.len());
+                    format!("[{}]({}){}", self.page_title(&first.page), link, count)
+                })
+                .collect();
+            md.push_str(&format!(
+                "- [{}{}]({}): {}\n",
+                kind.prefix,
+                entry.display,
+                link,
+                pages.join(", ")
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The footer listing the chapters at `sources`, which link to `chapter`,
+    /// in reading order.
+    pub fn backlinks(&self, chapter: &Chapter, sources: &[String]) -> String {
+        let from = chapter.path.clone().unwrap_or_default();
+        let mut sources = sources.to_vec();
+        sources.sort_by_key(|page| self.chapters.get(page).map(|chapter| chapter.position));
+        let items: Vec<String> = sources
+            .iter()
+            .map(|page| {
+                let link = relative_link(&from, Path::new(page));
+                format!("- [{}]({})", self.page_title(page), link)
+            })
+            .collect();
+        format!(
+            "\n{BACKLINKS_MARKER}\n\n---\n\n## {}\n\n{}\n",
+            self.config.labels.backlinks,
+            items.join("\n")
+        )
+    }
+
+    /// The comment suggesting `tags` appended to an untagged chapter, for its
+    /// author to find in the page's source.
+    pub fn suggestions(&self, tags: &[String]) -> String {
+        format!(
+            "\n{SUGGESTIONS_MARKER}\n<!-- Consider tagging this chapter with {} -->\n",
+            tags.join(", ")
+        )
+    }
+
+    /// The line saying how many of a chapter's `tasks` are done.
+    pub fn progress(&self, tasks: &[Task]) -> String {
+        format!(
+            "{PROGRESS_MARKER}<p class=\"indexer-progress\">{}</p>\n",
+            escape_html(&self.done_count(tasks))
+        )
+    }
+
+    /// The line showing `fields`, the metadata of the chapter at `page` by
+    /// front matter key, with the keys of `kinds` linked to their entries.
+    pub fn metadata(
+        &self,
+        page: &str,
+        fields: &[(String, Vec<String>)],
+        kinds: &[EntityKind],
+        indexes: &[Index],
+    ) -> String {
+        let html = self.renderer == "html";
+        let from = Path::new(page);
+        let shown: Vec<(String, Vec<String>)> = fields
+            .iter()
+            .map(|(field, values)| {
+                let label = self
+                    .config
+                    .metadata_labels
+                    .get(field)
+                    .cloned()
+                    .unwrap_or_else(|| capitalize(field));
+                let kind = kinds.iter().position(|kind| &kind.name == field);
+                let values = values
+                    .iter()
+                    .map(|value| {
+                        let entry =
+                            kind.and_then(|kind| Some((&kinds[kind], indexes[kind].get(value)?)));
+                        match entry {
+                            Some((kind, entry)) if html => format!(
+                                "<a href=\"{}\">{}</a>",
+                                link_to(from, Path::new(&entry.file), &entry.anchor),
+                                escape_html(&format!("{}{}", kind.prefix, entry.display))
+                            ),
+                            Some((kind, entry)) => format!(
+                                "[{}{}]({})",
+                                kind.prefix,
+                                escape_markdown(&entry.display),
+                                link_to(from, Path::new(&entry.file), &entry.anchor)
+                            ),
+                            None if html => escape_html(value),
+                            None => escape_markdown(value),
+                        }
+                    })
+                    .collect();
+                (label, values)
+            })
+            .collect();
+
+        if html && self.config.metadata_style == MetadataStyle::Table {
+            let rows: String = shown
+                .iter()
+                .map(|(label, values)| {
+                    format!(
+                        "<tr><th>{}</th><td>{}</td></tr>",
+                        escape_html(label),
+                        values.join(", ")
+                    )
+                })
+                .collect();
+            return format!(
+                "{METADATA_MARKER}<table class=\"indexer-metadata\">{rows}</table>\n\n"
+            );
+        }
+        let fields: Vec<String> = shown
+            .iter()
+            .map(|(label, values)| {
+                if html {
+                    format!("{}: {}", escape_html(label), values.join(", "))
+                } else {
+                    format!("{}: {}", escape_markdown(label), values.join(", "))
+                }
+            })
+            .collect();
+        if html {
+            format!(
+                "{METADATA_MARKER}<p class=\"indexer-metadata\">{}</p>\n\n",
+                fields.join(" · ")
+            )
+        } else {
+            // A line opening with a comment would be read as HTML
+            format!("*{}* {METADATA_MARKER}\n\n", fields.join(" · "))
+        }
+    }
+
+    /// The list, in reading order, of the chapters of `matching` that a
+    /// query of the chapter at `page` finds.
+    pub fn query_results(&self, page: &str, matching: &[String]) -> String {
+        let matching = self.in_reading_order(matching);
+        if matching.is_empty() {
+            return format!("*{}*\n", self.config.labels.no_matches);
+        }
+        matching
+            .into_iter()
+            .map(|target| {
+                let link = relative_link(Path::new(page), Path::new(target));
+                format!("- [{}]({link})\n", self.page_title(target))
+            })
+            .collect()
+    }
+
+    /// How many of `tasks` are done out of all, as in `3/7 tasks complete`.
+    fn done_count(&self, tasks: &[Task]) -> String {
+        let done = tasks.iter().filter(|task| task.done).count();
+        let label = &self.config.labels.tasks_complete;
+        format!("{done}/{} {label}", tasks.len())
+    }
+
+    /// The links appended to the daily note at `page` to the notes before
+    /// and after it, given as their day and path.
+    pub fn journal_links(
+        &self,
+        page: &str,
+        previous: Option<&(String, String)>,
+        next: Option<&(String, String)>,
+    ) -> String {
+        let link = |note: &str| relative_link(Path::new(page), Path::new(note));
+        let mut links = Vec::new();
+        if let Some((day, note)) = previous {
+            links.push(format!("[← {day}]({})", link(note)));
+        }
+        if let Some((day, note)) = next {
+            links.push(format!("[{day} →]({})", link(note)));
+        }
+        if links.is_empty() {
+            return String::new();
+        }
+        format!("\n{JOURNAL_MARKER}\n\n{}\n", links.join(" · "))
+    }
+
+    /// The list of `related` chapters appended to `chapter`, below its
+    /// backlinks if it has some.
+    pub fn related(&self, chapter: &Chapter, related: &[String]) -> String {
+        let from = chapter.path.clone().unwrap_or_default();
+        let items: Vec<String> = related
+            .iter()
+            .map(|page| {
+                let link = relative_link(&from, Path::new(page));
+                format!("- [{}]({})", self.page_title(page), link)
+            })
+            .collect();
+        let rule = if chapter.content.contains(BACKLINKS_MARKER) {
+            ""
+        } else {
+            "---\n\n"
+        };
+        format!(
+            "\n{RELATED_MARKER}\n\n{rule}## {}\n\n{}\n",
+            self.config.labels.related,
+            items.join("\n")
+        )
+    }
+
+    /// The chapters of `pages` appended to `chapter` by the `see_also` rules,
+    /// below its backlinks and related chapters if it has some.
+    pub fn see_also(&self, chapter: &Chapter, pages: &[&str]) -> String {
+        let from = chapter.path.clone().unwrap_or_default();
+        let items: Vec<String> = pages
+            .iter()
+            .map(|page| {
+                let link = relative_link(&from, Path::new(page));
+                format!("- [{}]({})", self.page_title(page), link)
+            })
+            .collect();
+        let rule = if chapter.content.contains(BACKLINKS_MARKER)
+            || chapter.content.contains(RELATED_MARKER)
+        {
+            ""
+        } else {
+            "---\n\n"
+        };
+        format!(
+            "\n{SEE_ALSO_MARKER}\n\n{rule}## {}\n\n{}\n",
+            self.config.labels.see_also,
+            items.join("\n")
+        )
+    }
+
+    /// The chapter showing `graph` as a Mermaid diagram, or with the HTML
+    /// renderer and `graph_viewer` on, as an interactive view of the graph
+    /// data embedded in the page.
+    pub fn graph_chapter(&self, graph: &Graph) -> Chapter {
+        let file = &self.config.graph_file;
+        let title = &self.config.graph_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        if self.config.graph_viewer && self.renderer == "html" {
+            let mut data = graph.json();
+            for node in data["nodes"].as_array_mut().into_iter().flatten() {
+                let page = node["path"].as_str().unwrap_or_default();
+                let anchor = node["anchor"].as_str().unwrap_or_default();
+                let link = link_to(Path::new(file), Path::new(page), anchor);
+                node["url"] = Value::from(html_link(&link));
+            }
+            // Nothing in the data may close the script element early
+            let data = data.to_string().replace("</", "<\\/");
+            md.push_str(&format!(
+                "<canvas id=\"indexer-graph\" class=\"indexer-graph\" \
+                 style=\"width: 100%; height: 600px\"></canvas>\n\
+                 <script type=\"application/json\" id=\"indexer-graph-data\">{data}</script>\n\
+                 <script>\n{GRAPH_VIEWER}</script>\n"
+            ));
+        } else {
+            md.push_str(&format!("```mermaid\n{}```\n", graph.mermaid()));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The chapter standing in for a missing wikilink target at `page`,
+    /// listing the chapters linking to it in reading order.
+    pub fn stub_chapter(&self, page: &str, stub: &Stub) -> Chapter {
+        let mut sources = stub.sources.clone();
+        sources.sort_by_key(|page| self.chapters.get(page).map(|chapter| chapter.position));
+        let mut md = format!(
+            "{GENERATED_MARKER}\n{STUB_MARKER}\n# {}\n\n{}\n\n",
+            stub.title, self.config.labels.stub
+        );
+        for source in &sources {
+            let link = relative_link(Path::new(page), Path::new(source));
+            md.push_str(&format!("- [{}]({})\n", self.page_title(source), link));
+        }
+        Chapter::new(&stub.title, md, page, Vec::new())
+    }
+
+    /// The list of `orphans`, in reading order.
+    pub fn orphans_chapter(&self, orphans: &[String]) -> Chapter {
+        let file = &self.config.orphans_file;
+        let title = &self.config.orphans_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for page in orphans {
+            let link = relative_link(Path::new(file), Path::new(page));
+            md.push_str(&format!("- [{}]({})\n", self.page_title(page), link));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The glossary: every term with its definition and the chapters using
+    /// it, `uses` holding those of each definition, in reading order.
+    pub fn glossary_chapter(&self, definitions: &[Definition], uses: &[Vec<String>]) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.glossary_file;
+        let title = &self.config.glossary_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        for (definition, pages) in definitions.iter().zip(uses) {
+            md.push_str(&format!("\n## {}\n\n", definition.term));
+            if !definition.definition.is_empty() {
+                md.push_str(&format!("{}\n\n", definition.definition));
+            }
+            if !pages.is_empty() {
+                let links: Vec<String> = pages
+                    .iter()
+                    .map(|page| {
+                        let link = relative_link(Path::new(file), Path::new(page));
+                        format!("[{}]({})", self.page_title(page), link)
+                    })
+                    .collect();
+                md.push_str(&format!("{}: {}\n", labels.used_in, links.join(", ")));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The table of `acronyms`, with what they stand for and the chapters in
+    /// `uses`, in reading order.
+    pub fn acronyms_chapter(
+        &self,
+        acronyms: &BTreeMap<String, String>,
+        uses: &BTreeMap<String, Vec<String>>,
+    ) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.acronym_file;
+        let title = &self.config.acronym_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        if !acronyms.is_empty() {
+            md.push_str(&format!(
+                "| {} | {} | {} |\n|---|---|---|\n",
+                labels.acronym, labels.meaning, labels.used_in
+            ));
+        }
+        for (acronym, meaning) in acronyms {
+            let pages: Vec<String> = uses
+                .get(acronym)
+                .into_iter()
+                .flatten()
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            md.push_str(&format!(
+                "| {acronym} | {} | {} |\n",
+                escape_markdown(meaning),
+                pages.join(", ")
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The tasks of every chapter of `tasks`, by path, under a heading per
+    /// chapter, in reading order, with how many are done.
+    pub fn tasks_chapter(&self, tasks: &BTreeMap<String, Vec<Task>>) -> Chapter {
+        let file = &self.config.tasks_file;
+        let title = &self.config.tasks_title;
+        let mut pages: Vec<(&String, &Vec<Task>)> = tasks
+            .iter()
+            .filter(|(page, tasks)| self.chapters.contains_key(*page) && !tasks.is_empty())
+            .collect();
+        pages.sort_by_key(|(page, _)| self.chapters[*page].position);
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        let all: Vec<Task> = pages
+            .iter()
+            .flat_map(|(_, tasks)| tasks.iter().cloned())
+            .collect();
+        if !all.is_empty() {
+            let done = all.iter().filter(|task| task.done).count();
+            let percent = done * 100 / all.len();
+            md.push_str(&format!("\n{} ({percent}%)\n", self.done_count(&all)));
+        }
+        for (page, tasks) in pages {
+            let link = relative_link(Path::new(file), Path::new(page));
+            let done = tasks.iter().filter(|task| task.done).count();
+            md.push_str(&format!(
+                "\n## [{}]({}) ({done}/{})\n\n",
+                self.page_title(page),
+                link,
+                tasks.len()
+            ));
+            for task in tasks {
+                let mark = if task.done { 'x' } else { ' ' };
+                md.push_str(&format!("- [{mark}] {}\n", escape_markdown(&task.text)));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The board: a section per status of `columns`, with the chapters
+    /// tagged with it, then its tasks, in reading order.
+    pub fn kanban_chapter(&self, columns: &[Column]) -> Chapter {
+        let file = &self.config.kanban_file;
+        let title = &self.config.kanban_title;
+        let link = |page: &str| {
+            let link = relative_link(Path::new(file), Path::new(page));
+            format!("[{}]({})", self.page_title(page), link)
+        };
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        for column in columns {
+            md.push_str(&format!("\n## {}\n\n", capitalize(&column.status)));
+            let mut pages: Vec<&String> = column
+                .pages
+                .iter()
+                .filter(|page| self.chapters.contains_key(*page))
+                .collect();
+            pages.sort_by_key(|page| self.chapters[*page].position);
+            for page in pages {
+                md.push_str(&format!("- {}\n", link(page)));
+            }
+            let mut tasks: Vec<&(String, Task)> = column
+                .tasks
+                .iter()
+                .filter(|(page, _)| self.chapters.contains_key(page))
+                .collect();
+            tasks.sort_by_key(|(page, _)| self.chapters[page].position);
+            for (page, task) in tasks {
+                let mark = if task.done { 'x' } else { ' ' };
+                let text = escape_markdown(&task.text);
+                md.push_str(&format!("- [{mark}] {text} — {}\n", link(page)));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The agenda: what is overdue at `today`, the earliest first, then a
+    /// section per date for the rest. Tasks done are never overdue.
+    pub fn agenda_chapter(&self, due: &[Due], today: &str) -> Chapter {
+        let file = &self.config.agenda_file;
+        let title = &self.config.agenda_title;
+        let mut due: Vec<&Due> = due
+            .iter()
+            .filter(|due| self.chapters.contains_key(&due.page))
+            .collect();
+        due.sort_by(|a, b| {
+            (a.date.cmp(&b.date)).then_with(|| {
+                let position = |due: &Due| self.chapters[&due.page].position;
+                position(a).cmp(&position(b))
+            })
+        });
+        let item = |due: &Due, date: bool| {
+            let relative = relative_link(Path::new(file), Path::new(&due.page));
+            let link = format!("[{}]({})", self.page_title(&due.page), relative);
+            let date = if date {
+                format!("{}: ", due.date)
+            } else {
+                String::new()
+            };
+            match &due.task {
+                Some(task) => {
+                    let mark = if task.done { 'x' } else { ' ' };
+                    let text = escape_markdown(&task.text);
+                    format!("- [{mark}] {date}{text} — {link}\n")
+                }
+                None => format!("- {date}{link}\n"),
+            }
+        };
+        let overdue = |due: &Due| {
+            due.date.as_str() < today && due.task.as_ref().is_none_or(|task| !task.done)
+        };
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        if due.iter().any(|due| overdue(due)) {
+            md.push_str(&format!("\n## {}\n\n", self.config.labels.overdue));
+            for due in due.iter().filter(|due| overdue(due)) {
+                md.push_str(&item(due, true));
+            }
+        }
+        let mut date = None;
+        for due in due.into_iter().filter(|due| !overdue(due)) {
+            if date != Some(&due.date) {
+                md.push_str(&format!("\n## {}\n\n", due.date));
+                date = Some(&due.date);
+            }
+            md.push_str(&item(due, false));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The timeline: a section per date of `dated`, the earliest first,
+    /// listing where it appears in reading order.
+    pub fn timeline_chapter(&self, dated: &[Dated]) -> Chapter {
+        let file = &self.config.timeline_file;
+        let title = &self.config.timeline_title;
+        let mut dated: Vec<&Dated> = dated
+            .iter()
+            .filter(|dated| self.chapters.contains_key(&dated.page))
+            .collect();
+        // Stable, so dates of a chapter stay in the order they appear
+        dated.sort_by(|a, b| {
+            (a.date.cmp(&b.date)).then_with(|| {
+                let position = |dated: &Dated| self.chapters[&dated.page].position;
+                position(a).cmp(&position(b))
+            })
+        });
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        let mut date = None;
+        for dated in dated {
+            if date != Some(&dated.date) {
+                md.push_str(&format!("\n## {}\n\n", dated.date));
+                date = Some(&dated.date);
+            }
+            let link = link_to(Path::new(file), Path::new(&dated.page), &dated.anchor);
+            md.push_str(&format!("- [{}]({})", self.page_title(&dated.page), link));
+            if !dated.snippet.is_empty() {
+                md.push_str(&format!(": {}", escape_markdown(&dated.snippet)));
+            }
+            md.push('\n');
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The journal's calendar: a table per month of `notes`, given as their
+    /// day and path, the earliest first, the days with a note linking to it.
+    pub fn journal_chapter(&self, notes: &[(String, String)]) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.journal_file;
+        let title = &self.config.journal_title;
+        let mut months: BTreeMap<(i64, u32), BTreeMap<u32, &str>> = BTreeMap::new();
+        for (day, page) in notes {
+            if let Some((year, month, day)) = dates::parse(day) {
+                months.entry((year, month)).or_default().insert(day, page);
+            }
+        }
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        for ((year, month), days) in months {
+            let name = labels
+                .months
+                .get(month as usize - 1)
+                .cloned()
+                .unwrap_or_else(|| month.to_string());
+            let weekdays: Vec<String> = (0..7)
+                .map(|i| labels.weekdays.get(i).cloned().unwrap_or_default())
+                .collect();
+            md.push_str(&format!(
+                "\n## {name} {year}\n\n| {} |\n",
+                weekdays.join(" | ")
+            ));
+            md.push_str(&format!("|{}\n", "---|".repeat(7)));
+            let mut cells = vec![String::new(); dates::weekday(year, month, 1)];
+            for day in 1..=dates::month_len(year, month) {
+                cells.push(match days.get(&day) {
+                    Some(page) => {
+                        let link = relative_link(Path::new(file), Path::new(page));
+                        format!("[{day}]({link})")
+                    }
+                    None => day.to_string(),
+                });
+            }
+            cells.resize(cells.len().div_ceil(7) * 7, String::new());
+            for week in cells.chunks(7) {
+                md.push_str(&format!("| {} |\n", week.join(" | ")));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The reading list: each of `readings` by its link or text, with the
+    /// chapter it comes from, in reading order.
+    pub fn reading_list_chapter(&self, readings: &[Reading]) -> Chapter {
+        let file = &self.config.reading_list_file;
+        let title = &self.config.reading_list_title;
+        let mut readings: Vec<&Reading> = readings
+            .iter()
+            .filter(|reading| self.chapters.contains_key(&reading.page))
+            .collect();
+        readings.sort_by_key(|reading| self.chapters[&reading.page].position);
+        let chapter_link = |page: &str| {
+            let link = relative_link(Path::new(file), Path::new(page));
+            format!("[{}]({})", self.page_title(page), link)
+        };
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for reading in readings {
+            let source = chapter_link(&reading.page);
+            let item = match &reading.link {
+                Some(path) if is_relative_link(path) => {
+                    let text = match reading.title.as_str() {
+                        "" => self.page_title(path),
+                        title => title.to_string(),
+                    };
+                    let link = relative_link(Path::new(file), Path::new(path));
+                    format!("[{}]({}) — {source}", escape_markdown(&text), link)
+                }
+                Some(url) if reading.title.is_empty() => format!("<{url}> — {source}"),
+                Some(url) => format!("[{}]({url}) — {source}", escape_markdown(&reading.title)),
+                None if reading.title.is_empty() => source,
+                None => format!("{} — {source}", escape_markdown(&reading.title)),
+            };
+            md.push_str(&format!("- {item}\n"));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// Every one of `quotes`, attributed to who said it if known, and
+    /// linking to where it is quoted, in reading order.
+    pub fn quotes_chapter(&self, quotes: &[Quote]) -> Chapter {
+        let file = &self.config.quotes_file;
+        let title = &self.config.quotes_title;
+        let mut quotes: Vec<&Quote> = quotes
+            .iter()
+            .filter(|quote| self.chapters.contains_key(&quote.page))
+            .collect();
+        quotes.sort_by_key(|quote| self.chapters[&quote.page].position);
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        for quote in quotes {
+            md.push('\n');
+            for paragraph in &quote.paragraphs {
+                md.push_str(&format!("> {}\n>\n", escape_markdown(paragraph)));
+            }
+            let link = relative_link(Path::new(file), Path::new(&quote.page));
+            let source = format!("[{}]({})", self.page_title(&quote.page), link);
+            match &quote.attribution {
+                Some(name) => md.push_str(&format!("> — {}, {source}\n", escape_markdown(name))),
+                None => md.push_str(&format!("> — {source}\n")),
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The endnotes: every one of `notes`, numbered, with links to the
+    /// chapters defining it.
+    pub fn endnotes_chapter(&self, notes: &[Note]) -> Chapter {
+        let file = &self.config.endnotes_file;
+        let title = &self.config.endnotes_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for (i, note) in notes.iter().enumerate() {
+            let links: Vec<String> = note
+                .pages
+                .iter()
+                .filter(|page| self.chapters.contains_key(*page))
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            md.push_str(&format!(
+                "{}. {} ({})\n",
+                i + 1,
+                escape_markdown(&note.text),
+                links.join(", ")
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of `callouts`, a section per kind in the order of
+    /// `callout_kinds` or by name, each in reading order.
+    pub fn callouts_chapter(&self, callouts: &[Callout]) -> Chapter {
+        let file = &self.config.callouts_file;
+        let title = &self.config.callouts_title;
+        let kinds: Vec<String> = match &self.config.callout_kinds {
+            Some(kinds) => kinds.iter().map(|kind| kind.to_lowercase()).collect(),
+            None => {
+                let kinds: BTreeSet<&String> = callouts.iter().map(|c| &c.kind).collect();
+                kinds.into_iter().cloned().collect()
+            }
+        };
+        let mut callouts: Vec<&Callout> = callouts
+            .iter()
+            .filter(|callout| self.chapters.contains_key(&callout.page))
+            .collect();
+        callouts.sort_by_key(|callout| self.chapters[&callout.page].position);
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        for kind in kinds {
+            let of_kind: Vec<&&Callout> = callouts.iter().filter(|c| c.kind == kind).collect();
+            if of_kind.is_empty() {
+                continue;
+            }
+            md.push_str(&format!("\n## {}\n\n", capitalize(&kind)));
+            for callout in of_kind {
+                let link = relative_link(Path::new(file), Path::new(&callout.page));
+                let source = format!("[{}]({})", self.page_title(&callout.page), link);
+                let title = escape_markdown(&callout.title);
+                let text = escape_markdown(&callout.text);
+                let item = match (title.as_str(), text.as_str()) {
+                    ("", "") => source,
+                    ("", text) => format!("{text} — {source}"),
+                    (title, "") => format!("**{title}** — {source}"),
+                    (title, text) => format!("**{title}**: {text} — {source}"),
+                };
+                md.push_str(&format!("- {item}\n"));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The table of `images`, each once with its alt texts and the chapters
+    /// showing it, in reading order. With the HTML renderer and
+    /// `image_thumbnails` on, each image is shown small.
+    pub fn images_chapter(&self, images: &[Image]) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.images_file;
+        let title = &self.config.images_title;
+        let mut images: Vec<&Image> = images
+            .iter()
+            .filter(|image| self.chapters.contains_key(&image.page))
+            .collect();
+        images.sort_by_key(|image| self.chapters[&image.page].position);
+        let mut sources: Vec<(&str, Vec<&Image>)> = Vec::new();
+        for image in images {
+            match sources.iter_mut().find(|(src, _)| *src == image.src) {
+                Some((_, shown)) => shown.push(image),
+                None => sources.push((&image.src, vec![image])),
+            }
+        }
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        if !sources.is_empty() {
+            md.push_str(&format!(
+                "| {} | {} | {} |\n|---|---|---|\n",
+                labels.image, labels.alt_text, labels.used_in
+            ));
+        }
+        let thumbnails = self.config.image_thumbnails && self.renderer == "html";
+        for (src, shown) in sources {
+            let link = if is_relative_link(src) {
+                relative_link(Path::new(file), Path::new(src))
+            } else {
+                src.to_string()
+            };
+            let mut alts: Vec<&str> = Vec::new();
+            let mut pages: Vec<&str> = Vec::new();
+            for image in &shown {
+                if !image.alt.is_empty() && !alts.contains(&image.alt.as_str()) {
+                    alts.push(&image.alt);
+                }
+                if !pages.contains(&image.page.as_str()) {
+                    pages.push(&image.page);
+                }
+            }
+            let image = if thumbnails {
+                format!(
+                    "<img src=\"{}\" alt=\"{}\" width=\"120\">",
+                    escape_html(&link),
+                    escape_html(alts.first().unwrap_or(&""))
+                )
+            } else {
+                format!("[{}]({link})", escape_markdown(src))
+            };
+            let alts: Vec<String> = alts.iter().map(|alt| escape_markdown(alt)).collect();
+            let pages: Vec<String> = pages
+                .iter()
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            md.push_str(&format!(
+                "| {image} | {} | {} |\n",
+                alts.join(" / "),
+                pages.join(", ")
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of `tables`, by number, linking to each.
+    pub fn tables_chapter(&self, tables: &[Table]) -> Chapter {
+        let label = &self.config.labels.table;
+        let file = &self.config.tables_file;
+        let title = &self.config.tables_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for table in tables
+            .iter()
+            .filter(|table| self.chapters.contains_key(&table.page))
+        {
+            let caption = match &table.caption {
+                Some(caption) => caption.clone(),
+                None => format!("{label} {}", table.number),
+            };
+            let anchor = tables::anchor(table.number);
+            let link = link_to(Path::new(file), Path::new(&table.page), &anchor);
+            let chapter = relative_link(Path::new(file), Path::new(&table.page));
+            md.push_str(&format!(
+                "{}. [{caption}]({link}) — [{}]({chapter})\n",
+                table.number,
+                self.page_title(&table.page)
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The external links of the book, a section per domain, each with the
+    /// chapters linking to it.
+    pub fn external_links_chapter(&self, domains: &BTreeMap<String, Vec<ExternalLink>>) -> Chapter {
+        let file = &self.config.external_links_file;
+        let title = &self.config.external_links_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        for (domain, links) in domains {
+            md.push_str(&format!("\n## {}\n\n", escape_markdown(domain)));
+            for link in links {
+                let mut pages: Vec<&String> = link
+                    .pages
+                    .iter()
+                    .filter(|page| self.chapters.contains_key(*page))
+                    .collect();
+                pages.sort_by_key(|page| self.chapters[*page].position);
+                let pages: Vec<String> = pages
+                    .into_iter()
+                    .map(|page| {
+                        let link = relative_link(Path::new(file), Path::new(page));
+                        format!("[{}]({})", self.page_title(page), link)
+                    })
+                    .collect();
+                md.push_str(&format!("- <{}> — {}\n", link.url, pages.join(", ")));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of `symbols`, ignoring case, each with the chapters showing
+    /// it in reading order.
+    pub fn symbols_chapter(&self, symbols: &BTreeMap<String, Vec<String>>) -> Chapter {
+        let file = &self.config.symbols_file;
+        let title = &self.config.symbols_title;
+        let mut sorted: Vec<(&String, &Vec<String>)> = symbols.iter().collect();
+        sorted.sort_by_key(|(symbol, _)| symbol.to_lowercase());
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for (symbol, pages) in sorted {
+            let mut pages: Vec<&String> = pages
+                .iter()
+                .filter(|page| self.chapters.contains_key(*page))
+                .collect();
+            if pages.is_empty() {
+                continue;
+            }
+            pages.sort_by_key(|page| self.chapters[*page].position);
+            let links: Vec<String> = pages
+                .into_iter()
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            md.push_str(&format!("- `{symbol}` — {}\n", links.join(", ")));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of the `references` cited, in `cited` by key with the
+    /// chapters citing each, sorted by author, then year.
+    pub fn references_chapter(
+        &self,
+        references: &BTreeMap<String, Reference>,
+        cited: &BTreeMap<String, Vec<String>>,
+    ) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.references_file;
+        let title = &self.config.references_title;
+        let mut listed: Vec<&Reference> =
+            cited.keys().filter_map(|key| references.get(key)).collect();
+        listed.sort_by_key(|reference| reference.sort_key());
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for reference in listed {
+            let mut pages: Vec<&String> = cited[&reference.key]
+                .iter()
+                .filter(|page| self.chapters.contains_key(*page))
+                .collect();
+            pages.sort_by_key(|page| self.chapters[*page].position);
+            let links: Vec<String> = pages
+                .into_iter()
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            md.push_str(&format!(
+                "- <a id=\"{}\"></a>{} — {}\n",
+                citations::anchor(&reference.key),
+                reference.formatted(labels),
+                links.join(", ")
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of the DOIs and arXiv identifiers of `sources`, each with
+    /// its resolver's URL and the chapters giving it.
+    pub fn sources_chapter(&self, sources: &BTreeMap<String, (String, Vec<String>)>) -> Chapter {
+        let file = &self.config.sources_file;
+        let title = &self.config.sources_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for (identifier, (url, pages)) in sources {
+            let mut pages: Vec<&String> = pages
+                .iter()
+                .filter(|page| self.chapters.contains_key(*page))
+                .collect();
+            if pages.is_empty() {
+                continue;
+            }
+            pages.sort_by_key(|page| self.chapters[*page].position);
+            let links: Vec<String> = pages
+                .into_iter()
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            md.push_str(&format!(
+                "- [{}]({url}) — {}\n",
+                escape_markdown(identifier),
+                links.join(", ")
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of `commits`, by hash, each linking to the repository and
+    /// to the chapters naming it.
+    pub fn commits_chapter(&self, commits: &BTreeMap<String, Vec<String>>) -> Chapter {
+        let file = &self.config.commits_file;
+        let title = &self.config.commits_title;
+        let repository = self.config.commit_repository().unwrap_or_default();
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for (hash, pages) in commits {
+            let mut pages: Vec<&String> = pages
+                .iter()
+                .filter(|page| self.chapters.contains_key(*page))
+                .collect();
+            if pages.is_empty() {
+                continue;
+            }
+            pages.sort_by_key(|page| self.chapters[*page].position);
+            let links: Vec<String> = pages
+                .into_iter()
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            md.push_str(&format!(
+                "- [`{hash}`]({repository}/commit/{hash}) — {}\n",
+                links.join(", ")
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of `tickets`, a section per project, ordered by number, each
+    /// linking to the tracker and to the chapters discussing it.
+    pub fn tickets_chapter(&self, tickets: &BTreeMap<String, Vec<String>>) -> Chapter {
+        let file = &self.config.tickets_file;
+        let title = &self.config.tickets_title;
+        let mut projects: BTreeMap<&str, Vec<(&String, Vec<&String>)>> = BTreeMap::new();
+        for (key, pages) in tickets {
+            let mut pages: Vec<&String> = pages
+                .iter()
+                .filter(|page| self.chapters.contains_key(*page))
+                .collect();
+            if pages.is_empty() {
+                continue;
+            }
+            pages.sort_by_key(|page| self.chapters[*page].position);
+            let project = key.rsplit_once('-').map_or("", |(project, _)| project);
+            projects.entry(project).or_default().push((key, pages));
+        }
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        for (project, mut keys) in projects {
+            keys.sort_by_key(|(key, _)| {
+                let number = key
+                    .rsplit_once('-')
+                    .map_or(key.as_str(), |(_, number)| number);
+                (number.parse::<u64>().unwrap_or(u64::MAX), key.to_string())
+            });
+            if !project.is_empty() {
+                md.push_str(&format!("\n## {}\n", escape_markdown(project)));
+            }
+            md.push('\n');
+            for (key, pages) in keys {
+                let url = self.config.ticket_url(key).unwrap_or_default();
+                let links: Vec<String> = pages
+                    .into_iter()
+                    .map(|page| {
+                        let link = relative_link(Path::new(file), Path::new(page));
+                        format!("[{}]({})", self.page_title(page), link)
+                    })
+                    .collect();
+                md.push_str(&format!(
+                    "- [{}]({url}) — {}\n",
+                    escape_markdown(key),
+                    links.join(", ")
+                ));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of the pages outside the book the chapters link to through
+    /// `interwiki` sources, source by source, each with the chapters linking
+    /// to it.
+    pub fn interwiki_chapter(
+        &self,
+        sources: &BTreeMap<String, BTreeMap<String, (String, Vec<String>)>>,
+    ) -> Chapter {
+        let file = &self.config.interwiki_file;
+        let title = &self.config.interwiki_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        for (source, pages) in sources {
+            md.push_str(&format!("\n## {}\n\n", escape_markdown(source)));
+            for (key, (url, linking)) in pages {
+                let links: Vec<String> = self
+                    .in_reading_order(linking)
+                    .into_iter()
+                    .map(|page| {
+                        let link = relative_link(Path::new(file), Path::new(page));
+                        format!("[{}]({})", self.page_title(page), link)
+                    })
+                    .collect();
+                let shown = escape_markdown(&key.replace('_', " "));
+                md.push_str(&format!("- [{shown}]({url}) — {}\n", links.join(", ")));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of the email addresses of `contacts`, each with the chapters
+    /// referencing it, and spelled as they are with `obfuscate_emails` on.
+    pub fn contacts_chapter(&self, contacts: &BTreeMap<String, Vec<String>>) -> Chapter {
+        let file = &self.config.contacts_file;
+        let title = &self.config.contacts_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for (address, pages) in contacts {
+            let mut pages: Vec<&String> = pages
+                .iter()
+                .filter(|page| self.chapters.contains_key(*page))
+                .collect();
+            if pages.is_empty() {
+                continue;
+            }
+            pages.sort_by_key(|page| self.chapters[*page].position);
+            let links: Vec<String> = pages
+                .into_iter()
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            let address = if self.config.obfuscate_emails {
+                emails::obfuscate(address, self.renderer == "html")
+            } else {
+                format!("<{address}>")
+            };
+            md.push_str(&format!("- {address} — {}\n", links.join(", ")));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of `locations`, each linking to the map where it is known,
+    /// with the chapters set there.
+    pub fn locations_chapter(&self, locations: &BTreeMap<String, Location>) -> Chapter {
+        let file = &self.config.locations_file;
+        let title = &self.config.locations_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for location in locations.values() {
+            let links: Vec<String> = self
+                .in_reading_order(&location.pages)
+                .into_iter()
+                .map(|page| {
+                    let link = relative_link(Path::new(file), Path::new(page));
+                    format!("[{}]({})", self.page_title(page), link)
+                })
+                .collect();
+            if links.is_empty() {
+                continue;
+            }
+            let name = escape_markdown(&location.name);
+            let name = match location.point {
+                Some(point) => format!("[{name}]({})", point.url()),
+                None => name,
+            };
+            md.push_str(&format!("- {name} — {}\n", links.join(", ")));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The map of the HTML renderer, with a marker on each of `locations`
+    /// whose coordinates are known, opening the chapters set there.
+    pub fn location_map_chapter(&self, locations: &BTreeMap<String, Location>) -> Chapter {
+        let file = &self.config.location_map_file;
+        let title = &self.config.location_map_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        let places: Vec<Value> = locations
+            .values()
+            .filter_map(|location| {
+                let point = location.point?;
+                let pages: Vec<Value> = self
+                    .in_reading_order(&location.pages)
+                    .into_iter()
+                    .map(|page| {
+                        let link = relative_link(Path::new(file), Path::new(page));
+                        json!({ "title": self.page_title(page), "url": html_link(&link) })
+                    })
+                    .collect();
+                (!pages.is_empty()).then(|| {
+                    json!({
+                        "name": location.name,
+                        "lat": point.lat,
+                        "lon": point.lon,
+                        "pages": pages,
+                    })
+                })
+            })
+            .collect();
+        // Nothing in the data may close the script element early
+        let data = json!({ "places": places })
+            .to_string()
+            .replace("</", "<\\/");
+        md.push_str(&format!(
+            "<link rel=\"stylesheet\" href=\"{LEAFLET}/leaflet.css\">\n\
+             <script src=\"{LEAFLET}/leaflet.js\"></script>\n\
+             <div id=\"indexer-map\" class=\"indexer-map\" \
+             style=\"width: 100%; height: 600px\"></div>\n\
+             <script type=\"application/json\" id=\"indexer-map-data\">{data}</script>\n\
+             <script>\n{MAP_VIEWER}</script>\n"
+        ));
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The events' calendar: `events` month by month, the earliest first,
+    /// each linking to where the chapters describing it mention it.
+    pub fn events_chapter(&self, events: &[Event]) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.events_file;
+        let title = &self.config.events_title;
+        let mut grouped: BTreeMap<(&str, String), Vec<&Event>> = BTreeMap::new();
+        for event in events
+            .iter()
+            .filter(|event| self.chapters.contains_key(&event.page))
+        {
+            let key = (event.date.as_str(), event.name.to_lowercase());
+            grouped.entry(key).or_default().push(event);
+        }
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+        let mut month = None;
+        for ((date, _), mut described) in grouped {
+            let Some((year, number, _)) = dates::parse(date) else {
+                continue;
+            };
+            if month != Some((year, number)) {
+                let name = labels
+                    .months
+                    .get(number as usize - 1)
+                    .cloned()
+                    .unwrap_or_else(|| number.to_string());
+                md.push_str(&format!("\n## {name} {year}\n\n"));
+                month = Some((year, number));
+            }
+            described.sort_by_key(|event| self.chapters[&event.page].position);
+            let links: Vec<String> = described
+                .iter()
+                .map(|event| {
+                    let link = link_to(Path::new(file), Path::new(&event.page), &event.anchor);
+                    format!("[{}]({})", self.page_title(&event.page), link)
+                })
+                .collect();
+            md.push_str(&format!(
+                "- {date}: {} — {}\n",
+                described[0].name,
+                links.join(", ")
+            ));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The review chapter of `cards`, chapter by chapter in reading order.
+    /// For the HTML renderer, answers are folded below their question, and
+    /// a link downloads the cards for Anki, into `deck`.
+    pub fn flashcards_chapter(&self, cards: &[Card], deck: &str) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.flashcards_file;
+        let title = &self.config.flashcards_title;
+        let html = self.renderer == "html";
+        let cards: Vec<&Card> = cards
+            .iter()
+            .filter(|card| self.chapters.contains_key(&card.page))
+            .collect();
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        if html && !cards.is_empty() {
+            md.push_str(&format!(
+                "<a download=\"{}.txt\" href=\"data:text/tab-separated-values;charset=utf-8,{}\">{}</a>\n\n",
+                slug(deck),
+                percent_encode(&flashcards::tsv(cards.iter().copied(), deck)),
+                escape_html(&labels.anki_download)
+            ));
+        }
+        let mut page = None;
+        for card in cards {
+            if page != Some(&card.page) {
+                let link = relative_link(Path::new(file), Path::new(&card.page));
+                md.push_str(&format!("## [{}]({link})\n\n", self.page_title(&card.page)));
+                page = Some(&card.page);
+            }
+            let link = link_to(Path::new(file), Path::new(&card.page), &card.anchor);
+            if html {
+                md.push_str(&format!(
+                    "<details>\n<summary>{}</summary>\n\n{}\n\n[↩]({link})\n\n</details>\n\n",
+                    flashcards::to_html(&card.question),
+                    card.answer
+                ));
+            } else {
+                // An answer of several lines may open with a list or a quote
+                let gap = if card.answer.contains('\n') {
+                    "\n\n"
+                } else {
+                    " "
+                };
+                md.push_str(&format!(
+                    "**Q:** {} [↩]({link})\n\n**A:**{gap}{}\n\n",
+                    card.question, card.answer
+                ));
+            }
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The chapter listing the former names of chapters, with a page at each
+    /// of `redirects`, given as its path, the alias and the chapter it names,
+    /// sending readers on to the chapter. HTML readers are sent right away.
+    pub fn redirects_chapter(&self, redirects: &[(String, String, String)]) -> Chapter {
+        let labels = &self.config.labels;
+        let file = &self.config.redirects_file;
+        let title = &self.config.redirects_title;
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        let mut sub_items = Vec::new();
+        for (path, alias, page) in redirects {
+            let target = relative_link(Path::new(file), Path::new(page));
+            md.push_str(&format!(
+                "- {} → [{}]({target})\n",
+                escape_markdown(alias),
+                self.page_title(page)
+            ));
+            let link = relative_link(Path::new(path), Path::new(page));
+            let mut redirect = format!("{GENERATED_MARKER}\n");
+            if self.renderer == "html" {
+                redirect.push_str(&format!(
+                    "<meta http-equiv=\"refresh\" content=\"0; url={}\">\n\n",
+                    escape_html(&html_link(&link))
+                ));
+            }
+            redirect.push_str(&format!(
+                "# {}\n\n{} [{}]({link})\n",
+                escape_markdown(alias),
+                labels.moved_to,
+                self.page_title(page)
+            ));
+            sub_items.push(BookItem::Chapter(Chapter::new(
+                alias,
+                redirect,
+                path,
+                vec![title.clone()],
+            )));
+        }
+        let mut chapter = Chapter::new(title, md, file, Vec::new());
+        chapter.sub_items = sub_items;
+        chapter
+    }
+
+    /// The register of the Zettelkasten: every note with an ID, by ID.
+    pub fn register_chapter(&self) -> Chapter {
+        let file = &self.config.register_file;
+        let title = &self.config.register_title;
+        let mut notes: Vec<(&String, &String)> = self
+            .chapters
+            .iter()
+            .filter_map(|(page, chapter)| Some((chapter.id.as_ref()?, page)))
+            .collect();
+        notes.sort();
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for (id, page) in notes {
+            let link = relative_link(Path::new(file), Path::new(page));
+            md.push_str(&format!("- {id} — [{}]({})\n", self.page_title(page), link));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// The list of chapters no index references, in reading order.
+    pub fn untagged_chapter(&self, indexes: &[Index]) -> Chapter {
+        let file = &self.config.untagged_file;
+        let title = &self.config.untagged_title;
+        let tagged: HashSet<&str> = indexes
+            .iter()
+            .flat_map(|index| index.entries())
+            .flat_map(|entry| &entry.occurrences)
+            .map(|occurrence| occurrence.page.as_str())
+            .collect();
+        let mut untagged: Vec<(&String, &ChapterInfo)> = self
+            .chapters
+            .iter()
+            .filter(|(page, _)| !page.is_empty() && !tagged.contains(page.as_str()))
+            .collect();
+        untagged.sort_by_key(|(_, chapter)| chapter.position);
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        for (page, _) in untagged {
+            let link = relative_link(Path::new(file), Path::new(page));
+            md.push_str(&format!("- [{}]({})\n", self.page_title(page), link));
+        }
+        Chapter::new(title, md, file, Vec::new())
+    }
+
+    /// A numbered list of chapters with their number of indexed tokens,
+    /// under `heading`.
+    fn chapter_ranking<'b>(
+        &self,
+        heading: &str,
+        from: &Path,
+        pages: impl Iterator<Item = &'b (&'b str, usize)>,
+    ) -> String {
+        let items: Vec<String> = pages
+            .map(|(page, links)| {
+                let link = relative_link(from, Path::new(page));
+                format!("1. [{}]({}) ({})", self.page_title(page), link, links)
+            })
+            .collect();
+        if items.is_empty() {
+            return String::new();
+        }
+        format!("\n## {}\n\n{}\n", heading, items.join("\n"))
+    }
+
+    /// An index page in the layout of the kind's template, if there is one,
+    /// or the built-in one: a heading per entry above its page list.
+    fn generate_index(
+        &self,
+        kind: &EntityKind,
+        index: &Index,
+        page: &IndexPage,
+    ) -> Result<String, Error> {
+        if let Some(rendered) = self
+            .templates
+            .render(&kind.name, &self.template_data(kind, index, page))
+        {
+            let rendered = rendered?;
+            if rendered.starts_with(GENERATED_MARKER) {
+                return Ok(rendered);
+            }
+            return Ok(format!("{GENERATED_MARKER}\n{rendered}"));
+        }
+
+        // Letter headings push entries one level down
+        let base = if self.config.letter_nav { 3 } else { 2 };
+        let entries = page.keys.iter().filter_map(|key| index.get(key));
+        Ok(self.index_page(&page.title, entries, |entry| {
+            let entries = self.page_list(&page.file, entry);
+            if self.is_collapsed(entry) {
+                // Headings don't render inside HTML, so the block takes the id
+                return format!(
+                    "\n<details id=\"{}\">\n<summary>{} ({})</summary>\n\n{}\n\n</details>\n\n",
+                    entry.anchor,
+                    escape_html(&format!("{}{}", kind.prefix, entry.display)),
+                    entry.count(),
+                    entries
+                );
+            }
+            // Sorted keys put children right after their parent
+            let level = "#".repeat((base + depth(kind, entry)).min(6));
+            let profile = kind
+                .profile(&entry.display)
+                .map(|profile| profile_list(profile, &self.config.labels, &page.file))
+                .unwrap_or_default();
+            format!(
+                "{} {}\n{}{}{}\n",
+                level,
+                self.heading(kind, entry),
+                profile,
+                self.elsewhere(kind, entry),
+                entries
+            )
+        }))
+    }
+
+    /// What templates get to lay out an index page. Links are relative to
+    /// the page, and inline tokens link to each entry's `anchor`, which a
+    /// template should set on the entry's heading, as in `{#{{anchor}}}`.
+    fn template_data(&self, kind: &EntityKind, index: &Index, page: &IndexPage) -> Value {
+        let from = Path::new(&page.file);
+        let entries: Vec<Value> = page
+            .keys
+            .iter()
+            .filter_map(|key| index.get(key))
+            .map(|entry| {
+                let pages: Vec<Value> = entry
+                    .pages()
+                    .map(|occurrences| {
+                        let path = &occurrences[0].page;
+                        let occurrences: Vec<Value> = occurrences
+                            .iter()
+                            .map(|occurrence| {
+                                json!({
+                                    "link": link_to(from, Path::new(path), &occurrence.anchor),
+                                    "anchor": occurrence.anchor,
+                                    "snippet": occurrence.snippet,
+                                })
+                            })
+                            .collect();
+                        json!({
+                            "title": self.page_title(path),
+                            "path": path,
+                            "link": relative_link(from, Path::new(path)),
+                            "count": occurrences.len(),
+                            "occurrences": occurrences,
+                        })
+                    })
+                    .collect();
+                json!({
+                    "key": entry.display,
+                    "name": format!("{}{}", kind.prefix, entry.display),
+                    "anchor": entry.anchor,
+                    "count": entry.count(),
+                    "depth": depth(kind, entry),
+                    "description": kind.description(&entry.display),
+                    "profile": kind.profile(&entry.display),
+                    "pages": pages,
+                })
+            })
+            .collect();
+        json!({
+            "title": page.title,
+            "kind": kind.name,
+            "prefix": kind.prefix.to_string(),
+            "file": page.file,
+            "entries": entries,
+        })
+    }
+
+    /// The index chapter of a kind with a page per entry: a list of links to
+    /// those pages, indented by nesting depth.
+    fn generate_overview(&self, kind: &EntityKind, index: &Index) -> String {
+        let entries = index.entries().filter(|entry| self.is_listed(entry));
+        self.index_page(&kind.title, entries, |entry| {
+            let indent = "  ".repeat(depth(kind, entry));
+            let link = relative_link(Path::new(&kind.file), Path::new(&entry.file));
+            let count = self.count_suffix(entry.count());
+            format!(
+                "{}- [{}{}]({}){}\n",
+                indent, kind.prefix, entry.display, link, count
+            )
+        })
+    }
+
+    /// `title` followed by `entries` as rendered by `render`. With
+    /// `letter_nav` on, entries are grouped under a heading per initial and a
+    /// bar of links to those headings opens the page.
+    fn index_page<'e>(
+        &self,
+        title: &str,
+        entries: impl Iterator<Item = &'e Entry>,
+        render: impl Fn(&Entry) -> String,
+    ) -> String {
+        let mut nav: Vec<String> = Vec::new();
+        let mut sections = String::new();
+        let mut letter = None;
+
+        for entry in entries {
+            let initial = initial(&entry.display, &self.config.labels);
+            if self.config.letter_nav && letter.as_ref() != Some(&initial) {
+                // Explicit ids stay clear of the ids replayed for the entries
+                let id = format!("letter-{}", slug(&initial));
+                let link = format!("[{}](#{})", initial, percent_encode(&id));
+                if !nav.contains(&link) {
+                    nav.push(link);
+                }
+                sections.push_str(&format!("\n## {initial} {{#{id}}}\n\n"));
+                letter = Some(initial);
+            }
+            sections.push_str(&render(entry));
+        }
+
+        let mut md = format!("{GENERATED_MARKER}\n# {title}\n\n");
+        if !nav.is_empty() {
+            md.push_str(&nav.join(" | "));
+            md.push('\n');
+        }
+        md.push_str(&sections);
+        md
+    }
+
+    /// The page of a single entry: its description or profile, the entries
+    /// nested directly below it, and every chapter referencing it.
+    fn generate_entry_page(&self, kind: &EntityKind, index: &Index, entry: &Entry) -> String {
+        let mut md = format!("{GENERATED_MARKER}\n# {}{}\n\n", kind.prefix, entry.display);
+        if let Some(description) = kind.description(&entry.display) {
+            md.push_str(description.trim());
+            md.push_str("\n\n");
+        }
+        if let Some(profile) = kind.profile(&entry.display) {
+            md.push_str(&profile_list(profile, &self.config.labels, &entry.file));
+        }
+        md.push_str(&self.elsewhere(kind, entry));
+
+        let children: Vec<String> = index
+            .children(&entry.display)
+            .filter(|child| self.is_listed(child))
+            .map(|child| {
+                let link = relative_link(Path::new(&entry.file), Path::new(&child.file));
+                let count = self.count_suffix(child.count());
+                format!("- [{}{}]({}){}", kind.prefix, child.display, link, count)
+            })
+            .collect();
+        if kind.nested && !children.is_empty() {
+            let label = &self.config.labels.children;
+            md.push_str(&format!("## {}\n{}\n\n", label, children.join("\n")));
+        }
+
+        md.push_str(&format!(
+            "## {}\n{}\n",
+            self.config.labels.pages,
+            self.page_list(&entry.file, entry)
+        ));
+        md
+    }
+
+    /// Whether `entry` is folded into a `<details>` block on its index page.
+    fn is_collapsed(&self, entry: &Entry) -> bool {
+        self.config
+            .collapse
+            .is_some_and(|threshold| entry.count() >= threshold)
+    }
+
+    /// Text of the heading of `entry` in its kind's chapter. With counts on,
+    /// the id is spelled out so it doesn't change along with the count, and
+    /// with `collapse` on so folded entries don't shift the ids of the rest.
+    fn heading(&self, kind: &EntityKind, entry: &Entry) -> String {
+        if self.config.collapse.is_some() && !self.config.counts {
+            return format!("{}{} {{#{}}}", kind.prefix, entry.display, entry.anchor);
+        }
+        if !self.config.counts {
+            return format!("{}{}", kind.prefix, entry.display);
+        }
+        format!(
+            "{}{} ({}) {{#{}}}",
+            kind.prefix,
+            entry.display,
+            entry.count(),
+            entry.anchor
+        )
+    }
+
+    /// Links from the chapter at `from` to every page referencing `entry`,
+    /// each listed once, with its number of occurrences when counts are on
+    /// and the snippet of each below it when snippets are. Deep links point
+    /// single occurrences and snippets at the anchor of the token, and with
+    /// `list_occurrences` on, every occurrence gets a line of its own.
+    fn page_list(&self, from: &str, entry: &Entry) -> String {
+        let from = Path::new(from);
+        let grouped = self.config.counts
+            || self.config.snippets
+            || !(self.config.deep_links && self.config.list_occurrences);
+        let pages: Vec<&[Occurrence]> = if grouped {
+            entry.pages().collect()
+        } else {
+            entry.occurrences.chunks(1).collect()
+        };
+        pages
+            .into_iter()
+            .map(|occurrences| {
+                let first = &occurrences[0];
+                let page = Path::new(&first.page);
+                let link = if occurrences.len() == 1 {
+                    link_to(from, page, &first.anchor)
+                } else {
+                    relative_link(from, page)
+                };
+                let count = self.count_suffix(occurrences.len());
+                let title = self.page_title(&first.page);
+                let mut item = format!("- [{}]({}){}", title, link, count);
+                // Several tokens on one line share a snippet
+                let mut snippets: Vec<&Occurrence> = occurrences
+                    .iter()
+                    .filter(|occurrence| !occurrence.snippet.is_empty())
+                    .collect();
+                snippets.dedup_by_key(|occurrence| &occurrence.snippet);
+                for occurrence in snippets {
+                    let snippet = escape_markdown(&occurrence.snippet);
+                    if occurrence.anchor.is_empty() {
+                        item.push_str(&format!("\n  - {snippet}"));
+                    } else {
+                        let link = link_to(from, page, &occurrence.anchor);
+                        item.push_str(&format!("\n  - [{snippet}]({link})"));
+                    }
+                }
+                item
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// ` (n)` after a link or heading when counts are shown, else nothing.
+    fn count_suffix(&self, count: usize) -> String {
+        if self.config.counts {
+            format!(" ({count})")
+        } else {
+            String::new()
+        }
+    }
+
+    /// The name of the chapter at `page`, after its parents' names with
+    /// `breadcrumbs` on.
+    fn page_title(&self, page: &str) -> String {
+        let Some(title) = self.chapters.get(page) else {
+            return page.to_string();
+        };
+        if !self.config.breadcrumbs {
+            return title.name.clone();
+        }
+        let mut names = title.parents.clone();
+        names.push(title.name.clone());
+        names.join(" › ")
+    }
+
+    /// The line linking to `entry` in the other books of the workspace that
+    /// list it, if any do.
+    fn elsewhere(&self, kind: &EntityKind, entry: &Entry) -> String {
+        let links: Vec<String> = self
+            .siblings
+            .iter()
+            .filter_map(|sibling| {
+                let url = sibling.entry(&kind.name, &entry.display)?;
+                Some(format!("[{}]({url})", escape_markdown(&sibling.title)))
+            })
+            .collect();
+        if links.is_empty() {
+            return String::new();
+        }
+        format!(
+            "\n*{}* {}\n\n",
+            self.config.labels.also_in,
+            links.join(", ")
+        )
+    }
+
+    /// The chapters of `pages` the book has, in reading order.
+    fn in_reading_order<'a>(&self, pages: &'a [String]) -> Vec<&'a String> {
+        let mut pages: Vec<&String> = pages
+            .iter()
+            .filter(|page| self.chapters.contains_key(*page))
+            .collect();
+        pages.sort_by_key(|page| self.chapters[*page].position);
+        pages
+    }
+}
+
+/// How often `key` is written in each of its pages, leaving out the
+/// occurrences it only has through the keys nested below it.
+fn written_counts<'a>(
+    kind: &EntityKind,
+    index: &Index,
+    key: &str,
+    entry: &'a Entry,
+) -> Vec<(&'a str, usize)> {
+    let mut counts: Vec<(&str, usize)> = entry
+        .pages()
+        .map(|occurrences| (occurrences[0].page.as_str(), occurrences.len()))
+        .collect();
+    if kind.nested {
+        // Every child's occurrence was recorded for its parent as well
+        for child in index.children(key) {
+            for occurrences in child.pages() {
+                let page = occurrences[0].page.as_str();
+                if let Some((_, count)) = counts.iter_mut().find(|(p, _)| *p == page) {
+                    *count = count.saturating_sub(occurrences.len());
+                }
+            }
+        }
+    }
+    counts.retain(|(_, count)| *count > 0);
+    counts
+}
+
+/// The details of `profile` as a list, for a page at `from`.
+fn profile_list(profile: &Profile, labels: &Labels, from: &str) -> String {
+    let mut fields = Vec::new();
+    if let Some(avatar) = &profile.avatar {
+        let src = if is_relative_link(avatar) {
+            relative_link(Path::new(from), Path::new(avatar))
+        } else {
+            avatar.clone()
+        };
+        let alt = profile.name.as_deref().unwrap_or_default();
+        fields.push(format!(
+            "<img class=\"indexer-avatar\" src=\"{}\" alt=\"{}\" width=\"96\">\n",
+            escape_html(&src),
+            escape_html(alt)
+        ));
+    }
+    if let Some(name) = &profile.name {
+        fields.push(format!("- {}: {name}", labels.name));
+    }
+    if let Some(role) = &profile.role {
+        fields.push(format!("- {}: {role}", labels.role));
+    }
+    if let Some(email) = &profile.email {
+        fields.push(format!("- {}: <mailto:{email}>", labels.email));
+    }
+    if let Some(homepage) = &profile.homepage {
+        fields.push(format!("- {}: <{homepage}>", labels.homepage));
+    }
+    if !profile.links.is_empty() {
+        let links: Vec<String> = profile
+            .links
+            .iter()
+            .map(|link| format!("<{link}>"))
+            .collect();
+        fields.push(format!("- {}: {}", labels.links, links.join(", ")));
+    }
+    if fields.is_empty() {
+        return String::new();
+    }
+    format!("{}\n\n", fields.join("\n"))
+}
+
+/// The group `key` is listed under with `letter_nav` on: its first letter in
+/// uppercase, `0–9` for digits, and the `other` label for anything else.
+fn initial(key: &str, labels: &Labels) -> String {
+    match key.chars().next() {
+        Some(c) if c.is_alphabetic() => c.to_uppercase().collect(),
+        Some(c) if c.is_numeric() => String::from("0–9"),
+        _ => labels.other.clone(),
+    }
+}
+
+/// Which of the [`CLOUD_SIZES`] sizes `count` gets, from 0 for the rarest
+/// to the last for the most frequent, on a logarithmic scale between the
+/// smallest and biggest counts of `range`.
+fn cloud_size(count: usize, (min, max): (usize, usize)) -> usize {
+    if max <= min {
+        return CLOUD_SIZES / 2;
+    }
+    let scale = ((count as f64).ln() - (min as f64).ln()) / ((max as f64).ln() - (min as f64).ln());
+    ((scale * (CLOUD_SIZES - 1) as f64).round() as usize).min(CLOUD_SIZES - 1)
+}
+
+/// `text` safe to place between HTML tags or in a quoted attribute.
+/// `link` to a chapter's source as the HTML renderer serves it, which
+/// Markdown links get from mdBook but scripts don't.
+pub(crate) fn html_link(link: &str) -> String {
+    let (path, anchor) = link
+        .split_once('#')
+        .map_or((link, None), |(p, a)| (p, Some(a)));
+    let path = match path.strip_suffix(".md") {
+        Some(stem) => format!("{stem}.html"),
+        None => path.to_string(),
+    };
+    match anchor {
+        Some(anchor) => format!("{path}#{anchor}"),
+        None => path,
+    }
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// `text` with the characters that could start Markdown markup escaped.
+pub(crate) fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#' | '!'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub(crate) fn depth(kind: &EntityKind, entry: &Entry) -> usize {
+    if kind.nested {
+        entry.display.matches('/').count()
+    } else {
+        0
+    }
+}
+
+/// `key` in lowercase with everything but letters, digits and `_` turned
+/// into single dashes, e.g. `rust-async` for `rust/async`.
+pub(crate) fn slug(key: &str) -> String {
+    let mut slug = String::new();
+    for c in key.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() || c == '_' {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    match slug.trim_end_matches('-') {
+        "" => String::from("entry"),
+        slug => slug.to_string(),
+    }
+}
+
+/// A file name for `key` that no other entry of the index uses yet.
+fn unique_slug(key: &str, used: &mut HashSet<String>) -> String {
+    let slug = slug(key);
+
+    let mut candidate = slug.clone();
+    let mut n = 1;
+    while !used.insert(candidate.clone()) {
+        n += 1;
+        candidate = format!("{slug}-{n}");
+    }
+    candidate
+}
@@ -0,0 +1,149 @@
+use crate::extract::parser;
+use crate::frontmatter::FrontMatter;
+use crate::generate::footer_start;
+use crate::unlinked::Term;
+use anyhow::Context;
+use mdbook::book::{Book, BookItem};
+use mdbook::errors::Error;
+use mdbook::utils::unique_id_from_content;
+use pulldown_cmark::{Event, HeadingLevel, Tag, TagEnd};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// A term of the glossary and what it means, in Markdown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Definition {
+    pub term: String,
+    pub definition: String,
+}
+
+/// The terms defined by `source`: a chapter of `book`, by path, whose
+/// second-level headings are the terms, each defined by what follows it, or
+/// a TOML or YAML file under `root` mapping terms to their definitions.
+pub(crate) fn load(source: &str, root: &Path, book: &Book) -> Result<Vec<Definition>, Error> {
+    let extension = Path::new(source)
+        .extension()
+        .and_then(|extension| extension.to_str());
+    let file = || {
+        let path = root.join(source);
+        fs::read_to_string(&path)
+            .with_context(|| format!("couldn't read the glossary {}", path.display()))
+    };
+    let terms: BTreeMap<String, String> = match extension {
+        Some("md") => {
+            let chapter = book.iter().find_map(|item| match item {
+                BookItem::Chapter(chapter)
+                    if chapter.path.as_deref() == Some(Path::new(source)) =>
+                {
+                    Some(chapter)
+                }
+                _ => None,
+            });
+            let Some(chapter) = chapter else {
+                anyhow::bail!("the glossary `{source}` is not a chapter of the book");
+            };
+            return Ok(parse_chapter(&chapter.content));
+        }
+        Some("toml") => {
+            toml::from_str(&file()?).with_context(|| format!("invalid glossary `{source}`"))?
+        }
+        Some("yaml" | "yml") => serde_yaml::from_str(&file()?)
+            .with_context(|| format!("invalid glossary `{source}`"))?,
+        _ => anyhow::bail!("the glossary `{source}` should be a chapter, or a TOML or YAML file"),
+    };
+    Ok(terms
+        .into_iter()
+        .map(|(term, definition)| Definition {
+            term: term.trim().to_string(),
+            definition: definition.trim().to_string(),
+        })
+        .collect())
+}
+
+/// The terms of a glossary chapter, in order: the text of each `##` heading,
+/// defined by everything up to the next heading of that level or above.
+fn parse_chapter(content: &str) -> Vec<Definition> {
+    let content = &content[..footer_start(content).unwrap_or(content.len())];
+    let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+    let content = &content[body..];
+
+    let mut definitions = Vec::new();
+    // Term being read, and where its heading ends once read
+    let mut term: Option<(String, Option<usize>)> = None;
+    let mut in_heading = false;
+    let finish = |term: Option<(String, Option<usize>)>, end: usize, definitions: &mut Vec<_>| {
+        if let Some((term, Some(start))) = term {
+            definitions.push(Definition {
+                term: term.trim().to_string(),
+                definition: content[start..end].trim().to_string(),
+            });
+        }
+    };
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Heading { level, .. }) if level <= HeadingLevel::H2 => {
+                finish(term.take(), range.start, &mut definitions);
+                if level == HeadingLevel::H2 {
+                    term = Some((String::new(), None));
+                    in_heading = true;
+                }
+            }
+            Event::Text(text) | Event::Code(text) if in_heading => {
+                if let Some((term, _)) = &mut term {
+                    term.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) if in_heading => {
+                if let Some((_, start)) = &mut term {
+                    *start = Some(range.end);
+                }
+                in_heading = false;
+            }
+            _ => {}
+        }
+    }
+    finish(term, content.len(), &mut definitions);
+    definitions.retain(|definition| !definition.term.is_empty());
+    definitions
+}
+
+/// The ids the HTML renderer gives the headings of the glossary chapter
+/// titled `title`, one for each definition.
+pub(crate) fn anchors(title: &str, definitions: &[Definition]) -> Vec<String> {
+    let mut counts = HashMap::new();
+    unique_id_from_content(title, &mut counts);
+    definitions
+        .iter()
+        .map(|definition| unique_id_from_content(&definition.term, &mut counts))
+        .collect()
+}
+
+/// The terms to link to the glossary chapter at `file`, the longest first,
+/// each with the definition it stands for.
+pub(crate) fn terms(
+    definitions: &[Definition],
+    anchors: &[String],
+    file: &str,
+) -> Vec<(usize, Term)> {
+    let mut terms: Vec<(usize, Term)> = definitions
+        .iter()
+        .zip(anchors)
+        .enumerate()
+        .map(|(i, (definition, anchor))| {
+            let term = Term {
+                text: definition.term.clone(),
+                page: file.to_string(),
+                anchor: anchor.clone(),
+            };
+            (i, term)
+        })
+        .collect();
+    terms.sort_by(|(_, a), (_, b)| {
+        b.text
+            .len()
+            .cmp(&a.text.len())
+            .then_with(|| a.text.cmp(&b.text))
+    });
+    terms
+}
@@ -0,0 +1,218 @@
+// Force-directed view of the graph data embedded next to the canvas.
+// Drag nodes or the background, scroll to zoom, click a node to open it.
+(function () {
+    "use strict";
+    const canvas = document.getElementById("indexer-graph");
+    const data = JSON.parse(document.getElementById("indexer-graph-data").textContent);
+    const context = canvas.getContext("2d");
+
+    const nodes = data.nodes.map((node, i) => {
+        // Start on a spiral so no two nodes share a position
+        const angle = i * 2.4;
+        const radius = 12 * Math.sqrt(i + 1);
+        return {
+            data: node,
+            x: radius * Math.cos(angle),
+            y: radius * Math.sin(angle),
+            vx: 0,
+            vy: 0,
+            radius: 4 + 2 * Math.sqrt(node.weight),
+            neighbours: new Set(),
+        };
+    });
+    const edges = data.edges.map((edge) => {
+        const from = nodes[edge.source];
+        const to = nodes[edge.target];
+        from.neighbours.add(to);
+        to.neighbours.add(from);
+        return { data: edge, from, to };
+    });
+
+    let width = 0;
+    let height = 0;
+    let zoom = 1;
+    let panX = 0;
+    let panY = 0;
+    let alpha = 1;
+    let hovered = null;
+    let dragged = null;
+    let panning = null;
+    let moved = false;
+    let dirty = true;
+
+    function resize() {
+        const ratio = window.devicePixelRatio || 1;
+        width = canvas.clientWidth;
+        height = canvas.clientHeight;
+        canvas.width = width * ratio;
+        canvas.height = height * ratio;
+        context.setTransform(ratio, 0, 0, ratio, 0, 0);
+        dirty = true;
+    }
+
+    function tick() {
+        for (let i = 0; i < nodes.length; i++) {
+            for (let j = i + 1; j < nodes.length; j++) {
+                const a = nodes[i];
+                const b = nodes[j];
+                const dx = b.x - a.x;
+                const dy = b.y - a.y;
+                const force = (300 * alpha) / (dx * dx + dy * dy + 1);
+                a.vx -= dx * force;
+                a.vy -= dy * force;
+                b.vx += dx * force;
+                b.vy += dy * force;
+            }
+        }
+        for (const edge of edges) {
+            const dx = edge.to.x - edge.from.x;
+            const dy = edge.to.y - edge.from.y;
+            const distance = Math.sqrt(dx * dx + dy * dy) || 1;
+            const force = ((distance - 60) * 0.03 * alpha) / distance;
+            edge.from.vx += dx * force;
+            edge.from.vy += dy * force;
+            edge.to.vx -= dx * force;
+            edge.to.vy -= dy * force;
+        }
+        for (const node of nodes) {
+            node.vx -= node.x * 0.01 * alpha;
+            node.vy -= node.y * 0.01 * alpha;
+            if (node !== dragged) {
+                node.x += node.vx;
+                node.y += node.vy;
+            }
+            node.vx *= 0.6;
+            node.vy *= 0.6;
+        }
+        alpha *= 0.99;
+    }
+
+    function color(name, fallback) {
+        const value = getComputedStyle(document.documentElement).getPropertyValue(name).trim();
+        return value || fallback;
+    }
+
+    function draw() {
+        const fg = color("--fg", "#333");
+        const links = color("--links", "#4183c4");
+        const faded = color("--icons", "#999");
+        context.clearRect(0, 0, width, height);
+        context.save();
+        context.translate(width / 2 + panX, height / 2 + panY);
+        context.scale(zoom, zoom);
+
+        for (const edge of edges) {
+            const lit = hovered && (edge.from === hovered || edge.to === hovered);
+            context.strokeStyle = lit ? links : faded;
+            context.globalAlpha = hovered && !lit ? 0.2 : 0.7;
+            context.lineWidth = (edge.data.type === "uses" ? Math.sqrt(edge.data.weight) : 1) / zoom;
+            context.setLineDash(edge.data.type === "links" ? [4 / zoom, 4 / zoom] : []);
+            context.beginPath();
+            context.moveTo(edge.from.x, edge.from.y);
+            context.lineTo(edge.to.x, edge.to.y);
+            context.stroke();
+        }
+        context.setLineDash([]);
+
+        context.font = `${12 / zoom}px sans-serif`;
+        context.textAlign = "center";
+        for (const node of nodes) {
+            const lit = !hovered || node === hovered || hovered.neighbours.has(node);
+            context.globalAlpha = lit ? 1 : 0.2;
+            context.fillStyle = node.data.type === "chapter" ? links : fg;
+            context.beginPath();
+            context.arc(node.x, node.y, node.radius, 0, 2 * Math.PI);
+            context.fill();
+            if (lit && (nodes.length <= 100 || hovered)) {
+                context.fillStyle = fg;
+                context.fillText(node.data.label, node.x, node.y - node.radius - 4 / zoom);
+            }
+        }
+        context.restore();
+    }
+
+    function frame() {
+        if (alpha > 0.005) {
+            tick();
+            dirty = true;
+        }
+        if (dirty) {
+            draw();
+            dirty = false;
+        }
+        requestAnimationFrame(frame);
+    }
+
+    function position(event) {
+        const box = canvas.getBoundingClientRect();
+        return {
+            x: (event.clientX - box.left - width / 2 - panX) / zoom,
+            y: (event.clientY - box.top - height / 2 - panY) / zoom,
+        };
+    }
+
+    function nodeAt(point) {
+        for (let i = nodes.length - 1; i >= 0; i--) {
+            const node = nodes[i];
+            const dx = node.x - point.x;
+            const dy = node.y - point.y;
+            if (dx * dx + dy * dy <= (node.radius + 3 / zoom) ** 2) {
+                return node;
+            }
+        }
+        return null;
+    }
+
+    canvas.addEventListener("mousedown", (event) => {
+        const point = position(event);
+        moved = false;
+        dragged = nodeAt(point);
+        if (!dragged) {
+            panning = { x: event.clientX - panX, y: event.clientY - panY };
+        }
+    });
+    canvas.addEventListener("mousemove", (event) => {
+        const point = position(event);
+        if (dragged) {
+            moved = true;
+            dragged.x = point.x;
+            dragged.y = point.y;
+            alpha = Math.max(alpha, 0.3);
+        } else if (panning) {
+            moved = true;
+            panX = event.clientX - panning.x;
+            panY = event.clientY - panning.y;
+        } else {
+            const node = nodeAt(point);
+            if (node !== hovered) {
+                hovered = node;
+                canvas.style.cursor = node ? "pointer" : "grab";
+            }
+        }
+        dirty = true;
+    });
+    window.addEventListener("mouseup", () => {
+        if (dragged && !moved && dragged.data.url) {
+            window.location.href = dragged.data.url;
+        }
+        dragged = null;
+        panning = null;
+    });
+    canvas.addEventListener("wheel", (event) => {
+        event.preventDefault();
+        const box = canvas.getBoundingClientRect();
+        const x = event.clientX - box.left - width / 2;
+        const y = event.clientY - box.top - height / 2;
+        const factor = Math.exp(-event.deltaY * 0.001);
+        // Keep the point under the cursor in place
+        panX = x - (x - panX) * factor;
+        panY = y - (y - panY) * factor;
+        zoom *= factor;
+        dirty = true;
+    }, { passive: false });
+    window.addEventListener("resize", resize);
+
+    canvas.style.cursor = "grab";
+    resize();
+    requestAnimationFrame(frame);
+})();
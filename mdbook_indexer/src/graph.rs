@@ -0,0 +1,424 @@
+use crate::config::EntityKind;
+use crate::generate::{depth, escape_html, Generator};
+use crate::index::{Entry, Index};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Chapters and the keys they use, as nodes joined by edges.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    /// Name shown for the node: the chapter's title, or the prefixed key.
+    pub label: String,
+    pub kind: NodeKind,
+    /// Where the node is shown: a chapter, or an entry's place in the
+    /// generated chapters, relative to the source directory.
+    pub page: String,
+    pub anchor: String,
+    /// Occurrences of an entry, or indexed tokens of a chapter.
+    pub weight: usize,
+    /// Chapters closest to a chapter in wording, by node, with their score.
+    pub similar: Vec<(usize, f64)>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum NodeKind {
+    /// A chapter of the book, by path.
+    Chapter(String),
+    /// An entry of the index of the entity type with this name.
+    Entry { kind: String, key: String },
+}
+
+/// A relationship between the nodes at `from` and `to`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum EdgeKind {
+    /// A chapter references an entry, `weight` times.
+    Uses { weight: usize },
+    /// A chapter links to another one.
+    Links,
+    /// An entry is nested below another one.
+    Nests,
+}
+
+impl Node {
+    /// `chapter`, or the name of the entry's entity type.
+    fn type_name(&self) -> &str {
+        match &self.kind {
+            NodeKind::Chapter(_) => "chapter",
+            NodeKind::Entry { kind, .. } => kind,
+        }
+    }
+}
+
+impl Edge {
+    fn type_name(&self) -> &'static str {
+        match self.kind {
+            EdgeKind::Uses { .. } => "uses",
+            EdgeKind::Links => "links",
+            EdgeKind::Nests => "nests",
+        }
+    }
+
+    fn weight(&self) -> usize {
+        match self.kind {
+            EdgeKind::Uses { weight } => weight,
+            EdgeKind::Links | EdgeKind::Nests => 1,
+        }
+    }
+}
+
+impl Graph {
+    /// The graph of the listed entries of `indexes` and the chapters using
+    /// them, limited to the kinds and depth of the `graph_*` settings.
+    /// `links` holds the chapters linking to each chapter, by path, and
+    /// `similar` those closest to it in wording, with their score.
+    pub fn build(
+        generator: &Generator,
+        kinds: &[EntityKind],
+        indexes: &[Index],
+        links: &HashMap<String, Vec<String>>,
+        similar: &HashMap<String, Vec<(String, f64)>>,
+    ) -> Self {
+        let config = generator.config;
+        let mut graph = Graph::default();
+        let mut pages: Vec<(&String, usize)> = generator
+            .chapters
+            .iter()
+            .filter(|(page, _)| !page.is_empty())
+            .map(|(page, chapter)| (page, chapter.position))
+            .collect();
+        pages.sort_by_key(|&(_, position)| position);
+        let mut chapter_nodes = HashMap::new();
+        for (page, _) in pages {
+            chapter_nodes.insert(page.as_str(), graph.nodes.len());
+            graph.nodes.push(Node {
+                label: generator.chapters[page].name.clone(),
+                kind: NodeKind::Chapter(page.clone()),
+                page: page.clone(),
+                anchor: String::new(),
+                weight: 0,
+                similar: Vec::new(),
+            });
+        }
+        for (page, closest) in similar {
+            if let Some(&node) = chapter_nodes.get(page.as_str()) {
+                graph.nodes[node].similar = closest
+                    .iter()
+                    .filter_map(|(other, score)| {
+                        Some((*chapter_nodes.get(other.as_str())?, *score))
+                    })
+                    .collect();
+            }
+        }
+
+        for (kind, index) in kinds.iter().zip(indexes) {
+            let shown = config
+                .graph_kinds
+                .as_ref()
+                .is_none_or(|names| names.contains(&kind.name));
+            if !shown {
+                continue;
+            }
+            let drawn: Vec<(&str, &Entry)> = index
+                .keyed()
+                .filter(|(_, entry)| {
+                    generator.is_listed(entry)
+                        && config
+                            .graph_depth
+                            .is_none_or(|max| depth(kind, entry) <= max)
+                })
+                .collect();
+            let mut entry_nodes = HashMap::new();
+            for &(key, entry) in &drawn {
+                entry_nodes.insert(key, graph.nodes.len());
+                graph.nodes.push(Node {
+                    label: format!("{}{}", kind.prefix, entry.display),
+                    kind: NodeKind::Entry {
+                        kind: kind.name.clone(),
+                        key: key.to_string(),
+                    },
+                    page: entry.file.clone(),
+                    anchor: entry.anchor.clone(),
+                    weight: entry.count(),
+                    similar: Vec::new(),
+                });
+            }
+
+            let parent = |key: &str| {
+                key.rsplit_once('/')
+                    .filter(|_| kind.nested)
+                    .map(|(parent, _)| parent.to_string())
+            };
+            for &(key, entry) in &drawn {
+                let node = entry_nodes[key];
+                if let Some(&parent) =
+                    parent(key).and_then(|parent| entry_nodes.get(parent.as_str()))
+                {
+                    graph.edges.push(Edge {
+                        from: parent,
+                        to: node,
+                        kind: EdgeKind::Nests,
+                    });
+                }
+
+                // A drawn child's occurrences were recorded for its parent as
+                // well, so chapters only use the deepest key drawn
+                let mut counts: Vec<(&str, usize)> = entry
+                    .pages()
+                    .map(|occurrences| (occurrences[0].page.as_str(), occurrences.len()))
+                    .collect();
+                for &(_, child) in drawn
+                    .iter()
+                    .filter(|(child, _)| parent(child).as_deref() == Some(key))
+                {
+                    for occurrences in child.pages() {
+                        let page = occurrences[0].page.as_str();
+                        if let Some((_, count)) = counts.iter_mut().find(|(p, _)| *p == page) {
+                            *count = count.saturating_sub(occurrences.len());
+                        }
+                    }
+                }
+                for (page, weight) in counts.into_iter().filter(|&(_, weight)| weight > 0) {
+                    if let Some(&chapter) = chapter_nodes.get(page) {
+                        graph.nodes[chapter].weight += weight;
+                        graph.edges.push(Edge {
+                            from: chapter,
+                            to: node,
+                            kind: EdgeKind::Uses { weight },
+                        });
+                    }
+                }
+            }
+        }
+
+        if config.graph_links {
+            let mut linked: Vec<Edge> = links
+                .iter()
+                .filter_map(|(target, sources)| {
+                    Some((chapter_nodes.get(target.as_str())?, sources))
+                })
+                .flat_map(|(&to, sources)| {
+                    sources
+                        .iter()
+                        .filter_map(|source| chapter_nodes.get(source.as_str()))
+                        .map(move |&from| Edge {
+                            from,
+                            to,
+                            kind: EdgeKind::Links,
+                        })
+                })
+                .collect();
+            linked.sort_by_key(|edge| (edge.from, edge.to));
+            graph.edges.extend(linked);
+        }
+        graph
+    }
+
+    /// The graph as JSON: `nodes` with their `id`, `label`, `type` (`chapter`
+    /// or the entity type's name), `path`, `anchor`, `weight` and the
+    /// `similar` chapters' `id` and `score`, and `edges` from `source` to
+    /// `target` with their `type` and `weight`.
+    pub fn json(&self) -> Value {
+        let nodes: Vec<Value> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(id, node)| {
+                let key = match &node.kind {
+                    NodeKind::Chapter(_) => None,
+                    NodeKind::Entry { key, .. } => Some(key),
+                };
+                json!({
+                    "id": id,
+                    "label": node.label,
+                    "type": node.type_name(),
+                    "key": key,
+                    "path": node.page,
+                    "anchor": node.anchor,
+                    "weight": node.weight,
+                    "similar": node
+                        .similar
+                        .iter()
+                        .map(|&(id, score)| json!({ "id": id, "score": (score * 1000.0).round() / 1000.0 }))
+                        .collect::<Vec<_>>(),
+                })
+            })
+            .collect();
+        let edges: Vec<Value> = self
+            .edges
+            .iter()
+            .map(|edge| {
+                json!({
+                    "source": edge.from,
+                    "target": edge.to,
+                    "type": edge.type_name(),
+                    "weight": edge.weight(),
+                })
+            })
+            .collect();
+        json!({ "nodes": nodes, "edges": edges })
+    }
+
+    /// The graph in Graphviz DOT, named `name`: chapters as boxes linking to
+    /// their source, keys as ellipses, uses as arrows as thick as they are
+    /// frequent, links between chapters as dashed arrows and nesting as
+    /// plain lines.
+    pub fn dot(&self, name: &str) -> String {
+        let quote = |text: &str| format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = format!("digraph {} {{\n", quote(name));
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = quote(&node.label);
+            match node.kind {
+                NodeKind::Chapter(_) => dot.push_str(&format!(
+                    "  n{i} [label={label}, shape=box, URL={}];\n",
+                    quote(&node.page)
+                )),
+                NodeKind::Entry { .. } => {
+                    dot.push_str(&format!("  n{i} [label={label}, shape=ellipse];\n"))
+                }
+            }
+        }
+        for edge in &self.edges {
+            let attributes = match edge.kind {
+                EdgeKind::Uses { weight } => {
+                    format!("weight={weight}, penwidth={:.1}", (weight as f32).sqrt())
+                }
+                EdgeKind::Links => String::from("style=dashed"),
+                EdgeKind::Nests => String::from("arrowhead=none"),
+            };
+            dot.push_str(&format!(
+                "  n{} -> n{} [{attributes}];\n",
+                edge.from, edge.to
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// The graph in GraphML, with the `label`, `type`, `path` and `weight` of
+    /// nodes and the `type` and `weight` of edges as data.
+    pub fn graphml(&self, name: &str) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n\
+             \x20 <key id=\"label\" for=\"node\" attr.name=\"label\" attr.type=\"string\"/>\n\
+             \x20 <key id=\"type\" for=\"node\" attr.name=\"type\" attr.type=\"string\"/>\n\
+             \x20 <key id=\"path\" for=\"node\" attr.name=\"path\" attr.type=\"string\"/>\n\
+             \x20 <key id=\"weight\" for=\"node\" attr.name=\"weight\" attr.type=\"int\"/>\n\
+             \x20 <key id=\"relation\" for=\"edge\" attr.name=\"type\" attr.type=\"string\"/>\n\
+             \x20 <key id=\"strength\" for=\"edge\" attr.name=\"weight\" attr.type=\"int\"/>\n",
+        );
+        xml.push_str(&format!(
+            "  <graph id=\"{}\" edgedefault=\"directed\">\n",
+            escape_html(name)
+        ));
+        for (i, node) in self.nodes.iter().enumerate() {
+            xml.push_str(&format!(
+                "    <node id=\"n{i}\">\
+                 <data key=\"label\">{}</data>\
+                 <data key=\"type\">{}</data>\
+                 <data key=\"path\">{}</data>\
+                 <data key=\"weight\">{}</data></node>\n",
+                escape_html(&node.label),
+                escape_html(node.type_name()),
+                escape_html(&node.page),
+                node.weight
+            ));
+        }
+        for edge in &self.edges {
+            xml.push_str(&format!(
+                "    <edge source=\"n{}\" target=\"n{}\">\
+                 <data key=\"relation\">{}</data>\
+                 <data key=\"strength\">{}</data></edge>\n",
+                edge.from,
+                edge.to,
+                edge.type_name(),
+                edge.weight()
+            ));
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+
+    /// The graph in GEXF, as Gephi reads it: labelled nodes with their `type`,
+    /// `path` and `weight` as attributes, and weighted edges with a `type`.
+    pub fn gexf(&self, name: &str) -> String {
+        let mut xml = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <gexf xmlns=\"http://gexf.net/1.3\" version=\"1.3\">\n\
+             \x20 <meta><description>{}</description></meta>\n\
+             \x20 <graph defaultedgetype=\"directed\">\n\
+             \x20   <attributes class=\"node\">\n\
+             \x20     <attribute id=\"type\" title=\"type\" type=\"string\"/>\n\
+             \x20     <attribute id=\"path\" title=\"path\" type=\"string\"/>\n\
+             \x20     <attribute id=\"weight\" title=\"weight\" type=\"integer\"/>\n\
+             \x20   </attributes>\n\
+             \x20   <attributes class=\"edge\">\n\
+             \x20     <attribute id=\"type\" title=\"type\" type=\"string\"/>\n\
+             \x20   </attributes>\n\
+             \x20   <nodes>\n",
+            escape_html(name)
+        );
+        for (i, node) in self.nodes.iter().enumerate() {
+            xml.push_str(&format!(
+                "      <node id=\"n{i}\" label=\"{}\"><attvalues>\
+                 <attvalue for=\"type\" value=\"{}\"/>\
+                 <attvalue for=\"path\" value=\"{}\"/>\
+                 <attvalue for=\"weight\" value=\"{}\"/>\
+                 </attvalues></node>\n",
+                escape_html(&node.label),
+                escape_html(node.type_name()),
+                escape_html(&node.page),
+                node.weight
+            ));
+        }
+        xml.push_str("    </nodes>\n    <edges>\n");
+        for (i, edge) in self.edges.iter().enumerate() {
+            xml.push_str(&format!(
+                "      <edge id=\"e{i}\" source=\"n{}\" target=\"n{}\" weight=\"{}\">\
+                 <attvalues><attvalue for=\"type\" value=\"{}\"/></attvalues></edge>\n",
+                edge.from,
+                edge.to,
+                edge.weight(),
+                edge.type_name()
+            ));
+        }
+        xml.push_str("    </edges>\n  </graph>\n</gexf>\n");
+        xml
+    }
+
+    /// The graph as a Mermaid `graph TD` diagram: chapters as boxes, keys as
+    /// rounded nodes, uses as arrows, links between chapters as dotted
+    /// arrows and nesting as plain lines.
+    pub fn mermaid(&self) -> String {
+        let mut diagram = String::from("graph TD\n");
+        for (i, node) in self.nodes.iter().enumerate() {
+            let label = node.label.replace('"', "#quot;");
+            match node.kind {
+                NodeKind::Chapter(_) => diagram.push_str(&format!("  n{i}[\"{label}\"]\n")),
+                NodeKind::Entry { .. } => diagram.push_str(&format!("  n{i}([\"{label}\"])\n")),
+            }
+        }
+        for edge in &self.edges {
+            let arrow = match edge.kind {
+                EdgeKind::Uses { .. } => "-->",
+                EdgeKind::Links => "-.->",
+                EdgeKind::Nests => "---",
+            };
+            diagram.push_str(&format!("  n{} {} n{}\n", edge.from, arrow, edge.to));
+        }
+        diagram
+    }
+}
@@ -0,0 +1,124 @@
+use crate::extract::parser;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// `doi:10.1000/xyz123`, `arXiv:2101.12345v2` or `arXiv:hep-th/9901001`.
+static IDENTIFIER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(?:doi:(10\.\d{4,9}/[-._;()/:a-z0-9]+)|arxiv:(\d{4}\.\d{4,5}(?:v\d+)?|[a-z-]+(?:\.[a-z]{2})?/\d{7}(?:v\d+)?))",
+    )
+    .expect("the identifier pattern is valid")
+});
+
+/// A DOI or arXiv identifier written in a chapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Identifier {
+    pub range: Range<usize>,
+    /// As shown, like `doi:10.1000/xyz123` or `arXiv:2101.12345`.
+    pub text: String,
+    /// Where its resolver serves it.
+    pub url: String,
+}
+
+/// The identifiers of `content` outside code, HTML and links, leaving out
+/// the punctuation ending a sentence after them.
+pub(crate) fn find(content: &str) -> Vec<Identifier> {
+    let skipped: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Link { .. } | Tag::Image { .. })
+            | Event::Code(_)
+            | Event::Html(_)
+            | Event::InlineHtml(_) => Some(range),
+            _ => None,
+        })
+        .collect();
+    parse(content)
+        .into_iter()
+        .filter(|identifier| !skipped.iter().any(|r| r.contains(&identifier.range.start)))
+        .collect()
+}
+
+/// The identifiers of the links of `content` an earlier run made, those
+/// showing one identifier and leading to its resolver, with their ranges.
+pub(crate) fn linked(content: &str) -> Vec<Identifier> {
+    let mut found = Vec::new();
+    let mut open: Option<(String, Range<usize>, String)> = None;
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                open = Some((dest_url.to_string(), range, String::new()));
+            }
+            Event::Text(text) => {
+                if let Some((_, _, shown)) = open.as_mut() {
+                    shown.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Link) => {
+                let Some((dest, range, shown)) = open.take() else {
+                    continue;
+                };
+                if let [identifier] = &parse(&shown)[..] {
+                    if identifier.range == (0..shown.len()) && identifier.url == dest {
+                        found.push(Identifier {
+                            range,
+                            ..identifier.clone()
+                        });
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    found
+}
+
+/// Every identifier `text` holds.
+fn parse(text: &str) -> Vec<Identifier> {
+    let mut found = Vec::new();
+    for captures in IDENTIFIER.captures_iter(text) {
+        let whole = captures.get(0).expect("a match has a whole");
+        let (id, doi) = match (captures.get(1), captures.get(2)) {
+            (Some(doi), _) => (doi.as_str(), true),
+            (None, Some(arxiv)) => (arxiv.as_str(), false),
+            (None, None) => continue,
+        };
+        let mut id = id.trim_end_matches(['.', ',', ';', ':']);
+        // A closing parenthesis belongs to the DOI only if it opens one
+        while id.ends_with(')') && id.matches('(').count() < id.matches(')').count() {
+            id = id[..id.len() - 1].trim_end_matches(['.', ',', ';', ':']);
+        }
+        let prefix_len = whole.as_str().find(':').map_or(0, |colon| colon + 1);
+        let end = whole.start() + prefix_len + id.len();
+        let (text, url) = if doi {
+            (format!("doi:{id}"), format!("https://doi.org/{id}"))
+        } else {
+            (format!("arXiv:{id}"), format!("https://arxiv.org/abs/{id}"))
+        };
+        found.push(Identifier {
+            range: whole.start()..end,
+            text,
+            url,
+        });
+    }
+    found
+}
+
+/// `content` with each of `identifiers` linked to its resolver.
+pub(crate) fn link(content: &str, identifiers: &[Identifier]) -> String {
+    let mut linked = String::with_capacity(content.len());
+    let mut last = 0;
+    for identifier in identifiers {
+        linked.push_str(&content[last..identifier.range.start]);
+        linked.push_str(&format!(
+            "[{}]({})",
+            &content[identifier.range.clone()],
+            identifier.url
+        ));
+        last = identifier.range.end;
+    }
+    linked.push_str(&content[last..]);
+    linked
+}
@@ -0,0 +1,50 @@
+use crate::extract::{collapse_whitespace, parser};
+use crate::paths::{is_relative_link, resolve_link};
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::path::Path;
+
+/// An image shown in a chapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Image {
+    /// Where the image is: a URL, or a path relative to the source
+    /// directory.
+    pub src: String,
+    pub alt: String,
+    /// Path of the chapter.
+    pub page: String,
+}
+
+/// The images of `content`, the body of the chapter at `page`, in order.
+pub(crate) fn images(content: &str, page: &str) -> Vec<Image> {
+    let mut images = Vec::new();
+    let mut current: Option<Image> = None;
+    for event in parser(content) {
+        match event {
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                let src = if is_relative_link(&dest_url) {
+                    resolve_link(Path::new(page), &dest_url)
+                } else {
+                    Some(dest_url.into_string())
+                };
+                current = src.map(|src| Image {
+                    src,
+                    alt: String::new(),
+                    page: page.to_string(),
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(image) = &mut current {
+                    image.alt.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some(mut image) = current.take() {
+                    image.alt = collapse_whitespace(&image.alt);
+                    images.push(image);
+                }
+            }
+            _ => {}
+        }
+    }
+    images
+}
@@ -0,0 +1,177 @@
+use crate::config::CaseMode;
+use std::collections::BTreeMap;
+
+/// One place a key is referenced.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Occurrence {
+    /// Path of the referencing chapter.
+    pub page: String,
+    /// Byte offset of the token in the chapter's body, 0 for front matter.
+    pub offset: usize,
+    /// Text around the token, empty unless snippets are collected.
+    pub snippet: String,
+    /// Id placed right before the token, empty unless deep links are on.
+    pub anchor: String,
+}
+
+/// Everything collected for one key of an index.
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    /// Spelling shown in the generated chapter.
+    pub display: String,
+    pub occurrences: Vec<Occurrence>,
+    /// Generated chapter that links to the entry point at.
+    pub file: String,
+    /// Id of the entry's heading in `file`, empty to link to the top.
+    pub anchor: String,
+}
+
+impl Entry {
+    /// Number of occurrences in the whole book.
+    pub fn count(&self) -> usize {
+        self.occurrences.len()
+    }
+
+    /// The occurrences grouped by page, in page order once sorted.
+    pub fn pages(&self) -> impl Iterator<Item = &[Occurrence]> {
+        self.occurrences.chunk_by(|a, b| a.page == b.page)
+    }
+}
+
+/// The entries of one entity kind, keyed by normalized key. Keys and paths
+/// are kept sorted so generated chapters are identical across builds.
+#[derive(Debug, Clone)]
+pub(crate) struct Index {
+    case: CaseMode,
+    entries: BTreeMap<String, Entry>,
+    /// Normalized keys in listing order, alphabetical unless rearranged.
+    order: Vec<String>,
+}
+
+impl Index {
+    pub fn new(case: CaseMode) -> Self {
+        Index {
+            case,
+            entries: BTreeMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    fn normalize(&self, key: &str) -> String {
+        match self.case {
+            CaseMode::Sensitive => key.to_string(),
+            CaseMode::Lowercase | CaseMode::FirstSeen => key.to_lowercase(),
+        }
+    }
+
+    /// Records an occurrence of `key`.
+    pub fn record(&mut self, key: &str, occurrence: Occurrence) {
+        let display = match self.case {
+            CaseMode::Lowercase => key.to_lowercase(),
+            CaseMode::Sensitive | CaseMode::FirstSeen => key.to_string(),
+        };
+        self.entries
+            .entry(self.normalize(key))
+            .or_insert_with(|| Entry {
+                display,
+                occurrences: Vec::new(),
+                file: String::new(),
+                anchor: String::new(),
+            })
+            .occurrences
+            .push(occurrence);
+    }
+
+    /// The entry `key` was merged into.
+    pub fn get(&self, key: &str) -> Option<&Entry> {
+        self.entries.get(&self.normalize(key))
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Entry> {
+        let key = self.normalize(key);
+        self.entries.get_mut(&key)
+    }
+
+    /// The entries nested directly below `key`, in listing order.
+    pub fn children(&self, key: &str) -> impl Iterator<Item = &Entry> {
+        let prefix = format!("{}/", self.normalize(key));
+        self.keyed()
+            .filter(move |(child, _)| {
+                child
+                    .strip_prefix(prefix.as_str())
+                    .is_some_and(|rest| !rest.contains('/'))
+            })
+            .map(|(_, entry)| entry)
+    }
+
+    /// Drops the occurrences on pages `keep` rejects, and the entries left
+    /// without any.
+    pub fn retain_pages(&mut self, keep: impl Fn(&str) -> bool) {
+        for entry in self.entries.values_mut() {
+            entry
+                .occurrences
+                .retain(|occurrence| keep(&occurrence.page));
+        }
+        self.entries
+            .retain(|_, entry| !entry.occurrences.is_empty());
+        self.order.retain(|key| self.entries.contains_key(key));
+    }
+
+    /// Puts keys and occurrences in their default order, alphabetical and by
+    /// page, once collection is done. Nested keys compare segment by segment,
+    /// so `rust/async` follows `rust` before `rust-embedded` does.
+    pub fn sort(&mut self) {
+        for entry in self.entries.values_mut() {
+            entry.occurrences.sort();
+        }
+        self.order = self.entries.keys().cloned().collect();
+        self.order.sort_by(|a, b| a.split('/').cmp(b.split('/')));
+    }
+
+    /// Lists the entries in the order of `keys`, normalized keys as yielded
+    /// by [`Index::keyed`].
+    pub fn set_order(&mut self, keys: Vec<String>) {
+        self.order = keys;
+    }
+
+    /// The entries with their normalized keys, in listing order.
+    pub fn keyed(&self) -> impl Iterator<Item = (&str, &Entry)> {
+        self.order
+            .iter()
+            .map(|key| (key.as_str(), &self.entries[key]))
+    }
+
+    /// The entries in listing order.
+    pub fn entries(&self) -> impl Iterator<Item = &Entry> {
+        self.keyed().map(|(_, entry)| entry)
+    }
+
+    pub fn entries_mut(&mut self) -> impl Iterator<Item = &mut Entry> {
+        self.entries.values_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn children_follow_their_parent() {
+        let mut index = Index::new(CaseMode::Lowercase);
+        for key in ["rust-embedded", "rust/async", "rust", "rust/async/tokio"] {
+            let occurrence = Occurrence {
+                page: String::from("a.md"),
+                offset: 0,
+                snippet: String::new(),
+                anchor: String::new(),
+            };
+            index.record(key, occurrence);
+        }
+        index.sort();
+        let keys: Vec<&str> = index.keyed().map(|(key, _)| key).collect();
+        assert_eq!(
+            keys,
+            ["rust", "rust/async", "rust/async/tokio", "rust-embedded"]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use crate::autolinks;
+use crate::generate::escape_markdown;
+use regex::Regex;
+use std::ops::Range;
+
+/// A page of a source outside the book, written like `wiki:Rust`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Interwiki {
+    pub range: Range<usize>,
+    /// Prefix of the source, as configured.
+    pub source: String,
+    /// The page, as written after the prefix.
+    pub key: String,
+}
+
+impl Interwiki {
+    /// What its link shows, like `Rust (programming language)` for
+    /// `wiki:Rust_(programming_language)`.
+    pub fn title(&self) -> String {
+        self.key.replace('_', " ")
+    }
+}
+
+/// What matches a page of one of `sources`, their prefixes, if any.
+pub(crate) fn pattern<'a>(sources: impl Iterator<Item = &'a String>) -> Option<Regex> {
+    let sources: Vec<String> = sources.map(|source| regex::escape(source)).collect();
+    if sources.is_empty() {
+        return None;
+    }
+    let pattern = format!(r"\b(?:{}):[^\s<>\[\]]+", sources.join("|"));
+    Some(Regex::new(&pattern).expect("escaped prefixes make a valid pattern"))
+}
+
+/// The pages `pattern` finds in `content` outside code, HTML and links,
+/// leaving out those right after one of `prefixes` and the punctuation
+/// ending a sentence after them.
+pub(crate) fn find(content: &str, pattern: &Regex, prefixes: &[char]) -> Vec<Interwiki> {
+    autolinks::find(content, pattern, prefixes)
+        .into_iter()
+        .filter_map(|(range, written)| {
+            let (source, key) = written.split_once(':')?;
+            let mut key = key.trim_end_matches(['.', ',', ';', ':', '!', '?', '*', '"', '\'']);
+            // A closing parenthesis belongs to the page only if it opens one
+            while key.ends_with(')') && key.matches('(').count() < key.matches(')').count() {
+                key = key[..key.len() - 1].trim_end_matches(['.', ',', ';', ':', '!', '?']);
+            }
+            (!key.is_empty()).then(|| Interwiki {
+                range: range.start..range.start + source.len() + 1 + key.len(),
+                source: source.to_string(),
+                key: key.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// `content` with each of `found` linked to where `url` says it is.
+pub(crate) fn link(
+    content: &str,
+    found: &[Interwiki],
+    url: impl Fn(&Interwiki) -> String,
+) -> String {
+    let mut linked = String::with_capacity(content.len());
+    let mut last = 0;
+    for page in found {
+        linked.push_str(&content[last..page.range.start]);
+        linked.push_str(&format!(
+            "[{}]({})",
+            escape_markdown(&page.title()),
+            url(page)
+        ));
+        last = page.range.end;
+    }
+    linked.push_str(&content[last..]);
+    linked
+}
@@ -1,11 +1,109 @@
+mod acronyms;
+mod autolinks;
+mod blocks;
+mod citations;
+pub mod config;
+mod dates;
+mod deadlinks;
+mod emails;
+mod embeds;
+mod events;
+mod external;
+mod extract;
+mod flashcards;
+mod footnotes;
+mod frontmatter;
+mod generate;
+mod glossary;
+mod graph;
+mod identifiers;
+mod images;
+mod index;
+mod interwiki;
+mod locations;
+mod obsidian;
+mod paths;
+mod placement;
+mod query;
+mod quotes;
+mod reading;
+mod redaction;
+mod similarity;
+mod snippets;
+mod symbols;
+mod tables;
+mod tasks;
+mod templates;
+mod unlinked;
+mod variables;
+mod wikilinks;
+mod workspace;
+mod xrefs;
+mod zettel;
+
 pub mod indexer_lib {
 
+    use crate::acronyms;
+    use crate::autolinks;
+    use crate::blocks;
+    use crate::citations::{self, Reference};
+    use crate::config::{
+        BuildProfile, DeadLinks, EntityKind, FrontMatterMode, IndexerConfig, MentionLinksMode,
+        NumericKeys, PartIndexes, RelatedBy, SeeAlsoRule, TagSuggestions, UnlinkedMentions,
+        UnresolvedLinks,
+    };
+    use crate::dates::{self, Dated};
+    use crate::deadlinks;
+    use crate::emails;
+    use crate::embeds::Embedder;
+    use crate::events::{self, Event};
+    use crate::external::{self, ExternalLink};
+    use crate::extract::{link_targets, snippet, Form, Scanner, Token};
+    use crate::flashcards::{self, Card};
+    use crate::footnotes::{self, Note};
+    use crate::frontmatter::FrontMatter;
+    use crate::generate::{
+        footer_start, is_generated, is_stub, slug, ChapterInfo, Generator, METADATA_MARKER,
+        PROGRESS_MARKER,
+    };
+    use crate::glossary;
+    use crate::graph::Graph;
+    use crate::identifiers;
+    use crate::images::{self, Image};
+    use crate::index::{Entry, Index, Occurrence};
+    use crate::interwiki;
+    use crate::locations::{self, Location, Point};
+    use crate::obsidian;
+    use crate::paths::{link_to, resolve_link};
+    use crate::placement;
+    use crate::query::{self, Condition, Query};
+    use crate::quotes::{self, Callout, Quote};
+    use crate::reading::{self, Reading};
+    use crate::redaction;
+    use crate::similarity::{self, Cache};
+    use crate::snippets::Snippets;
+    use crate::symbols;
+    use crate::tables::{self, Table};
+    use crate::tasks::{self, Column, Due, Task};
+    use crate::templates::Templates;
+    use crate::unlinked::{self, Term};
+    use crate::variables;
+    use crate::wikilinks::{self, Stub, Stubs};
+    use crate::workspace::{self, Export};
+    use crate::xrefs::{self, Heading};
+    use crate::zettel;
+    use anyhow::Context;
     use mdbook::book::{Book, BookItem, Chapter};
     use mdbook::errors::Error;
     use mdbook::preprocess::{Preprocessor, PreprocessorContext};
-    use std::collections::HashMap;
-    use std::path::PathBuf;
+    use regex::Regex;
+    use serde_json::json;
+    use std::cmp::Reverse;
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+    use std::fs;
+    use std::path::Path;
 
+    #[derive(Default)]
     pub struct Indexer;
 
     impl Indexer {
@@ -16,17 +114,88 @@ pub mod indexer_lib {
 
     impl Preprocessor for Indexer {
         fn name(&self) -> &str {
-            "indexer_preprocessor"
+            "indexer"
         }
 
-        fn run(&self, _ctx: &PreprocessorContext, book: Book) -> Result<Book, Error> {
-            let mut updated_book = book.clone();
-
-            let (mentions, tags) = collect_mentions_and_tags(&mut updated_book);
-
-            // Generate index chapters
-            add_index_chapter(&mut updated_book, "tags.md", "Tags", "#", &tags);
-            add_index_chapter(&mut updated_book, "mentions.md", "Mentions", "@", &mentions);
+        fn run(&self, ctx: &PreprocessorContext, book: Book) -> Result<Book, Error> {
+            let config = IndexerConfig::from_context(ctx, self.name())?;
+            let kinds = config.entity_kinds()?;
+            let see_also = validate_config(&config, &kinds)?;
+            let tags = kinds.iter().position(|kind| kind.name == "tags");
+            let build = config.build_profile(&ctx.renderer)?;
+            let scanner = Scanner {
+                kinds: &kinds,
+                escape: config.escape,
+                mention_links: config.mention_links.as_ref(),
+            };
+            let src_dir = ctx.root.join(&ctx.config.book.src);
+            let (mut updated_book, mut stubs) =
+                prepare_book(&config, &book, &scanner, tags, &build, &src_dir)?;
+            let mut chapters = chapter_infos(&updated_book, &src_dir);
+            if config.zettelkasten {
+                let pattern = config
+                    .zettel_pattern
+                    .as_deref()
+                    .unwrap_or(zettel::DEFAULT_PATTERN);
+                let pattern = Regex::new(pattern)
+                    .with_context(|| format!("zettel_pattern `{pattern}` is not a valid regex"))?;
+                assign_ids(&updated_book, &mut chapters, &pattern);
+            }
+            let theme = ctx
+                .config
+                .get("output.html.theme")
+                .and_then(|theme| theme.as_str())
+                .unwrap_or("theme");
+            let template_dir = ctx.root.join(config.templates.as_deref().unwrap_or(theme));
+            let templates = Templates::load(&template_dir, &kinds)?;
+            let siblings = workspace::load(&ctx.root, &config.workspace)?;
+            let generator = Generator {
+                config: &config,
+                chapters: &chapters,
+                renderer: &ctx.renderer,
+                templates: &templates,
+                siblings: &siblings,
+            };
+            let run = Run {
+                ctx,
+                scanner: &scanner,
+                generator: &generator,
+                tags,
+            };
+            let collected = extract_book(&run, &mut updated_book)?;
+            let (indexes, scopes, links) = collect_entities(
+                &mut updated_book,
+                &scanner,
+                &generator,
+                &mut stubs,
+                &collected.similar,
+                &collected.front_matters,
+                &collected.notes,
+            );
+            let graph = config
+                .builds_graph()
+                .then(|| Graph::build(&generator, &kinds, &indexes, &links, &collected.similar));
+            let orphans = if config.reports_orphans() {
+                orphans(&indexes, &chapters, &links)
+            } else {
+                Vec::new()
+            };
+            let indexed = Indexed {
+                indexes,
+                scopes,
+                graph,
+                orphans,
+            };
+            let glossed = rewrite_book(&run, &mut updated_book, &indexed, &collected, &see_also);
+            export_files(&run, &indexed, &collected)?;
+            let generated = generate_chapters(&run, &indexed, &collected, &glossed, &stubs)?;
+            placement::place(
+                &mut updated_book,
+                generated.book,
+                generated.in_parts,
+                &config.placement,
+            )?;
+            validate_links(&updated_book, &src_dir, &config)?;
 
             Ok(updated_book)
         }
@@ -36,92 +205,3133 @@ pub mod indexer_lib {
         }
     }
 
-    fn collect_mentions_and_tags(
-        book: &mut Book,
-    ) -> (HashMap<String, Vec<String>>, HashMap<String, Vec<String>>) {
-        let mut mentions = HashMap::new();
-        let mut tags = HashMap::new();
+    /// What every step of a run after extraction works with.
+    #[derive(Clone, Copy)]
+    struct Run<'a> {
+        ctx: &'a PreprocessorContext,
+        scanner: &'a Scanner<'a>,
+        generator: &'a Generator<'a>,
+        /// The position of the `tags` entity type, if enabled.
+        tags: Option<usize>,
+    }
 
-        book.for_each_mut(|item| {
+    /// What the chapters hold besides their tokens, as collected before
+    /// they are rewritten.
+    struct Collected {
+        keywords: BTreeMap<String, Vec<(String, f64)>>,
+        similar: HashMap<String, Vec<(String, f64)>>,
+        definitions: Vec<glossary::Definition>,
+        tasks: BTreeMap<String, Vec<Task>>,
+        metadata: HashMap<String, Vec<(String, Vec<String>)>>,
+        front_matters: HashMap<String, FrontMatter>,
+        due: Vec<Due>,
+        dated: Vec<Dated>,
+        readings: Vec<Reading>,
+        quotes: Vec<Quote>,
+        footnotes: Vec<Note>,
+        callouts: Vec<Callout>,
+        images: Vec<Image>,
+        references: BTreeMap<String, Reference>,
+        cited: BTreeMap<String, Vec<String>>,
+        commits: BTreeMap<String, Vec<String>>,
+        tickets: BTreeMap<String, Vec<String>>,
+        interwikis: BTreeMap<String, BTreeMap<String, (String, Vec<String>)>>,
+        contacts: BTreeMap<String, Vec<String>>,
+        places: BTreeMap<String, Location>,
+        events: Vec<Event>,
+        cards: Vec<Card>,
+        sources: BTreeMap<String, (String, Vec<String>)>,
+        symbols: BTreeMap<String, Vec<String>>,
+        outbound: BTreeMap<String, Vec<ExternalLink>>,
+        numbered: Vec<Table>,
+        /// The daily notes of `journal`, as their day and path.
+        notes: Vec<(String, String)>,
+    }
+
+    /// The entries of a run, and how its chapters link to each other.
+    struct Indexed {
+        indexes: Vec<Index>,
+        scopes: Vec<PartScope>,
+        graph: Option<Graph>,
+        /// The chapters nothing links to, if they are reported.
+        orphans: Vec<String>,
+    }
+
+    /// Where the glossary's terms and the acronyms are used.
+    struct Glossed {
+        uses: Vec<Vec<String>>,
+        acronyms: BTreeMap<String, String>,
+        acronym_uses: BTreeMap<String, Vec<String>>,
+    }
+
+    /// The generated chapters, for the book and for each part.
+    struct Generated {
+        book: Vec<Chapter>,
+        in_parts: Vec<(String, Vec<Chapter>)>,
+    }
+
+    /// Fails on settings that contradict each other or name what doesn't
+    /// exist, and reads the `see_also` rules.
+    fn validate_config<'a>(
+        config: &'a IndexerConfig,
+        kinds: &[EntityKind],
+    ) -> Result<Vec<(Query, &'a SeeAlsoRule)>, Error> {
+        if config.part_indexes == PartIndexes::Only
+            && (config.tag_cloud
+                || config.co_occurrence
+                || config.stats
+                || config.master_index
+                || !config.kanban.is_empty()
+                || config.builds_graph()
+                || config.reports_orphans())
+        {
+            anyhow::bail!(
+                "part_indexes = \"only\" leaves no book-wide index for tag_cloud, \
+                 co_occurrence, stats, master_index, kanban, graph or orphans to use"
+            );
+        }
+        let unknown = config
+            .graph_kinds
+            .iter()
+            .flatten()
+            .find(|name| !kinds.iter().any(|kind| &kind.name == *name));
+        if let Some(name) = unknown {
+            anyhow::bail!("graph_kinds lists `{name}`, which is not an enabled entity type");
+        }
+        let tags = kinds.iter().position(|kind| kind.name == "tags");
+        if config.tag_suggestions != TagSuggestions::Ignore && tags.is_none() {
+            anyhow::bail!("tag_suggestions needs the `tags` entity type");
+        }
+        if !config.kanban.is_empty() && tags.is_none() {
+            anyhow::bail!("kanban needs the `tags` entity type");
+        }
+        if config.reading_list && tags.is_none() {
+            anyhow::bail!("reading_list needs the `tags` entity type");
+        }
+        if config.quote_tag.is_some() && tags.is_none() {
+            anyhow::bail!("quote_tag needs the `tags` entity type");
+        }
+        if config.issue_repository.is_some() && tags.is_none() {
+            anyhow::bail!("issue_repository needs the `tags` entity type");
+        }
+        if config.commits && config.commit_repository().is_none() {
+            anyhow::bail!("commits needs a commit_repository or issue_repository");
+        }
+        if config.tickets && config.ticket_url.is_none() {
+            anyhow::bail!("tickets needs a ticket_url");
+        }
+        if config.interwiki_references && config.interwiki.is_empty() {
+            anyhow::bail!("interwiki_references needs interwiki sources");
+        }
+        if config.workspace_json.is_some() && config.site_url.is_none() {
+            anyhow::bail!("workspace_json needs a site_url");
+        }
+        if !config.exclude_statuses.is_empty() && tags.is_none() {
+            anyhow::bail!("exclude_statuses needs the `tags` entity type");
+        }
+        let unknown = config.exclude_statuses.values().flatten().find(|status| {
+            !config
+                .statuses
+                .iter()
+                .any(|s| s.eq_ignore_ascii_case(status))
+        });
+        if let Some(status) = unknown {
+            anyhow::bail!("exclude_statuses lists `{status}`, which is not one of statuses");
+        }
+        for (name, profile) in &config.profiles {
+            let by_tag = !profile.exclude_tags.is_empty() || !profile.exclude_statuses.is_empty();
+            if by_tag && tags.is_none() {
+                anyhow::bail!("the profile `{name}` needs the `tags` entity type");
+            }
+            let unknown = profile.exclude_statuses.iter().find(|status| {
+                !config
+                    .statuses
+                    .iter()
+                    .any(|s| s.eq_ignore_ascii_case(status))
+            });
+            if let Some(status) = unknown {
+                anyhow::bail!(
+                    "the profile `{name}` excludes `{status}`, which is not one of statuses"
+                );
+            }
+        }
+        let mut see_also = Vec::new();
+        for rule in &config.see_also {
+            match query::parse(&rule.when) {
+                Ok(query) => see_also.push((query, rule)),
+                Err(error) => {
+                    anyhow::bail!("the see_also rule `{}` is invalid: {error}", rule.when)
+                }
+            }
+        }
+        Ok(see_also)
+    }
+
+    /// A copy of `book` without what an earlier run added, and with what
+    /// `build` leaves out taken out, before anything is scanned, along with
+    /// the stubs earlier runs created.
+    fn prepare_book(
+        config: &IndexerConfig,
+        book: &Book,
+        scanner: &Scanner,
+        tags: Option<usize>,
+        build: &BuildProfile,
+        src_dir: &Path,
+    ) -> Result<(Book, Stubs), Error> {
+        let mut updated_book = book.clone();
+        // Chapters from an earlier run are rebuilt instead of scanned
+        placement::remove_part(&mut updated_book.sections, &config.placement);
+        let mut stubs = Stubs {
+            dir: config.stub_dir.clone(),
+            pages: BTreeMap::new(),
+        };
+        if config.wikilinks && config.unresolved_wikilinks == UnresolvedLinks::Stub {
+            // Links to earlier stubs are no longer wikilinks
+            earlier_stubs(&updated_book.sections, &mut stubs.pages);
+        }
+        remove_generated(&mut updated_book.sections);
+        // Lines shown below titles by an earlier run are put back later
+        updated_book.for_each_mut(|item| {
             if let BookItem::Chapter(chapter) = item {
-                if let Some(content) = process_chapter(chapter, &mut mentions, &mut tags) {
-                    chapter.content = content;
+                remove_below_title(&mut chapter.content, METADATA_MARKER, true);
+                remove_below_title(&mut chapter.content, PROGRESS_MARKER, false);
+            }
+        });
+        if config.obsidian {
+            let files = obsidian::files(src_dir)?;
+            obsidian::add_notes(&mut updated_book, src_dir, &files)?;
+            updated_book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item {
+                    let page = chapter_path(chapter);
+                    let content = obsidian::strip_comments(&chapter.content);
+                    chapter.content = obsidian::embed_images(&content, &page, &files);
+                }
+            });
+        }
+        if config.include_snippets {
+            let dir = config.snippets_dir.trim_end_matches('/');
+            let is_snippet = |page: &str| {
+                config.snippets_chapter.as_deref() == Some(page)
+                    || page
+                        .strip_prefix(dir)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            };
+            let mut sources = HashSet::new();
+            let mut chapter = None;
+            for item in updated_book.iter() {
+                if let BookItem::Chapter(source) = item {
+                    let page = chapter_path(source);
+                    if config.snippets_chapter.as_ref() == Some(&page) {
+                        chapter = Some((page.clone(), source.content.clone()));
+                    }
+                    if is_snippet(&page) {
+                        sources.insert(page);
+                    }
+                }
+            }
+            if let Some(page) = config
+                .snippets_chapter
+                .as_ref()
+                .filter(|_| chapter.is_none())
+            {
+                eprintln!("Warning: snippets_chapter names {page}, which isn't a chapter");
+            }
+            let snippets = Snippets::load(src_dir, dir, chapter)?;
+            leave_out(&mut updated_book.sections, &sources, &mut HashSet::new());
+            updated_book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item {
+                    let page = chapter_path(chapter);
+                    chapter.content = snippets.expand(&chapter.content, &page);
+                }
+            });
+        }
+
+        if build.redact {
+            let tag = format!("{}{}", config.tag_prefix, config.private_tag);
+            let notice = config
+                .redaction_notice
+                .then_some(config.labels.redacted.as_str());
+            updated_book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item {
+                    let body = FrontMatter::parse(&chapter.content).map_or(0, |front| front.len);
+                    let redacted = redaction::redact(&chapter.content[body..], &tag, notice);
+                    chapter.content.replace_range(body.., &redacted);
+                }
+            });
+        }
+        leave_out_chapters(&mut updated_book, scanner, tags, config, build);
+        Ok((updated_book, stubs))
+    }
+
+    /// Collects what the chapters of `book` hold besides their tokens,
+    /// linking what the settings ask to on the way.
+    fn extract_book(run: &Run, book: &mut Book) -> Result<Collected, Error> {
+        let Run {
+            ctx,
+            scanner,
+            generator,
+            tags,
+        } = *run;
+        let config = generator.config;
+        let chapters = generator.chapters;
+        let kinds = scanner.kinds;
+        let by_text = config.related_by == RelatedBy::Text
+            && (config.related.is_some() || config.graph_json.is_some());
+        let keywords = if by_text || config.tag_suggestions != TagSuggestions::Ignore {
+            chapter_keywords(book, chapters, &ctx.root, config)?
+        } else {
+            BTreeMap::new()
+        };
+        let similar = if by_text {
+            let limit = config.related.unwrap_or(similarity::DEFAULT_LIMIT);
+            similarity::similar(&keywords, limit)
+        } else {
+            HashMap::new()
+        };
+        let definitions = match &config.glossary {
+            Some(source) => glossary::load(source, &ctx.root, book)?,
+            None => Vec::new(),
+        };
+        let tasks =
+            if config.tasks || config.task_progress || !config.kanban.is_empty() || config.agenda {
+                chapter_tasks(book)
+            } else {
+                BTreeMap::new()
+            };
+        let metadata = if config.metadata {
+            chapter_metadata(book, kinds, &config.metadata_fields)
+        } else {
+            HashMap::new()
+        };
+        // Front matter may be stripped by the time queries are answered
+        let front_matters: HashMap<String, FrontMatter> =
+            if config.queries || !config.see_also.is_empty() {
+                book.iter()
+                    .filter_map(|item| match item {
+                        BookItem::Chapter(chapter) => {
+                            Some((chapter_path(chapter), FrontMatter::parse(&chapter.content)?))
+                        }
+                        _ => None,
+                    })
+                    .collect()
+            } else {
+                HashMap::new()
+            };
+        let due = if config.agenda {
+            due_dates(book, &tasks, &config.due_marker)
+        } else {
+            Vec::new()
+        };
+        let dated = if config.timeline {
+            written_dates(book)
+        } else {
+            Vec::new()
+        };
+        let readings = match tags.filter(|_| config.reading_list) {
+            Some(tags) => reading_list(book, scanner, tags, &config.reading_tag),
+            None => Vec::new(),
+        };
+        let quotes = if config.quotes {
+            let tag = tags.zip(config.quote_tag.as_deref());
+            collect_quotes(book, scanner, tag)
+        } else {
+            Vec::new()
+        };
+        let footnotes = if config.endnotes {
+            endnotes(book)
+        } else {
+            Vec::new()
+        };
+        let callouts = if config.callouts {
+            collect_callouts(book)
+        } else {
+            Vec::new()
+        };
+        let images = if config.images {
+            collect_images(book)
+        } else {
+            Vec::new()
+        };
+        let references = match &config.bibliography {
+            Some(file) => citations::load(&ctx.root.join(file))?,
+            None => BTreeMap::new(),
+        };
+        let cited = if config.bibliography.is_some() {
+            cite(book, &references, config)
+        } else {
+            BTreeMap::new()
+        };
+        if let Some((tags, repository)) = tags.zip(config.issue_repository.as_deref()) {
+            link_issues(book, &kinds[tags], config.escape, repository);
+        }
+        let prefixes: Vec<char> = kinds.iter().map(|kind| kind.prefix).collect();
+        let commits = match config.commit_repository() {
+            Some(repository) => {
+                let pattern = config
+                    .commit_pattern
+                    .as_deref()
+                    .unwrap_or(autolinks::COMMIT_PATTERN);
+                let pattern = Regex::new(pattern)
+                    .with_context(|| format!("commit_pattern `{pattern}` is not a valid regex"))?;
+                link_commits(book, &pattern, &prefixes, repository)
+            }
+            None => BTreeMap::new(),
+        };
+        let tickets = if config.ticket_url.is_some() {
+            let pattern = config
+                .ticket_pattern
+                .as_deref()
+                .unwrap_or(autolinks::TICKET_PATTERN);
+            let pattern = Regex::new(pattern)
+                .with_context(|| format!("ticket_pattern `{pattern}` is not a valid regex"))?;
+            link_tickets(book, &pattern, &prefixes, config)
+        } else {
+            BTreeMap::new()
+        };
+        let interwikis = match interwiki::pattern(config.interwiki.keys()) {
+            Some(pattern) => link_interwiki(book, &pattern, &prefixes, config),
+            None => BTreeMap::new(),
+        };
+        let contacts = if config.contacts || config.obfuscate_emails {
+            let html = ctx.renderer == "html";
+            collect_emails(book, &prefixes, config.obfuscate_emails, html)
+        } else {
+            BTreeMap::new()
+        };
+        let places = if config.locations || config.location_map {
+            collect_locations(book, scanner, tags, config)
+        } else {
+            BTreeMap::new()
+        };
+        let events = if config.events {
+            collect_events(book)
+        } else {
+            Vec::new()
+        };
+        let cards = if config.flashcards || config.flashcards_tsv.is_some() {
+            let tag = format!("{}{}", config.tag_prefix, config.flashcard_tag);
+            collect_flashcards(book, &tag)
+        } else {
+            Vec::new()
+        };
+        let sources = if config.link_identifiers || config.sources {
+            link_identifiers(book, config.link_identifiers)
+        } else {
+            BTreeMap::new()
+        };
+        if config.block_references {
+            book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item {
+                    let body = FrontMatter::parse(&chapter.content).map_or(0, |front| front.len);
+                    let rewritten = blocks::rewrite(&chapter.content[body..]);
+                    chapter.content.replace_range(body.., &rewritten);
+                }
+            });
+        }
+        if config.cross_references || config.headings_json.is_some() {
+            let registry = heading_registry(book);
+            if let Some(file) = &config.headings_json {
+                let json = serde_json::to_string_pretty(&json!({ "headings": registry }))?;
+                export(&ctx.root.join(file), &json)?;
+            }
+            if config.cross_references {
+                let mut errors = Vec::new();
+                book.for_each_mut(|item| {
+                    if let BookItem::Chapter(chapter) = item {
+                        let page = chapter_path(chapter);
+                        chapter.content = xrefs::rewrite(
+                            &chapter.content,
+                            &page,
+                            &registry,
+                            chapters,
+                            &mut errors,
+                        );
+                    }
+                });
+                if !errors.is_empty() {
+                    anyhow::bail!(
+                        "cross-references that don't resolve:\n  {}",
+                        errors.join("\n  ")
+                    );
+                }
+            }
+        }
+        let symbols = if config.symbols {
+            let pattern = config
+                .symbol_pattern
+                .as_deref()
+                .unwrap_or(symbols::DEFAULT_PATTERN);
+            let pattern = Regex::new(pattern)
+                .with_context(|| format!("symbol_pattern `{pattern}` is not a valid regex"))?;
+            code_symbols(book, &pattern)
+        } else {
+            BTreeMap::new()
+        };
+        let outbound = if config.external_links {
+            external_links(book)
+        } else {
+            BTreeMap::new()
+        };
+        let numbered = if config.tables {
+            number_tables(book, &config.labels.table)
+        } else {
+            Vec::new()
+        };
+        let notes = match &config.journal {
+            Some(dir) => daily_notes(chapters, dir),
+            None => Vec::new(),
+        };
+        Ok(Collected {
+            keywords,
+            similar,
+            definitions,
+            tasks,
+            metadata,
+            front_matters,
+            due,
+            dated,
+            readings,
+            quotes,
+            footnotes,
+            callouts,
+            images,
+            references,
+            cited,
+            commits,
+            tickets,
+            interwikis,
+            contacts,
+            places,
+            events,
+            cards,
+            sources,
+            symbols,
+            outbound,
+            numbered,
+            notes,
+        })
+    }
+
+    /// Adds to the chapters of `book` what depends on the final entries:
+    /// glossary links, acronyms, suggested tags, journal links, task
+    /// progress, see-also lists, variables and metadata.
+    fn rewrite_book(
+        run: &Run,
+        book: &mut Book,
+        indexed: &Indexed,
+        collected: &Collected,
+        see_also: &[(Query, &SeeAlsoRule)],
+    ) -> Glossed {
+        let Run {
+            ctx,
+            scanner,
+            generator,
+            tags,
+        } = *run;
+        let config = generator.config;
+        let chapters = generator.chapters;
+        let kinds = scanner.kinds;
+        let Indexed {
+            indexes, scopes, ..
+        } = indexed;
+        let Collected {
+            keywords,
+            definitions,
+            tasks,
+            metadata,
+            front_matters,
+            notes,
+            ..
+        } = collected;
+        let mentions = kinds.iter().position(|kind| kind.name == "mentions");
+        if let Some(mentions) = mentions.filter(|_| config.warn_unknown_people) {
+            let kind = &kinds[mentions];
+            for entry in indexes[mentions].entries() {
+                if kind.profile(&entry.display).is_none() {
+                    let pages: Vec<&str> = entry
+                        .pages()
+                        .map(|occurrences| occurrences[0].page.as_str())
+                        .collect();
+                    eprintln!(
+                        "Warning: {}{} is mentioned in {} but not declared in people",
+                        kind.prefix,
+                        entry.display,
+                        pages.join(", ")
+                    );
+                }
+            }
+        }
+        let uses = link_glossary(book, kinds, definitions, config);
+        let (acronyms, acronym_uses) = expand_acronyms(book, config, &ctx.renderer);
+        if let Some(tags) = tags.filter(|_| config.tag_suggestions != TagSuggestions::Ignore) {
+            let indexes: Vec<&Index> = std::iter::once(&indexes[tags])
+                .chain(scopes.iter().map(|scope| &scope.indexes[tags]))
+                .collect();
+            let suggested = suggest_tags(&kinds[tags], &indexes, keywords, config.suggested_tags);
+            if config.tag_suggestions == TagSuggestions::Warn {
+                for (page, tags) in &suggested {
+                    eprintln!("Warning: {page} has no tags; consider {}", tags.join(", "));
+                }
+            } else {
+                book.for_each_mut(|item| {
+                    if let BookItem::Chapter(chapter) = item {
+                        if let Some(tags) = suggested.get(&chapter_path(chapter)) {
+                            chapter.content.push_str(&generator.suggestions(tags));
+                        }
+                    }
+                });
+            }
+        }
+        if !notes.is_empty() {
+            book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item {
+                    let page = chapter_path(chapter);
+                    if let Some(i) = notes.iter().position(|(_, note)| *note == page) {
+                        let previous = i.checked_sub(1).map(|i| &notes[i]);
+                        let links = generator.journal_links(&page, previous, notes.get(i + 1));
+                        chapter.content.push_str(&links);
+                    }
+                }
+            });
+        }
+        if config.task_progress {
+            book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item {
+                    match tasks.get(&chapter_path(chapter)) {
+                        Some(tasks) if !tasks.is_empty() => {
+                            show_below_title(chapter, &generator.progress(tasks))
+                        }
+                        _ => {}
+                    }
+                }
+            });
+        }
+        let matches = |page: &str, condition: &Condition| {
+            meets_condition(
+                page,
+                condition,
+                kinds,
+                indexes,
+                front_matters,
+                chapters,
+                config,
+            )
+        };
+        if !see_also.is_empty() {
+            let mut targets = Vec::with_capacity(see_also.len());
+            for (_, rule) in see_also {
+                let mut pages = Vec::new();
+                for target in &rule.pages {
+                    match wikilinks::resolve(target, chapters) {
+                        Some(page) => pages.push(page.to_string()),
+                        None => eprintln!(
+                            "Warning: the see_also rule `{}` links to {target}, \
+                             which is not a chapter",
+                            rule.when
+                        ),
+                    }
+                }
+                targets.push(pages);
+            }
+            book.for_each_mut(|item| {
+                let BookItem::Chapter(chapter) = item else {
+                    return;
+                };
+                let page = chapter_path(chapter);
+                if page.is_empty() || is_generated(chapter) {
+                    return;
+                }
+                let mut pages: Vec<&str> = Vec::new();
+                for ((query, _), targets) in see_also.iter().zip(&targets) {
+                    if !query.matches(&|condition| matches(&page, condition)) {
+                        continue;
+                    }
+                    for target in targets {
+                        if *target != page && !pages.contains(&target.as_str()) {
+                            pages.push(target);
+                        }
+                    }
+                }
+                if !pages.is_empty() {
+                    chapter
+                        .content
+                        .push_str(&generator.see_also(chapter, &pages));
+                }
+            });
+        }
+        if config.expand_variables {
+            let mut values: HashMap<String, String> = HashMap::new();
+            values.insert(String::from("date"), dates::today());
+            let title = ctx.config.book.title.clone().unwrap_or_default();
+            values.insert(String::from("book.title"), title);
+            let count = chapters.keys().filter(|page| !page.is_empty()).count();
+            values.insert(String::from("chapter_count"), count.to_string());
+            for (kind, index) in kinds.iter().zip(indexes) {
+                let count = index.entries().filter(|e| generator.is_listed(e)).count();
+                let singular = kind.name.strip_suffix('s').unwrap_or(&kind.name);
+                values.insert(format!("{singular}_count"), count.to_string());
+                values.insert(format!("{}_count", kind.name), count.to_string());
+            }
+            book.for_each_mut(|item| {
+                let BookItem::Chapter(chapter) = item else {
+                    return;
+                };
+                let page = chapter_path(chapter);
+                let value = |name: &str| {
+                    config.variables.get(name).cloned().or_else(|| match name {
+                        "chapter.title" => Some(chapter.name.clone()),
+                        "chapter.path" => Some(page.clone()),
+                        _ => values.get(name).cloned(),
+                    })
+                };
+                let body = FrontMatter::parse(&chapter.content).map_or(0, |front| front.len);
+                let (expanded, unknown) = variables::expand(&chapter.content[body..], value);
+                for name in unknown {
+                    eprintln!("Warning: {page} uses the unknown variable {{{{{name}}}}}");
+                }
+                chapter.content.replace_range(body.., &expanded);
+            });
+        }
+        if config.metadata {
+            book.for_each_mut(|item| {
+                if let BookItem::Chapter(chapter) = item {
+                    let page = chapter_path(chapter);
+                    if let Some(fields) = metadata.get(&page) {
+                        let block = generator.metadata(&page, fields, kinds, indexes);
+                        show_below_title(chapter, &block);
+                    }
+                }
+            });
+        }
+        Glossed {
+            uses,
+            acronyms,
+            acronym_uses,
+        }
+    }
+
+    /// Writes the files the settings ask for outside the book.
+    fn export_files(run: &Run, indexed: &Indexed, collected: &Collected) -> Result<(), Error> {
+        let Run {
+            ctx,
+            scanner,
+            generator,
+            ..
+        } = *run;
+        let config = generator.config;
+        let chapters = generator.chapters;
+        let kinds = scanner.kinds;
+        let Indexed {
+            indexes,
+            graph,
+            orphans,
+            ..
+        } = indexed;
+        let Collected { cards, .. } = collected;
+        if let Some((file, url)) = config.workspace_json.as_ref().zip(config.site_url.as_ref()) {
+            let title = ctx.config.book.title.as_deref().unwrap_or_default();
+            let exported = Export::new(title, url, generator, kinds, indexes);
+            export(
+                &ctx.root.join(file),
+                &serde_json::to_string_pretty(&exported)?,
+            )?;
+        }
+        if let Some(graph) = graph {
+            if let Some(file) = &config.graph_json {
+                let json = serde_json::to_string_pretty(&graph.json())?;
+                export(&ctx.root.join(file), &json)?;
+            }
+            if let Some(file) = &config.graph_dot {
+                export(&ctx.root.join(file), &graph.dot(&config.graph_title))?;
+            }
+            if let Some(file) = &config.graph_graphml {
+                export(&ctx.root.join(file), &graph.graphml(&config.graph_title))?;
+            }
+            if let Some(file) = &config.graph_gexf {
+                export(&ctx.root.join(file), &graph.gexf(&config.graph_title))?;
+            }
+        }
+        if let Some(file) = &config.orphans_json {
+            let listed: Vec<_> = orphans
+                .iter()
+                .map(|page| json!({ "path": page, "title": chapters[page].name }))
+                .collect();
+            let json = serde_json::to_string_pretty(&json!({ "orphans": listed }))?;
+            export(&ctx.root.join(file), &json)?;
+        }
+        if let Some(file) = &config.flashcards_tsv {
+            let deck = deck(ctx, config);
+            export(&ctx.root.join(file), &flashcards::tsv(cards, deck))?;
+        }
+        Ok(())
+    }
+
+    /// Builds the index, glossary, report and stub chapters.
+    fn generate_chapters(
+        run: &Run,
+        indexed: &Indexed,
+        collected: &Collected,
+        glossed: &Glossed,
+        stubs: &Stubs,
+    ) -> Result<Generated, Error> {
+        let Run {
+            ctx,
+            scanner,
+            generator,
+            tags,
+        } = *run;
+        let config = generator.config;
+        let chapters = generator.chapters;
+        let kinds = scanner.kinds;
+        let Glossed {
+            uses,
+            acronyms,
+            acronym_uses,
+            ..
+        } = glossed;
+        let Indexed {
+            indexes, scopes, ..
+        } = indexed;
+        let Collected {
+            definitions,
+            tasks,
+            due,
+            dated,
+            readings,
+            quotes,
+            footnotes,
+            callouts,
+            images,
+            references,
+            cited,
+            commits,
+            tickets,
+            interwikis,
+            contacts,
+            places,
+            events,
+            cards,
+            sources,
+            symbols,
+            outbound,
+            numbered,
+            notes,
+            ..
+        } = collected;
+        let mut generated = Vec::new();
+        let book_wide = config.part_indexes != PartIndexes::Only
+            || chapters.values().any(|chapter| chapter.part.is_none());
+        for (kind, index) in kinds.iter().zip(indexes).filter(|_| book_wide) {
+            let chapter = generator.index_chapter(kind, index)?;
+            check_collisions(&chapter, &format!("`{}` index", kind.name), chapters)?;
+            generated.push(chapter);
+            if config.tag_cloud && kind.name == "tags" {
+                let cloud = generator.cloud_chapter(kind, index);
+                check_collisions(&cloud, "tag cloud", chapters)?;
+                generated.push(cloud);
+            }
+            if config.co_occurrence && kind.name == "tags" {
+                let pairs = generator.co_occurrence_chapter(kind, index);
+                check_collisions(&pairs, "pairs of tags' list", chapters)?;
+                generated.push(pairs);
+            }
+        }
+        if config.stats {
+            let stats = generator.stats_chapter(kinds, indexes);
+            check_collisions(&stats, "statistics chapter", chapters)?;
+            generated.push(stats);
+        }
+        if config.master_index {
+            let master = generator.master_index_chapter(kinds, indexes);
+            check_collisions(&master, "combined index", chapters)?;
+            generated.push(master);
+        }
+        if let Some(graph) = indexed.graph.as_ref().filter(|_| config.graph) {
+            let chapter = generator.graph_chapter(graph);
+            check_collisions(&chapter, "graph chapter", chapters)?;
+            generated.push(chapter);
+        }
+        if config.reports_orphans() {
+            if config.warn_orphans {
+                for page in &indexed.orphans {
+                    eprintln!(
+                        "Warning: {page} is an orphan: no chapter links to it or shares a key"
+                    );
+                }
+            }
+            if config.orphans {
+                let chapter = generator.orphans_chapter(&indexed.orphans);
+                check_collisions(&chapter, "orphans' list", chapters)?;
+                generated.push(chapter);
+            }
+        }
+        if config.untagged {
+            let untagged = generator.untagged_chapter(indexes);
+            check_collisions(&untagged, "untagged chapters' list", chapters)?;
+            generated.push(untagged);
+        }
+        if config.glossary.is_some() {
+            let glossary = generator.glossary_chapter(definitions, uses);
+            check_collisions(&glossary, "glossary", chapters)?;
+            generated.push(glossary);
+        }
+        if config.acronym_index {
+            let list = generator.acronyms_chapter(acronyms, acronym_uses);
+            check_collisions(&list, "acronyms' list", chapters)?;
+            generated.push(list);
+        }
+        if config.tasks {
+            let list = generator.tasks_chapter(tasks);
+            check_collisions(&list, "tasks' list", chapters)?;
+            generated.push(list);
+        }
+        if let Some(tags) = tags.filter(|_| !config.kanban.is_empty()) {
+            let columns = board(scanner, tags, &indexes[tags], tasks, &config.kanban);
+            let board = generator.kanban_chapter(&columns);
+            check_collisions(&board, "board", chapters)?;
+            generated.push(board);
+        }
+        if config.agenda {
+            let agenda = generator.agenda_chapter(due, &dates::today());
+            check_collisions(&agenda, "agenda", chapters)?;
+            generated.push(agenda);
+        }
+        if config.timeline {
+            let timeline = generator.timeline_chapter(dated);
+            check_collisions(&timeline, "timeline", chapters)?;
+            generated.push(timeline);
+        }
+        if config.journal.is_some() {
+            let journal = generator.journal_chapter(notes);
+            check_collisions(&journal, "journal's calendar", chapters)?;
+            generated.push(journal);
+        }
+        if config.reading_list {
+            let list = generator.reading_list_chapter(readings);
+            check_collisions(&list, "reading list", chapters)?;
+            generated.push(list);
+        }
+        if config.quotes {
+            let list = generator.quotes_chapter(quotes);
+            check_collisions(&list, "quotes' list", chapters)?;
+            generated.push(list);
+        }
+        if config.endnotes {
+            let endnotes = generator.endnotes_chapter(footnotes);
+            check_collisions(&endnotes, "endnotes", chapters)?;
+            generated.push(endnotes);
+        }
+        if config.callouts {
+            let list = generator.callouts_chapter(callouts);
+            check_collisions(&list, "callouts' list", chapters)?;
+            generated.push(list);
+        }
+        if config.images {
+            let list = generator.images_chapter(images);
+            check_collisions(&list, "images' list", chapters)?;
+            generated.push(list);
+        }
+        if config.tables {
+            let list = generator.tables_chapter(numbered);
+            check_collisions(&list, "tables' list", chapters)?;
+            generated.push(list);
+        }
+        if config.external_links {
+            let list = generator.external_links_chapter(outbound);
+            check_collisions(&list, "external links' list", chapters)?;
+            generated.push(list);
+        }
+        if config.symbols {
+            let list = generator.symbols_chapter(symbols);
+            check_collisions(&list, "symbols' list", chapters)?;
+            generated.push(list);
+        }
+        if config.bibliography.is_some() {
+            let list = generator.references_chapter(references, cited);
+            check_collisions(&list, "references' list", chapters)?;
+            generated.push(list);
+        }
+        if config.sources {
+            let list = generator.sources_chapter(sources);
+            check_collisions(&list, "sources' list", chapters)?;
+            generated.push(list);
+        }
+        if config.commits {
+            let list = generator.commits_chapter(commits);
+            check_collisions(&list, "commits' list", chapters)?;
+            generated.push(list);
+        }
+        if config.tickets {
+            let list = generator.tickets_chapter(tickets);
+            check_collisions(&list, "tickets' list", chapters)?;
+            generated.push(list);
+        }
+        if config.interwiki_references {
+            let list = generator.interwiki_chapter(interwikis);
+            check_collisions(&list, "external references' list", chapters)?;
+            generated.push(list);
+        }
+        if config.contacts {
+            let list = generator.contacts_chapter(contacts);
+            check_collisions(&list, "contacts' list", chapters)?;
+            generated.push(list);
+        }
+        if config.locations {
+            let list = generator.locations_chapter(places);
+            check_collisions(&list, "locations' list", chapters)?;
+            generated.push(list);
+        }
+        if config.location_map && ctx.renderer == "html" {
+            for place in places.values().filter(|place| place.point.is_none()) {
+                eprintln!(
+                    "Warning: {} has no coordinates in places, so the map leaves it out",
+                    place.name
+                );
+            }
+            let map = generator.location_map_chapter(places);
+            check_collisions(&map, "map", chapters)?;
+            generated.push(map);
+        }
+        if config.events {
+            let calendar = generator.events_chapter(events);
+            check_collisions(&calendar, "events' calendar", chapters)?;
+            generated.push(calendar);
+        }
+        let deck = deck(ctx, config);
+        if config.flashcards {
+            let review = generator.flashcards_chapter(cards, deck);
+            check_collisions(&review, "flashcards' review", chapters)?;
+            generated.push(review);
+        }
+        if config.alias_redirects {
+            let moved = generator.redirects_chapter(&redirects(chapters));
+            check_collisions(&moved, "list of moved pages", chapters)?;
+            generated.push(moved);
+        }
+        if config.zettelkasten {
+            let register = generator.register_chapter();
+            check_collisions(&register, "register", chapters)?;
+            generated.push(register);
+        }
+        for (page, stub) in &stubs.pages {
+            if stub.sources.is_empty() {
+                continue;
+            }
+            let chapter = generator.stub_chapter(page, stub);
+            check_collisions(&chapter, &format!("stub of [[{}]]", stub.title), chapters)?;
+            generated.push(chapter);
+        }
+        let mut in_parts = Vec::new();
+        for scope in scopes {
+            let mut generated = Vec::new();
+            for (kind, index) in scope.kinds.iter().zip(&scope.indexes) {
+                // Kinds the part never uses would only add empty chapters
+                if index.entries().next().is_none() {
+                    continue;
+                }
+                let chapter = generator.index_chapter(kind, index)?;
+                let what = format!("`{}` index of the part `{}`", kind.name, scope.part);
+                check_collisions(&chapter, &what, chapters)?;
+                generated.push(chapter);
+            }
+            in_parts.push((scope.part.clone(), generated));
+        }
+        Ok(Generated {
+            book: generated,
+            in_parts,
+        })
+    }
+
+    /// Reports the dead links of `book`, failing on them if asked to.
+    fn validate_links(book: &Book, src_dir: &Path, config: &IndexerConfig) -> Result<(), Error> {
+        let dead = match config.dead_links {
+            DeadLinks::Ignore => Vec::new(),
+            DeadLinks::Warn | DeadLinks::Fail => deadlinks::find(book, src_dir),
+        };
+        if config.dead_links == DeadLinks::Fail && !dead.is_empty() {
+            let listed: Vec<String> = dead
+                .iter()
+                .map(|link| format!("{}: ({}) {}", link.page, link.link, link.reason))
+                .collect();
+            anyhow::bail!("dead links in the book:\n  {}", listed.join("\n  "));
+        }
+        for link in dead {
+            eprintln!(
+                "Warning: the link ({}) in {} is dead: {}",
+                link.link, link.page, link.reason
+            );
+        }
+        Ok(())
+    }
+
+    /// The title of the flashcards' deck: the book's, or `flashcards_title`.
+    fn deck<'a>(ctx: &'a PreprocessorContext, config: &'a IndexerConfig) -> &'a str {
+        let deck = ctx.config.book.title.as_deref();
+        deck.unwrap_or(&config.flashcards_title)
+    }
+
+    /// Leaves out of `book` the chapters `build` excludes, along with the
+    /// chapters nested below them, and reports the links left pointing at
+    /// them. A chapter's status is the earliest of `statuses` the kind
+    /// `tags` tags it with.
+    fn leave_out_chapters(
+        book: &mut Book,
+        scanner: &Scanner,
+        tags: Option<usize>,
+        config: &IndexerConfig,
+        build: &BuildProfile,
+    ) {
+        let mut excluded = HashSet::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let page = chapter_path(chapter);
+            if build.excludes_page(&page) {
+                excluded.insert(page);
+                continue;
+            }
+            let Some(tags) = tags
+                .filter(|_| !build.exclude_tags.is_empty() || !build.exclude_statuses.is_empty())
+            else {
+                continue;
+            };
+            let entity = &scanner.kinds[tags];
+            let content = &chapter.content;
+            let front = FrontMatter::parse(content);
+            let body = front.as_ref().map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let mut keys = front.map(|front| front.keys(entity)).unwrap_or_default();
+            keys.extend(
+                scanner
+                    .find_tokens(&content[body..end])
+                    .into_iter()
+                    .filter(|token| token.kind == tags && token.form != Form::Escaped)
+                    .map(|token| token.key),
+            );
+            let keys: Vec<String> = keys
+                .iter()
+                .map(|key| entity.canonical(key, &page).to_lowercase())
+                .collect();
+            let tagged = build.exclude_tags.iter().any(|tag| {
+                let tag = tag.to_lowercase();
+                keys.iter().any(|key| {
+                    key.strip_prefix(&tag)
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+                })
+            });
+            let status = config
+                .statuses
+                .iter()
+                .find(|status| keys.contains(&status.to_lowercase()));
+            let statused = status.is_some_and(|status| {
+                build
+                    .exclude_statuses
+                    .iter()
+                    .any(|excluded| excluded.eq_ignore_ascii_case(status))
+            });
+            if tagged || statused {
+                excluded.insert(page);
+            }
+        }
+        if excluded.is_empty() {
+            return;
+        }
+
+        let mut left_out = HashSet::new();
+        leave_out(&mut book.sections, &excluded, &mut left_out);
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let from = chapter_path(chapter);
+            for link in link_targets(&chapter.content) {
+                let target = resolve_link(Path::new(&from), &link);
+                if let Some(target) = target.filter(|target| left_out.contains(target)) {
+                    eprintln!("Warning: {from} links to {target}, which this build leaves out");
                 }
             }
+        }
+    }
+
+    /// Removes the chapters of `items` at one of `pages`, and those below
+    /// them, adding the paths of all of them to `left_out`.
+    fn leave_out(
+        items: &mut Vec<BookItem>,
+        pages: &HashSet<String>,
+        left_out: &mut HashSet<String>,
+    ) {
+        items.retain(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return true;
+            };
+            if !pages.contains(&chapter_path(chapter)) {
+                return true;
+            }
+            add_paths(chapter, left_out);
+            false
         });
+        for item in items {
+            if let BookItem::Chapter(chapter) = item {
+                leave_out(&mut chapter.sub_items, pages, left_out);
+            }
+        }
+    }
 
-        (mentions, tags)
+    /// Adds the paths of `chapter` and the chapters below it to `paths`.
+    fn add_paths(chapter: &Chapter, paths: &mut HashSet<String>) {
+        paths.insert(chapter_path(chapter));
+        for item in &chapter.sub_items {
+            if let BookItem::Chapter(below) = item {
+                add_paths(below, paths);
+            }
+        }
     }
 
-    fn process_chapter(
-        chapter: &mut Chapter,
-        mentions: &mut HashMap<String, Vec<String>>,
-        tags: &mut HashMap<String, Vec<String>>,
-    ) -> Option<String> {
-        let mut content = chapter.content.clone();
+    /// Writes a file for other tools, creating its directory if needed.
+    fn export(path: &Path, contents: &str) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents).with_context(|| format!("couldn't write {}", path.display()))
+    }
+
+    /// The chapters, in reading order, that no other chapter links to or
+    /// shares a key of `indexes` with. `links` holds the chapters linking to
+    /// each chapter, by path.
+    fn orphans(
+        indexes: &[Index],
+        chapters: &HashMap<String, ChapterInfo>,
+        links: &HashMap<String, Vec<String>>,
+    ) -> Vec<String> {
+        let mut connected: HashSet<&str> = links
+            .iter()
+            .filter(|(_, sources)| !sources.is_empty())
+            .map(|(target, _)| target.as_str())
+            .collect();
+        for entry in indexes.iter().flat_map(|index| index.entries()) {
+            let pages: Vec<&str> = entry
+                .pages()
+                .map(|occurrences| occurrences[0].page.as_str())
+                .collect();
+            if pages.len() > 1 {
+                connected.extend(pages);
+            }
+        }
+
+        let mut orphans: Vec<(&String, &ChapterInfo)> = chapters
+            .iter()
+            .filter(|(page, _)| !page.is_empty() && !connected.contains(page.as_str()))
+            .collect();
+        orphans.sort_by_key(|(_, chapter)| chapter.position);
+        orphans.into_iter().map(|(page, _)| page.clone()).collect()
+    }
+
+    fn remove_generated(items: &mut Vec<BookItem>) {
+        items.retain(|item| !matches!(item, BookItem::Chapter(chapter) if is_generated(chapter)));
+        for item in items {
+            if let BookItem::Chapter(chapter) = item {
+                remove_generated(&mut chapter.sub_items);
+            }
+        }
+    }
+
+    /// Notes the stubs generated by an earlier run, so that links to them
+    /// bring them back.
+    fn earlier_stubs(items: &[BookItem], stubs: &mut BTreeMap<String, Stub>) {
+        for item in items {
+            if let BookItem::Chapter(chapter) = item {
+                if is_stub(chapter) {
+                    let stub = Stub {
+                        title: chapter.name.clone(),
+                        sources: Vec::new(),
+                    };
+                    stubs.insert(chapter_path(chapter), stub);
+                }
+                earlier_stubs(&chapter.sub_items, stubs);
+            }
+        }
+    }
+
+    /// What the first pass found in a chapter.
+    #[derive(Default)]
+    struct ScannedChapter {
+        /// Offset of the body, after any front matter.
+        body: usize,
+        /// Tokens of the body, with ranges relative to it.
+        tokens: Vec<Token>,
+        /// Id placed at each token with `deep_links` on, empty otherwise.
+        anchors: Vec<String>,
+    }
+
+    /// Indexes limited to the chapters of one part, generated inside it.
+    struct PartScope {
+        part: String,
+        /// The kinds of the book, with files under a directory for the part.
+        kinds: Vec<EntityKind>,
+        indexes: Vec<Index>,
+    }
+
+    /// Fails if `generated`, or one of its sub-chapters, would take the path of
+    /// a chapter of the book. `what` names the generated chapter in the error.
+    fn check_collisions(
+        generated: &Chapter,
+        what: &str,
+        chapters: &HashMap<String, ChapterInfo>,
+    ) -> Result<(), Error> {
+        let path = chapter_path(generated);
+        if chapters.contains_key(&path) {
+            anyhow::bail!(
+                "the {} would overwrite the chapter `{}`; \
+                 choose another file for it in [preprocessor.indexer]",
+                what,
+                path
+            );
+        }
+        for item in &generated.sub_items {
+            if let BookItem::Chapter(chapter) = item {
+                check_collisions(chapter, what, chapters)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Indexes every chapter of `book` and links its tokens, returning the
+    /// book-wide indexes, those of each part with `part_indexes` on, and the
+    /// chapters linking to each chapter if backlinks or the graph need them.
+    /// Query blocks are answered along the way, by `front_matters` among
+    /// the rest, so that the chapters they list count as linked to.
+    fn collect_entities(
+        book: &mut Book,
+        scanner: &Scanner,
+        generator: &Generator,
+        stubs: &mut Stubs,
+        similar: &HashMap<String, Vec<(String, f64)>>,
+        front_matters: &HashMap<String, FrontMatter>,
+        notes: &[(String, String)],
+    ) -> (Vec<Index>, Vec<PartScope>, HashMap<String, Vec<String>>) {
+        let config = generator.config;
+        let mut indexes = vec![Index::new(config.case); scanner.kinds.len()];
+        let mut chapter_tokens = Vec::new();
+        let mut backlinks: HashMap<String, Vec<String>> = HashMap::new();
+        let sources = if config.embeds {
+            chapter_sources(book)
+        } else {
+            HashMap::new()
+        };
+        let embedder = Embedder {
+            sources: &sources,
+            chapters: generator.chapters,
+            style: config.embed_style,
+            unresolved: config.unresolved_wikilinks,
+        };
 
-        // Process tags
-        for tag in extract_prefix_items(&content, '#') {
-            let tag_link = format!("[#{}](tags.md#{})", tag, tag);
-            content = content.replace(&format!("#{}", tag), &tag_link);
+        // Index everything first so rewriting can depend on the final entries
+        book.for_each_mut(|item| {
+            if let BookItem::Chapter(chapter) = item {
+                // Lists appended by an earlier run are rebuilt, not scanned
+                if let Some(footer) = footer_start(&chapter.content) {
+                    chapter.content.truncate(footer);
+                    chapter.content.truncate(chapter.content.trim_end().len());
+                    chapter.content.push('\n');
+                }
+                let front_matter = FrontMatter::parse(&chapter.content);
+                let body = front_matter.as_ref().map_or(0, |front| front.len);
+                if config.embeds {
+                    let expanded =
+                        embedder.expand(&chapter.content[body..], &chapter_path(chapter));
+                    chapter.content.replace_range(body.., &expanded);
+                }
+                if config.wikilinks {
+                    let rewritten = wikilinks::rewrite(
+                        &chapter.content[body..],
+                        &chapter_path(chapter),
+                        generator.chapters,
+                        generator.siblings,
+                        config.unresolved_wikilinks,
+                        stubs,
+                    );
+                    chapter.content.replace_range(body.., &rewritten);
+                }
+                if !stubs.pages.is_empty() {
+                    record_stub_links(chapter, generator.chapters, stubs);
+                }
+                if config.backlinks
+                    || config.reports_orphans()
+                    || (config.builds_graph() && config.graph_links)
+                {
+                    record_backlinks(chapter, generator.chapters, &mut backlinks);
+                    record_journal_links(chapter, notes, &mut backlinks);
+                }
+                let tokens = scanner.find_tokens(&chapter.content[body..]);
+                let anchors = occurrence_anchors(scanner, &tokens, config);
+                let scanned = ScannedChapter {
+                    body,
+                    tokens,
+                    anchors,
+                };
+                index_tokens(chapter, scanner, &scanned, &mut indexes, config);
+                if let Some(front_matter) = &front_matter {
+                    index_front_matter(chapter, scanner, front_matter, &mut indexes);
+                }
+                chapter_tokens.push(scanned);
+            }
+        });
 
-            let chapter_path = chapter_path(chapter);
-            tags.entry(tag).or_default().push(chapter_path);
+        let related = match (
+            config.related,
+            scanner.kinds.iter().position(|k| k.name == "tags"),
+        ) {
+            (Some(limit), _) if config.related_by == RelatedBy::Text => similar
+                .iter()
+                .map(|(page, closest)| {
+                    let pages = closest.iter().take(limit).map(|(other, _)| other.clone());
+                    (page.clone(), pages.collect())
+                })
+                .collect(),
+            (Some(limit), Some(tags)) => {
+                // Entries are only listed once sorted
+                indexes[tags].sort();
+                related_pages(&indexes[tags], generator.chapters, limit)
+            }
+            _ => HashMap::new(),
+        };
+        let mut scopes = part_scopes(scanner.kinds, &indexes, generator.chapters, config);
+        let part_of = |page: &str| {
+            generator
+                .chapters
+                .get(page)
+                .and_then(|chapter| chapter.part.as_deref())
+        };
+        if config.part_indexes == PartIndexes::Only {
+            // Chapters in a part are only indexed by their part
+            for index in &mut indexes {
+                index.retain_pages(|page| part_of(page).is_none());
+            }
+        }
+        for (kind, index) in scanner.kinds.iter().zip(&mut indexes) {
+            prepare(generator, kind, index);
+        }
+        for scope in &mut scopes {
+            for (kind, index) in scope.kinds.iter().zip(&mut scope.indexes) {
+                prepare(generator, kind, index);
+            }
         }
 
-        // Process mentions
-        for mention in extract_prefix_items(&content, '@') {
-            let mention_link = format!("[@{}](mentions.md#{})", mention, mention);
-            content = content.replace(&format!("@{}", mention), &mention_link);
+        let terms = |indexes: &[Index]| match config.unlinked_mentions {
+            UnlinkedMentions::Ignore => Vec::new(),
+            UnlinkedMentions::Warn | UnlinkedMentions::Link => {
+                unlinked::terms(generator.chapters, scanner.kinds, indexes)
+            }
+        };
+        let book_terms = terms(&indexes);
+        let scope_terms: Vec<Vec<Term>> =
+            scopes.iter().map(|scope| terms(&scope.indexes)).collect();
+        let prefixes: Vec<char> = scanner.kinds.iter().map(|kind| kind.prefix).collect();
 
-            let chapter_path = chapter_path(chapter);
-            mentions.entry(mention).or_default().push(chapter_path);
+        // The chapters each query block lists, or none if it is invalid
+        let mut answers: Vec<Vec<Option<Vec<String>>>> = Vec::new();
+        if config.queries {
+            let chapters = generator.chapters;
+            let matches = |page: &str, condition: &Condition| {
+                let kinds = scanner.kinds;
+                meets_condition(
+                    page,
+                    condition,
+                    kinds,
+                    &indexes,
+                    front_matters,
+                    chapters,
+                    config,
+                )
+            };
+            book.for_each_mut(|item| {
+                let BookItem::Chapter(chapter) = item else {
+                    return;
+                };
+                let page = chapter_path(chapter);
+                let mut answered = Vec::new();
+                for (_, text) in query::blocks(&chapter.content) {
+                    let query = match query::parse(&text) {
+                        Ok(query) => query,
+                        Err(error) => {
+                            let text = text.trim();
+                            eprintln!("Warning: the query `{text}` in {page} is invalid: {error}");
+                            answered.push(None);
+                            continue;
+                        }
+                    };
+                    let matching: Vec<String> = chapters
+                        .keys()
+                        .filter(|other| !other.is_empty() && **other != page)
+                        .filter(|other| query.matches(&|condition| matches(other, condition)))
+                        .cloned()
+                        .collect();
+                    if config.backlinks
+                        || config.reports_orphans()
+                        || (config.builds_graph() && config.graph_links)
+                    {
+                        for target in &matching {
+                            let sources = backlinks.entry(target.clone()).or_default();
+                            if !sources.contains(&page) {
+                                sources.push(page.clone());
+                            }
+                        }
+                    }
+                    answered.push(Some(matching));
+                }
+                answers.push(answered);
+            });
         }
 
-        Some(content)
+        // Same traversal order as above
+        let mut chapter_tokens = chapter_tokens.into_iter();
+        let mut answers = answers.into_iter();
+        book.for_each_mut(|item| {
+            if let BookItem::Chapter(chapter) = item {
+                let scanned = chapter_tokens.next().unwrap_or_default();
+                let scope = part_of(&chapter_path(chapter))
+                    .filter(|_| config.part_indexes == PartIndexes::Only)
+                    .and_then(|part| scopes.iter().position(|scope| scope.part == part));
+                let indexes = scope.map_or(&indexes, |i| &scopes[i].indexes);
+                chapter.content = rewrite_chapter(chapter, scanner, &scanned, indexes, config);
+                if config.unlinked_mentions != UnlinkedMentions::Ignore {
+                    let terms = scope.map_or(&book_terms, |i| &scope_terms[i]);
+                    link_mentions(chapter, &scanned, terms, &prefixes, config);
+                }
+                if let Some(answered) = answers.next() {
+                    let page = chapter_path(chapter);
+                    let blocks = query::blocks(&chapter.content);
+                    for ((range, _), matching) in blocks.into_iter().zip(answered).rev() {
+                        let Some(matching) = matching else {
+                            continue;
+                        };
+                        let list = generator.query_results(&page, &matching);
+                        query::answer(&mut chapter.content, range, &list);
+                    }
+                }
+                if let Some(sources) = backlinks
+                    .get(&chapter_path(chapter))
+                    .filter(|_| config.backlinks)
+                {
+                    chapter
+                        .content
+                        .push_str(&generator.backlinks(chapter, sources));
+                }
+                if let Some(pages) = related.get(&chapter_path(chapter)) {
+                    chapter.content.push_str(&generator.related(chapter, pages));
+                }
+            }
+        });
+
+        (indexes, scopes, backlinks)
+    }
+
+    /// Reports the unlinked mentions of `terms` in `chapter`, once rewritten,
+    /// or links them with `unlinked_mentions = "link"`.
+    fn link_mentions(
+        chapter: &mut Chapter,
+        scanned: &ScannedChapter,
+        terms: &[Term],
+        prefixes: &[char],
+        config: &IndexerConfig,
+    ) {
+        // The front matter, kept or not, is left as it was
+        let body = match config.front_matter {
+            FrontMatterMode::Keep => scanned.body,
+            FrontMatterMode::Strip => 0,
+        };
+        let page = chapter_path(chapter);
+        let found = unlinked::find(&chapter.content[body..], &page, terms, prefixes);
+        match config.unlinked_mentions {
+            UnlinkedMentions::Ignore => {}
+            UnlinkedMentions::Warn => {
+                for unlinked in found {
+                    eprintln!(
+                        "Warning: {} mentions \"{}\" without linking to it",
+                        page,
+                        &chapter.content[body..][unlinked.range]
+                    );
+                }
+            }
+            UnlinkedMentions::Link => {
+                let linked = unlinked::link(&chapter.content[body..], &page, &found, terms);
+                chapter.content.replace_range(body.., &linked);
+            }
+        }
     }
 
-    fn extract_prefix_items(text: &str, prefix: char) -> Vec<String> {
-        text.split(|c: char| c.is_whitespace() || (c != prefix && c.is_ascii_punctuation()))
-            .filter_map(|word| {
-                word.strip_prefix(prefix)
-                    .filter(|&trimmed| !trimmed.is_empty())
-                    .map(String::from)
+    /// The body of every chapter, without front matter or backlinks, by path.
+    fn chapter_sources(book: &Book) -> HashMap<String, String> {
+        book.iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) => Some(chapter),
+                _ => None,
+            })
+            .map(|chapter| {
+                let content = &chapter.content;
+                let content = &content[..footer_start(content).unwrap_or(content.len())];
+                let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+                (chapter_path(chapter), content[body..].to_string())
             })
             .collect()
     }
 
-    fn generate_index(title: &str, prefix: &str, index: &HashMap<String, Vec<String>>) -> String {
-        index
+    /// Takes the line holding `marker`, which an earlier run put below the
+    /// title of a chapter, out of its `content`, along with the blank line
+    /// after it if `spaced`.
+    fn remove_below_title(content: &mut String, marker: &str, spaced: bool) {
+        let Some(found) = content.find(marker) else {
+            return;
+        };
+        let start = content[..found].rfind('\n').map_or(0, |i| i + 1);
+        let mut end = content[start..]
+            .find('\n')
+            .map_or(content.len(), |i| start + i + 1);
+        if spaced && content[end..].starts_with('\n') {
+            end += 1;
+        }
+        content.replace_range(start..end, "");
+    }
+
+    /// Puts `line` below the front matter of `chapter` and its title if it
+    /// opens with one.
+    fn show_below_title(chapter: &mut Chapter, line: &str) {
+        let content = &mut chapter.content;
+        let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+        let blank = content[body..].len() - content[body..].trim_start().len();
+        let mut at = body + blank;
+        if content[at..].starts_with("# ") {
+            at = content[at..]
+                .find('\n')
+                .map_or(content.len(), |i| at + i + 1);
+            if at == content.len() && !content.ends_with('\n') {
+                content.push('\n');
+                at = content.len();
+            }
+        }
+        content.insert_str(at, line);
+    }
+
+    /// The columns of the board, one for each of `statuses`: the chapters
+    /// `index`, of the kind at `kind`, has tagged with it more often than
+    /// their tasks are, and the tasks tagged with it.
+    fn board(
+        scanner: &Scanner,
+        kind: usize,
+        index: &Index,
+        tasks: &BTreeMap<String, Vec<Task>>,
+        statuses: &[String],
+    ) -> Vec<Column> {
+        statuses
             .iter()
-            .map(|(key, pages)| {
-                let entries = pages
-                    .iter()
-                    .map(|page| format!("- [{}]({})", page, page))
-                    .collect::<Vec<_>>()
-                    .join("\n");
-                format!("## {}{}\n{}\n", prefix, key, entries)
-            })
-            .fold(format!("# {}\n\n", title), |mut md, section| {
-                md.push_str(&section);
-                md
+            .map(|status| {
+                let mut tagged = Vec::new();
+                let mut in_tasks: HashMap<&str, usize> = HashMap::new();
+                for (page, tasks) in tasks {
+                    for task in tasks {
+                        let count = scanner
+                            .find_tokens(&task.text)
+                            .iter()
+                            .filter(|token| token.kind == kind && token.form != Form::Escaped)
+                            .filter(|token| {
+                                let key = scanner.kinds[kind].canonical(&token.key, page);
+                                key.to_lowercase() == status.to_lowercase()
+                            })
+                            .count();
+                        if count > 0 {
+                            *in_tasks.entry(page).or_default() += count;
+                            tagged.push((page.clone(), task.clone()));
+                        }
+                    }
+                }
+                let pages = index
+                    .get(status)
+                    .into_iter()
+                    .flat_map(|entry| entry.pages())
+                    .filter(|occurrences| {
+                        let page = occurrences[0].page.as_str();
+                        occurrences.len() > in_tasks.get(page).copied().unwrap_or_default()
+                    })
+                    .map(|occurrences| occurrences[0].page.clone())
+                    .collect();
+                Column {
+                    status: status.clone(),
+                    pages,
+                    tasks: tagged,
+                }
             })
+            .collect()
     }
 
-    fn add_index_chapter(
-        book: &mut Book,
-        path: &str,
-        title: &str,
-        prefix: &str,
-        index: &HashMap<String, Vec<String>>,
-    ) {
-        let content = generate_index(title, prefix, index);
-        book.sections.push(BookItem::Chapter(Chapter::new(
-            path,
-            content,
-            PathBuf::from(path),
-            Vec::new(),
-        )));
+    /// What is due in the chapters of `book`: the tasks of `tasks` whose
+    /// text gives a date after `marker`, and the chapters giving one outside
+    /// their tasks or in their front matter.
+    fn due_dates(book: &Book, tasks: &BTreeMap<String, Vec<Task>>, marker: &str) -> Vec<Due> {
+        let mut due = Vec::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let content = &content[..footer_start(content).unwrap_or(content.len())];
+            let front = FrontMatter::parse(content);
+            let body = &content[front.as_ref().map_or(0, |front| front.len)..];
+
+            let mut in_tasks = Vec::new();
+            for task in tasks.get(&page).into_iter().flatten() {
+                for date in dates::marked(&task.text, marker) {
+                    in_tasks.push(date.clone());
+                    due.push(Due {
+                        date,
+                        page: page.clone(),
+                        task: Some(task.clone()),
+                    });
+                }
+            }
+            let mut dates: Vec<String> = front
+                .and_then(|front| front.date("due"))
+                .into_iter()
+                .collect();
+            for date in dates::marked(body, marker) {
+                match in_tasks.iter().position(|other| *other == date) {
+                    Some(i) => {
+                        in_tasks.swap_remove(i);
+                    }
+                    None => dates.push(date),
+                }
+            }
+            dates.sort();
+            dates.dedup();
+            for date in dates {
+                due.push(Due {
+                    date,
+                    page: page.clone(),
+                    task: None,
+                });
+            }
+        }
+        due
+    }
+
+    /// The dates of every chapter: that of its front matter, then those
+    /// written in it.
+    fn written_dates(book: &Book) -> Vec<Dated> {
+        let mut dated = Vec::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let content = &content[..footer_start(content).unwrap_or(content.len())];
+            let front = FrontMatter::parse(content);
+            let body = &content[front.as_ref().map_or(0, |front| front.len)..];
+            if let Some(date) = front.and_then(|front| front.date("date")) {
+                dated.push(Dated {
+                    date,
+                    page: page.clone(),
+                    anchor: String::new(),
+                    snippet: String::new(),
+                });
+            }
+            dated.extend(dates::written(body, &page));
+        }
+        dated
+    }
+
+    /// The daily notes of `dir`, the chapters below it named after a day,
+    /// as their day and path, the earliest first.
+    fn daily_notes(chapters: &HashMap<String, ChapterInfo>, dir: &str) -> Vec<(String, String)> {
+        let dir = Path::new(dir.trim_matches('/'));
+        let mut notes: Vec<(String, String)> = chapters
+            .keys()
+            .filter(|page| Path::new(page).starts_with(dir))
+            .filter_map(|page| {
+                let day = Path::new(page).file_stem()?.to_str()?;
+                dates::is_iso_date(day).then(|| (day.to_string(), page.clone()))
+            })
+            .collect();
+        notes.sort();
+        notes
+    }
+
+    /// What the chapters of `book` tag with `tag`, of the kind at `kind`:
+    /// each chapter tagged in its front matter, and what is on the line of
+    /// every tag in its text.
+    fn reading_list(book: &Book, scanner: &Scanner, kind: usize, tag: &str) -> Vec<Reading> {
+        let entity = &scanner.kinds[kind];
+        let mut readings = Vec::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let content = &content[..footer_start(content).unwrap_or(content.len())];
+            let front = FrontMatter::parse(content);
+            let body = &content[front.as_ref().map_or(0, |front| front.len)..];
+            let is_tag =
+                |key: &str| entity.canonical(key, &page).to_lowercase() == tag.to_lowercase();
+
+            let tagged =
+                front.is_some_and(|front| front.keys(entity).iter().any(|key| is_tag(key)));
+            if tagged {
+                readings.push(Reading {
+                    page: page.clone(),
+                    title: String::new(),
+                    link: None,
+                });
+            }
+            let ranges: Vec<_> = scanner
+                .find_tokens(body)
+                .into_iter()
+                .filter(|token| token.kind == kind && token.form != Form::Escaped)
+                .filter(|token| is_tag(&token.key))
+                .map(|token| token.range)
+                .collect();
+            for range in &ranges {
+                let reading = reading::read(body, &page, range.clone(), &ranges);
+                if !readings.contains(&reading) {
+                    readings.push(reading);
+                }
+            }
+        }
+        readings
+    }
+
+    /// The blockquotes of the chapters of `book`, or with `tag`, a kind's
+    /// position and a key, only those using the key, which is left out.
+    fn collect_quotes(book: &Book, scanner: &Scanner, tag: Option<(usize, &str)>) -> Vec<Quote> {
+        let mut quotes = Vec::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let content = &content[..footer_start(content).unwrap_or(content.len())];
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let body = &content[body..];
+            let tagged: Vec<_> = match tag {
+                Some((kind, key)) => scanner
+                    .find_tokens(body)
+                    .into_iter()
+                    .filter(|token| token.kind == kind && token.form != Form::Escaped)
+                    .filter(|token| {
+                        let canonical = scanner.kinds[kind].canonical(&token.key, &page);
+                        canonical.to_lowercase() == key.to_lowercase()
+                    })
+                    .map(|token| token.range)
+                    .collect(),
+                None => Vec::new(),
+            };
+            for range in quotes::blockquotes(body) {
+                let has_tag = tagged
+                    .iter()
+                    .any(|tag| tag.start >= range.start && tag.end <= range.end);
+                if tag.is_none() || has_tag {
+                    quotes.push(quotes::quote(body, &page, range, &tagged));
+                }
+            }
+        }
+        quotes
+    }
+
+    /// The images of the chapters of `book`, warning about those shown with
+    /// different alt texts.
+    fn collect_images(book: &Book) -> Vec<Image> {
+        let mut images = Vec::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let content = &chapter.content;
+            let content = &content[..footer_start(content).unwrap_or(content.len())];
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            images.extend(images::images(&content[body..], &chapter_path(chapter)));
+        }
+        let mut alts: BTreeMap<&str, Vec<&Image>> = BTreeMap::new();
+        for image in &images {
+            alts.entry(&image.src).or_default().push(image);
+        }
+        for (src, shown) in alts {
+            let mut different: Vec<&str> = shown.iter().map(|image| image.alt.as_str()).collect();
+            different.sort();
+            different.dedup();
+            if different.len() > 1 {
+                let pages: BTreeSet<&str> = shown.iter().map(|image| image.page.as_str()).collect();
+                let quoted: Vec<String> =
+                    different.iter().map(|alt| format!("\"{alt}\"")).collect();
+                eprintln!(
+                    "Warning: {src} has different alt texts in {}: {}",
+                    pages.into_iter().collect::<Vec<_>>().join(", "),
+                    quoted.join(", ")
+                );
+            }
+        }
+        images
+    }
+
+    /// Numbers the tables of `book` in reading order, giving each an anchor
+    /// and writing its caption as `Table 1: ...` with `label` for `Table`.
+    fn number_tables(book: &mut Book, label: &str) -> Vec<Table> {
+        let mut tables = Vec::new();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let end = footer_start(content).unwrap_or(content.len());
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let source = &content[body..end];
+            let mut edits = Vec::new();
+            for found in tables::tables(source, label) {
+                let number = tables.len() + 1;
+                let anchor = format!("<a id=\"{}\"></a>", tables::anchor(number));
+                match (found.paragraph, &found.caption) {
+                    (Some(paragraph), Some(caption)) => {
+                        edits.push((paragraph, format!("{anchor}{label} {number}: {caption}")))
+                    }
+                    (Some(paragraph), None) => edits.push((paragraph, anchor)),
+                    (None, _) => {
+                        let at = found.range.start;
+                        let anchor = tables::anchor_before(source, &found.range, &anchor);
+                        edits.push((at..at, anchor));
+                    }
+                }
+                tables.push(Table {
+                    page: page.clone(),
+                    number,
+                    caption: found.caption,
+                });
+            }
+            edits.sort_by_key(|(range, _)| range.start);
+            let mut numbered = content[..body].to_string();
+            let mut last = 0;
+            for (range, text) in edits {
+                numbered.push_str(&source[last..range.start]);
+                numbered.push_str(&text);
+                last = range.end;
+            }
+            numbered.push_str(&content[body + last..]);
+            chapter.content = numbered;
+        });
+        tables
+    }
+
+    /// The callouts of the chapters of `book`.
+    fn collect_callouts(book: &Book) -> Vec<Callout> {
+        let mut callouts = Vec::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let content = &content[..footer_start(content).unwrap_or(content.len())];
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let body = &content[body..];
+            for (kind, range) in quotes::callouts(body) {
+                callouts.push(quotes::callout(body, &page, kind, range));
+            }
+        }
+        callouts
+    }
+
+    /// The footnotes of the chapters of `book`, in reading order.
+    fn endnotes(book: &Book) -> Vec<Note> {
+        let chapters = book.iter().filter_map(|item| match item {
+            BookItem::Chapter(chapter) if chapter.path.is_some() => {
+                let content = &chapter.content;
+                let content = &content[..footer_start(content).unwrap_or(content.len())];
+                Some((chapter_path(chapter), content))
+            }
+            _ => None,
+        });
+        footnotes::collect(chapters)
+    }
+
+    /// Turns the citations of every chapter of `book` into links to the
+    /// `references` they cite, warning about keys the bibliography lacks.
+    /// Returns the chapters citing each key, in reading order.
+    fn cite(
+        book: &mut Book,
+        references: &BTreeMap<String, Reference>,
+        config: &IndexerConfig,
+    ) -> BTreeMap<String, Vec<String>> {
+        let mut cited: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            if page.is_empty() {
+                return;
+            }
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let (rewritten, keys, missing) = citations::cite(
+                &content[body..end],
+                &page,
+                references,
+                &config.references_file,
+                &config.labels,
+            );
+            for key in missing {
+                eprintln!("Warning: {page} cites @{key}, which the bibliography lacks");
+            }
+            for key in keys {
+                let pages = cited.entry(key).or_default();
+                if !pages.contains(&page) {
+                    pages.push(page.clone());
+                }
+            }
+            chapter.content.replace_range(body..end, &rewritten);
+        });
+        cited
+    }
+
+    /// Links the tokens made only of digits with the prefix of `tags`, like
+    /// `#123`, to the issue or pull request of `repository` with that number.
+    fn link_issues(book: &mut Book, tags: &EntityKind, escape: char, repository: &str) {
+        let issues = [EntityKind {
+            numeric: NumericKeys::Only,
+            ..tags.clone()
+        }];
+        let scanner = Scanner {
+            kinds: &issues,
+            escape,
+            mention_links: None,
+        };
+        let repository = repository.trim_end_matches('/');
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let source = &content[body..end];
+            let mut linked = String::with_capacity(source.len());
+            let mut last = 0;
+            for token in scanner.find_tokens(source) {
+                if token.form != Form::Bare {
+                    continue;
+                }
+                linked.push_str(&source[last..token.range.start]);
+                linked.push_str(&format!(
+                    "[{}{}]({repository}/issues/{})",
+                    tags.prefix, token.key, token.key
+                ));
+                last = token.range.end;
+            }
+            linked.push_str(&source[last..]);
+            chapter.content.replace_range(body..end, &linked);
+        });
+    }
+
+    /// Links the commit hashes `pattern` finds in every chapter of `book` to
+    /// `repository`. Returns the chapters naming each commit, linked by an
+    /// earlier run or not, a hash and the longer ones it abbreviates
+    /// counting as one.
+    fn link_commits(
+        book: &mut Book,
+        pattern: &Regex,
+        prefixes: &[char],
+        repository: &str,
+    ) -> BTreeMap<String, Vec<String>> {
+        let mut commits: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let url = |hash: &str| format!("{repository}/commit/{hash}");
+            let found = autolinks::find(&content[body..end], pattern, prefixes);
+            let earlier = autolinks::linked(&content[body..end], url);
+            if found.is_empty() && earlier.is_empty() {
+                return;
+            }
+            let hashes = found.iter().map(|(_, hash)| hash).chain(&earlier);
+            for hash in hashes {
+                let hash = hash.to_lowercase();
+                let same = commits
+                    .keys()
+                    .find(|known| known.starts_with(&hash) || hash.starts_with(known.as_str()))
+                    .cloned();
+                let (longest, mut pages) = match same {
+                    Some(known) => {
+                        let pages = commits.remove(&known).unwrap_or_default();
+                        (
+                            if known.len() > hash.len() {
+                                known
+                            } else {
+                                hash
+                            },
+                            pages,
+                        )
+                    }
+                    None => (hash, Vec::new()),
+                };
+                if !pages.contains(&page) {
+                    pages.push(page.clone());
+                }
+                commits.insert(longest, pages);
+            }
+            let linked = autolinks::link(&content[body..end], &found, url);
+            chapter.content.replace_range(body..end, &linked);
+        });
+        commits
+    }
+
+    /// Links the ticket keys `pattern` finds in every chapter of `book` to
+    /// the tracker. Returns the chapters discussing each ticket, linked by
+    /// an earlier run or not.
+    fn link_tickets(
+        book: &mut Book,
+        pattern: &Regex,
+        prefixes: &[char],
+        config: &IndexerConfig,
+    ) -> BTreeMap<String, Vec<String>> {
+        let mut tickets: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let url = |key: &str| config.ticket_url(key).unwrap_or_default();
+            let found = autolinks::find(&content[body..end], pattern, prefixes);
+            let earlier = autolinks::linked(&content[body..end], url);
+            if found.is_empty() && earlier.is_empty() {
+                return;
+            }
+            for key in found.iter().map(|(_, key)| key).chain(&earlier) {
+                let pages = tickets.entry(key.clone()).or_default();
+                if !pages.contains(&page) {
+                    pages.push(page.clone());
+                }
+            }
+            let linked = autolinks::link(&content[body..end], &found, url);
+            chapter.content.replace_range(body..end, &linked);
+        });
+        tickets
+    }
+
+    /// The events the chapters of `book` describe, in reading order, their
+    /// markers shown as their name and date.
+    fn collect_events(book: &mut Book) -> Vec<Event> {
+        let mut found = Vec::new();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let (rewritten, events) = events::rewrite(&content[body..end], &page);
+            if !events.is_empty() {
+                chapter.content.replace_range(body..end, &rewritten);
+                found.extend(events);
+            }
+        });
+        found
+    }
+
+    /// The flashcards of the chapters of `book`, in reading order, with the
+    /// list items `tag` marks as cards left with a marker in its place.
+    fn collect_flashcards(book: &mut Book, tag: &str) -> Vec<Card> {
+        let mut found = Vec::new();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let (rewritten, cards) = flashcards::rewrite(&content[body..end], &page, tag);
+            if rewritten != content[body..end] {
+                chapter.content.replace_range(body..end, &rewritten);
+            }
+            found.extend(cards);
+        });
+        found
+    }
+
+    /// The places the chapters of `book` are set in, by name in lowercase:
+    /// those the tags below `location_tag` of the kind `tags` name, and the
+    /// coordinates written out, which are linked to the map, or were by an
+    /// earlier run.
+    fn collect_locations(
+        book: &mut Book,
+        scanner: &Scanner,
+        tags: Option<usize>,
+        config: &IndexerConfig,
+    ) -> BTreeMap<String, Location> {
+        let root = format!("{}/", config.location_tag.to_lowercase());
+        let places: HashMap<String, Point> = config
+            .places
+            .iter()
+            .map(|(name, &(lat, lon))| (name.to_lowercase(), Point { lat, lon }))
+            .collect();
+        let mut locations: BTreeMap<String, Location> = BTreeMap::new();
+        let mut add = |name: &str, point: Option<Point>, page: &String| {
+            let location = locations
+                .entry(name.to_lowercase())
+                .or_insert_with(|| Location {
+                    name: name.to_string(),
+                    point: point.or_else(|| places.get(&name.to_lowercase()).copied()),
+                    pages: Vec::new(),
+                });
+            if !location.pages.contains(page) {
+                location.pages.push(page.clone());
+            }
+        };
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let front = FrontMatter::parse(content);
+            let body = front.as_ref().map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let source = &content[body..end];
+            if let Some(kind) = tags {
+                let entity = &scanner.kinds[kind];
+                let place = |key: &str| {
+                    let canonical = entity.canonical(key, &page);
+                    let head = canonical.get(..root.len())?;
+                    (head.to_lowercase() == root).then(|| canonical[root.len()..].to_string())
+                };
+                let mut named: Vec<String> = front
+                    .map(|front| front.keys(entity))
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|key| place(key))
+                    .collect();
+                named.extend(
+                    scanner
+                        .find_tokens(source)
+                        .into_iter()
+                        .filter(|token| token.kind == kind && token.form != Form::Escaped)
+                        .filter_map(|token| place(&token.key)),
+                );
+                for name in named.iter().filter(|name| !name.is_empty()) {
+                    add(name, None, &page);
+                }
+            }
+            let found = locations::find(source);
+            let earlier = locations::linked(source);
+            for point in found.iter().map(|(_, point)| point).chain(&earlier) {
+                add(&point.label(), Some(*point), &page);
+            }
+            if !found.is_empty() {
+                let linked = locations::link(source, &found);
+                chapter.content.replace_range(body..end, &linked);
+            }
+        });
+        locations
+    }
+
+    /// Links the pages of `interwiki` sources, which `pattern` matches, to
+    /// where they are, and returns them by source and page, each with where
+    /// it is and the chapters linking to it.
+    fn link_interwiki(
+        book: &mut Book,
+        pattern: &Regex,
+        prefixes: &[char],
+        config: &IndexerConfig,
+    ) -> BTreeMap<String, BTreeMap<String, (String, Vec<String>)>> {
+        let mut sources: BTreeMap<String, BTreeMap<String, (String, Vec<String>)>> =
+            BTreeMap::new();
+        let url = |page: &interwiki::Interwiki| {
+            config
+                .interwiki_url(&page.source, &page.key)
+                .unwrap_or_default()
+        };
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let found = interwiki::find(&content[body..end], pattern, prefixes);
+            if found.is_empty() {
+                return;
+            }
+            for linked in &found {
+                let (_, pages) = sources
+                    .entry(linked.source.clone())
+                    .or_default()
+                    .entry(linked.key.clone())
+                    .or_insert_with(|| (url(linked), Vec::new()));
+                if !pages.contains(&page) {
+                    pages.push(page.clone());
+                }
+            }
+            let linked = interwiki::link(&content[body..end], &found, url);
+            chapter.content.replace_range(body..end, &linked);
+        });
+        sources
+    }
+
+    /// The email addresses every chapter of `book` writes out or links to,
+    /// in lowercase, with the chapters referencing each, those an earlier
+    /// run obfuscated included. With `obfuscate` on, those written out are
+    /// spelled so that harvesters don't read them.
+    fn collect_emails(
+        book: &mut Book,
+        prefixes: &[char],
+        obfuscate: bool,
+        html: bool,
+    ) -> BTreeMap<String, Vec<String>> {
+        let mut contacts: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let source = &content[body..end];
+            let found = emails::find(source, prefixes);
+            let linked = emails::mailto(source);
+            let earlier = emails::obfuscated(source);
+            let addresses = found.iter().chain(&earlier).map(|(_, address)| address);
+            for address in addresses.chain(&linked) {
+                let pages = contacts.entry(address.to_lowercase()).or_default();
+                if !pages.contains(&page) {
+                    pages.push(page.clone());
+                }
+            }
+            if obfuscate && !found.is_empty() {
+                let mut spelled = String::with_capacity(source.len());
+                let mut last = 0;
+                for (range, address) in &found {
+                    spelled.push_str(&source[last..range.start]);
+                    spelled.push_str(&emails::obfuscate(address, html));
+                    last = range.end;
+                }
+                spelled.push_str(&source[last..]);
+                chapter.content.replace_range(body..end, &spelled);
+            }
+        });
+        contacts
+    }
+
+    /// The DOIs and arXiv identifiers of every chapter of `book`, with their
+    /// resolvers' URLs and the chapters giving them, linked by an earlier
+    /// run or not. With `link` on, they are linked to those URLs.
+    fn link_identifiers(book: &mut Book, link: bool) -> BTreeMap<String, (String, Vec<String>)> {
+        let mut sources: BTreeMap<String, (String, Vec<String>)> = BTreeMap::new();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            if page.is_empty() {
+                return;
+            }
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let found = identifiers::find(&content[body..end]);
+            let earlier = identifiers::linked(&content[body..end]);
+            for identifier in found.iter().chain(&earlier) {
+                let (_, pages) = sources
+                    .entry(identifier.text.clone())
+                    .or_insert_with(|| (identifier.url.clone(), Vec::new()));
+                if !pages.contains(&page) {
+                    pages.push(page.clone());
+                }
+            }
+            if link && !found.is_empty() {
+                let linked = identifiers::link(&content[body..end], &found);
+                chapter.content.replace_range(body..end, &linked);
+            }
+        });
+        sources
+    }
+
+    /// Every heading of `book`, in reading order.
+    fn heading_registry(book: &Book) -> Vec<Heading> {
+        book.iter()
+            .filter_map(|item| match item {
+                BookItem::Chapter(chapter) if chapter.path.is_some() => Some(chapter),
+                _ => None,
+            })
+            .flat_map(|chapter| {
+                let content = &chapter.content;
+                let content = &content[..footer_start(content).unwrap_or(content.len())];
+                let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+                xrefs::headings(&content[body..], &chapter_path(chapter))
+            })
+            .collect()
+    }
+
+    /// The symbols the code spans of the chapters of `book` show, matching
+    /// `pattern`, with the chapters showing each.
+    fn code_symbols(book: &Book, pattern: &Regex) -> BTreeMap<String, Vec<String>> {
+        let chapters = book.iter().filter_map(|item| match item {
+            BookItem::Chapter(chapter) if chapter.path.is_some() => {
+                let content = &chapter.content;
+                let content = &content[..footer_start(content).unwrap_or(content.len())];
+                Some((chapter_path(chapter), content))
+            }
+            _ => None,
+        });
+        symbols::collect(chapters, pattern)
+    }
+
+    /// The external links of the chapters of `book`, by domain.
+    fn external_links(book: &Book) -> BTreeMap<String, Vec<ExternalLink>> {
+        let chapters = book.iter().filter_map(|item| match item {
+            BookItem::Chapter(chapter) if chapter.path.is_some() => {
+                let content = &chapter.content;
+                let content = &content[..footer_start(content).unwrap_or(content.len())];
+                Some((chapter_path(chapter), content))
+            }
+            _ => None,
+        });
+        external::collect(chapters)
+    }
+
+    /// Whether the chapter at `page` meets `condition` of a query, by the keys
+    /// `indexes` of `kinds` hold, its front matter among `front_matters` and
+    /// its title among `chapters`.
+    fn meets_condition(
+        page: &str,
+        condition: &Condition,
+        kinds: &[EntityKind],
+        indexes: &[Index],
+        front_matters: &HashMap<String, FrontMatter>,
+        chapters: &HashMap<String, ChapterInfo>,
+        config: &IndexerConfig,
+    ) -> bool {
+        let value = condition.value.to_lowercase();
+        let references = |kind: usize, key: &str| {
+            indexes[kind]
+                .get(key)
+                .is_some_and(|entry| entry.occurrences.iter().any(|o| o.page == page))
+        };
+        let kind = match condition.field.as_str() {
+            "" => kinds
+                .iter()
+                .position(|kind| condition.value.starts_with(kind.prefix)),
+            field => kinds
+                .iter()
+                .position(|kind| kind.name == field || kind.name.strip_suffix('s') == Some(field)),
+        };
+        if let Some(kind) = kind {
+            let key = condition
+                .value
+                .strip_prefix(kinds[kind].prefix)
+                .unwrap_or(&condition.value);
+            return references(kind, key);
+        }
+        match condition.field.as_str() {
+            "" => chapters
+                .get(page)
+                .is_some_and(|chapter| chapter.name.to_lowercase().contains(&value)),
+            "path" => page.starts_with(condition.value.trim_start_matches("./")),
+            field => {
+                let given = front_matters.get(page).map_or_else(Vec::new, |front| {
+                    front
+                        .text(field)
+                        .map_or_else(|| front.list(field), |text| vec![text])
+                });
+                if given.iter().any(|given| given.to_lowercase() == value) {
+                    return true;
+                }
+                let tags = kinds.iter().position(|kind| kind.name == "tags");
+                let status = tags.and_then(|tags| {
+                    config
+                        .statuses
+                        .iter()
+                        .find(|status| references(tags, status))
+                });
+                field == "status" && status.is_some_and(|status| status.to_lowercase() == value)
+            }
+        }
+    }
+
+    /// The values of `fields` the front matter of each chapter gives, by
+    /// path, leaving out chapters giving none. The fields naming one of
+    /// `kinds` hold its keys, spelled as the index records them.
+    fn chapter_metadata(
+        book: &Book,
+        kinds: &[EntityKind],
+        fields: &[String],
+    ) -> HashMap<String, Vec<(String, Vec<String>)>> {
+        let mut metadata = HashMap::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let Some(front) = FrontMatter::parse(&chapter.content) else {
+                continue;
+            };
+            let page = chapter_path(chapter);
+            let given: Vec<(String, Vec<String>)> = fields
+                .iter()
+                .map(|field| {
+                    let values = match kinds.iter().find(|kind| &kind.name == field) {
+                        Some(kind) => front
+                            .keys(kind)
+                            .iter()
+                            .map(|key| kind.canonical(key, &page))
+                            .collect(),
+                        None => match front.text(field) {
+                            Some(text) => vec![text],
+                            None => front.list(field),
+                        },
+                    };
+                    (field.clone(), values)
+                })
+                .filter(|(_, values)| !values.is_empty())
+                .collect();
+            if !given.is_empty() {
+                metadata.insert(page, given);
+            }
+        }
+        metadata
+    }
+
+    /// The tasks of every chapter, by path.
+    fn chapter_tasks(book: &Book) -> BTreeMap<String, Vec<Task>> {
+        chapter_sources(book)
+            .into_iter()
+            .map(|(page, source)| (page, tasks::tasks(&source)))
+            .collect()
+    }
+
+    /// Links every term of the glossary to its definition, in every chapter
+    /// but the one defining them. Returns the chapters linking to each
+    /// definition, in reading order.
+    fn link_glossary(
+        book: &mut Book,
+        kinds: &[EntityKind],
+        definitions: &[glossary::Definition],
+        config: &IndexerConfig,
+    ) -> Vec<Vec<String>> {
+        let mut uses = vec![Vec::new(); definitions.len()];
+        if definitions.is_empty() {
+            return uses;
+        }
+        let file = &config.glossary_file;
+        let anchors = glossary::anchors(&config.glossary_title, definitions);
+        let (numbers, terms): (Vec<usize>, Vec<Term>) =
+            glossary::terms(definitions, &anchors, file)
+                .into_iter()
+                .unzip();
+        let prefixes: Vec<char> = kinds.iter().map(|kind| kind.prefix).collect();
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            if page.is_empty() || config.glossary.as_ref() == Some(&page) {
+                return;
+            }
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let found = unlinked::find_all(&content[body..end], &page, &terms, &prefixes);
+            let linked = unlinked::link(&content[body..end], &page, &found, &terms);
+            // Links from an earlier run or the author count as well
+            let targets = unlinked::linked(&linked, &page);
+            for (&number, term) in numbers.iter().zip(&terms) {
+                if targets.contains(&format!("{file}#{}", term.anchor)) {
+                    uses[number].push(page.clone());
+                }
+            }
+            chapter.content.replace_range(body..end, &linked);
+        });
+        uses
+    }
+
+    /// Wraps the acronyms of every chapter in an `<abbr>` for the HTML
+    /// renderer. Returns the acronyms, configured or defined in the book, and
+    /// the chapters using each, in reading order.
+    fn expand_acronyms(
+        book: &mut Book,
+        config: &IndexerConfig,
+        renderer: &str,
+    ) -> (BTreeMap<String, String>, BTreeMap<String, Vec<String>>) {
+        let mut acronyms = config.acronyms.clone();
+        let mut uses: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        if config.detect_acronyms {
+            // The first definition wins, and the configuration over them all
+            for item in book.iter() {
+                if let BookItem::Chapter(chapter) = item {
+                    for (acronym, meaning) in acronyms::defined(&chapter.content) {
+                        acronyms.entry(acronym).or_insert(meaning);
+                    }
+                }
+            }
+        }
+        if acronyms.is_empty() {
+            return (acronyms, uses);
+        }
+        book.for_each_mut(|item| {
+            let BookItem::Chapter(chapter) = item else {
+                return;
+            };
+            let page = chapter_path(chapter);
+            if page.is_empty() {
+                return;
+            }
+            let content = &chapter.content;
+            let body = FrontMatter::parse(content).map_or(0, |front| front.len);
+            let end = footer_start(&content[body..]).map_or(content.len(), |end| body + end);
+            let (expanded, used) =
+                acronyms::expand(&content[body..end], &acronyms, renderer == "html");
+            for acronym in used {
+                uses.entry(acronym).or_default().push(page.clone());
+            }
+            chapter.content.replace_range(body..end, &expanded);
+        });
+        (acronyms, uses)
+    }
+
+    /// The words of each chapter weighted by TF-IDF, through the cache of
+    /// `similarity_cache` if set.
+    fn chapter_keywords(
+        book: &Book,
+        chapters: &HashMap<String, ChapterInfo>,
+        root: &Path,
+        config: &IndexerConfig,
+    ) -> Result<BTreeMap<String, Vec<(String, f64)>>, Error> {
+        let mut sources = chapter_sources(book);
+        // Drafts have no page to relate to
+        sources.retain(|page, _| !page.is_empty() && chapters.contains_key(page));
+        let file = config.similarity_cache.as_ref().map(|file| root.join(file));
+        let mut cache = file
+            .as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .map_or_else(Cache::default, |json| Cache::parse(&json));
+        let keywords = similarity::keywords(&sources, &mut cache);
+        if let Some(file) = &file {
+            export(file, &cache.to_json())?;
+        }
+        Ok(keywords)
+    }
+
+    /// Up to `limit` tags for each chapter of `keywords` the `tags` indexes
+    /// don't reference: the existing tags among its heaviest words, matched
+    /// by their last part, then the other words.
+    fn suggest_tags(
+        kind: &EntityKind,
+        tags: &[&Index],
+        keywords: &BTreeMap<String, Vec<(String, f64)>>,
+        limit: usize,
+    ) -> BTreeMap<String, Vec<String>> {
+        let entries: Vec<&Entry> = tags.iter().flat_map(|index| index.entries()).collect();
+        let tagged: HashSet<&str> = entries
+            .iter()
+            .flat_map(|entry| &entry.occurrences)
+            .map(|occurrence| occurrence.page.as_str())
+            .collect();
+        let mut existing: HashMap<String, &str> = HashMap::new();
+        for entry in entries {
+            let name = entry.display.rsplit('/').next().unwrap_or(&entry.display);
+            existing
+                .entry(name.to_lowercase())
+                .or_insert(&entry.display);
+        }
+
+        let mut suggested = BTreeMap::new();
+        for (page, words) in keywords {
+            if tagged.contains(page.as_str()) {
+                continue;
+            }
+            let known = words
+                .iter()
+                .filter_map(|(word, _)| existing.get(word).copied());
+            let new = words.iter().map(|(word, _)| word.as_str()).filter(|word| {
+                !existing.contains_key(*word) && kind.accepts(word) && kind.takes(word)
+            });
+            let mut tags: Vec<String> = Vec::new();
+            for key in known.chain(new) {
+                let tag = format!("{}{key}", kind.prefix);
+                if !tags.contains(&tag) {
+                    tags.push(tag);
+                }
+                if tags.len() == limit {
+                    break;
+                }
+            }
+            if !tags.is_empty() {
+                suggested.insert(page.clone(), tags);
+            }
+        }
+        suggested
+    }
+
+    /// Notes `chapter` as linking to every other chapter its links point to.
+    fn record_backlinks(
+        chapter: &Chapter,
+        chapters: &HashMap<String, ChapterInfo>,
+        backlinks: &mut HashMap<String, Vec<String>>,
+    ) {
+        let from = chapter_path(chapter);
+        for link in link_targets(&chapter.content) {
+            let Some(mut target) = resolve_link(Path::new(&from), &link) else {
+                continue;
+            };
+            // mdBook serves README.md chapters as index.md
+            if let Some(dir) = target.strip_suffix("README.md") {
+                if !chapters.contains_key(&target) {
+                    target = format!("{dir}index.md");
+                }
+            }
+            if target == from || !chapters.contains_key(&target) {
+                continue;
+            }
+            let sources = backlinks.entry(target).or_default();
+            if !sources.contains(&from) {
+                sources.push(from.clone());
+            }
+        }
+    }
+
+    /// Notes the daily note `chapter`, if it is one of `notes`, as linking
+    /// to the notes before and after it, as its journal navigation will.
+    fn record_journal_links(
+        chapter: &Chapter,
+        notes: &[(String, String)],
+        backlinks: &mut HashMap<String, Vec<String>>,
+    ) {
+        let from = chapter_path(chapter);
+        let Some(i) = notes.iter().position(|(_, note)| *note == from) else {
+            return;
+        };
+        let previous = i.checked_sub(1).and_then(|i| notes.get(i));
+        for (_, target) in previous.into_iter().chain(notes.get(i + 1)) {
+            let sources = backlinks.entry(target.clone()).or_default();
+            if !sources.contains(&from) {
+                sources.push(from.clone());
+            }
+        }
+    }
+
+    /// Notes `chapter` as linking to every stub its links point to.
+    fn record_stub_links(
+        chapter: &Chapter,
+        chapters: &HashMap<String, ChapterInfo>,
+        stubs: &mut Stubs,
+    ) {
+        let from = chapter_path(chapter);
+        for link in link_targets(&chapter.content) {
+            let Some(target) = resolve_link(Path::new(&from), &link) else {
+                continue;
+            };
+            if chapters.contains_key(&target) {
+                continue;
+            }
+            if let Some(stub) = stubs.pages.get_mut(&target) {
+                if !stub.sources.contains(&from) {
+                    stub.sources.push(from.clone());
+                }
+            }
+        }
+    }
+
+    /// Up to `limit` other chapters for each tagged chapter, sharing the most
+    /// tags with it; ties go to the chapter with fewer other tags, then to
+    /// the first in reading order.
+    fn related_pages(
+        tags: &Index,
+        chapters: &HashMap<String, ChapterInfo>,
+        limit: usize,
+    ) -> HashMap<String, Vec<String>> {
+        let mut page_tags: HashMap<&str, HashSet<&str>> = HashMap::new();
+        for entry in tags.entries() {
+            for occurrence in &entry.occurrences {
+                if chapters.contains_key(&occurrence.page) {
+                    page_tags
+                        .entry(&occurrence.page)
+                        .or_default()
+                        .insert(&entry.display);
+                }
+            }
+        }
+
+        let mut related = HashMap::new();
+        for (&page, own) in &page_tags {
+            let mut scored: Vec<(usize, usize, usize, &str)> = page_tags
+                .iter()
+                .filter(|(&other, _)| other != page)
+                .filter_map(|(&other, theirs)| {
+                    let shared = own.intersection(theirs).count();
+                    let position = chapters.get(other).map_or(0, |chapter| chapter.position);
+                    (shared > 0).then_some((shared, theirs.len() - shared, position, other))
+                })
+                .collect();
+            scored.sort_by_key(|&(shared, rest, position, _)| (Reverse(shared), rest, position));
+            let pages: Vec<String> = scored
+                .into_iter()
+                .take(limit)
+                .map(|(_, _, _, other)| other.to_string())
+                .collect();
+            if !pages.is_empty() {
+                related.insert(page.to_string(), pages);
+            }
+        }
+        related
+    }
+
+    /// Orders a collected index and decides where its entries link to.
+    fn prepare(generator: &Generator, kind: &EntityKind, index: &mut Index) {
+        index.sort();
+        generator.arrange(kind, index);
+        generator.assign_targets(kind, index);
+    }
+
+    /// A copy of the book-wide `indexes` for each part of the book, in
+    /// reading order, keeping only the occurrences in that part.
+    fn part_scopes(
+        kinds: &[EntityKind],
+        indexes: &[Index],
+        chapters: &HashMap<String, ChapterInfo>,
+        config: &IndexerConfig,
+    ) -> Vec<PartScope> {
+        if config.part_indexes == PartIndexes::Off {
+            return Vec::new();
+        }
+        let mut in_order: Vec<&ChapterInfo> = chapters.values().collect();
+        in_order.sort_by_key(|chapter| chapter.position);
+        let mut parts: Vec<&str> = Vec::new();
+        for part in in_order
+            .iter()
+            .filter_map(|chapter| chapter.part.as_deref())
+        {
+            if !parts.contains(&part) {
+                parts.push(part);
+            }
+        }
+
+        parts
+            .into_iter()
+            .map(|part| {
+                let kinds = kinds
+                    .iter()
+                    .map(|kind| EntityKind {
+                        file: format!("{}/{}", slug(part), kind.file),
+                        ..kind.clone()
+                    })
+                    .collect();
+                let indexes = indexes
+                    .iter()
+                    .map(|index| {
+                        let mut index = index.clone();
+                        index.retain_pages(|page| {
+                            chapters
+                                .get(page)
+                                .is_some_and(|chapter| chapter.part.as_deref() == Some(part))
+                        });
+                        index
+                    })
+                    .collect();
+                PartScope {
+                    part: part.to_string(),
+                    kinds,
+                    indexes,
+                }
+            })
+            .collect()
+    }
+
+    /// Ids like `tags-rust-2` for every indexed token, numbered per chapter.
+    fn occurrence_anchors(
+        scanner: &Scanner,
+        tokens: &[Token],
+        config: &IndexerConfig,
+    ) -> Vec<String> {
+        let mut counts = HashMap::new();
+        tokens
+            .iter()
+            .map(|token| {
+                if !config.deep_links || token.form == Form::Escaped {
+                    return String::new();
+                }
+                let kind = &scanner.kinds[token.kind];
+                let base = format!("{}-{}", kind.name, slug(&token.key));
+                let count = counts.entry(base.clone()).or_insert(0);
+                *count += 1;
+                format!("{base}-{count}")
+            })
+            .collect()
+    }
+
+    fn index_tokens(
+        chapter: &Chapter,
+        scanner: &Scanner,
+        scanned: &ScannedChapter,
+        indexes: &mut [Index],
+        config: &IndexerConfig,
+    ) {
+        let chapter_path = chapter_path(chapter);
+        let body = &chapter.content[scanned.body..];
+
+        for (token, anchor) in scanned.tokens.iter().zip(&scanned.anchors) {
+            if token.form == Form::Escaped {
+                continue;
+            }
+            let kind = &scanner.kinds[token.kind];
+            let occurrence = Occurrence {
+                page: chapter_path.clone(),
+                offset: token.range.start,
+                snippet: if config.snippets {
+                    snippet(body, token.range.clone())
+                } else {
+                    String::new()
+                },
+                anchor: anchor.clone(),
+            };
+            record_key(&mut indexes[token.kind], kind, &token.key, occurrence);
+        }
+    }
+
+    /// Indexes the keys a chapter declares in its front matter, which count
+    /// like inline tokens but leave the body untouched.
+    fn index_front_matter(
+        chapter: &Chapter,
+        scanner: &Scanner,
+        front_matter: &FrontMatter,
+        indexes: &mut [Index],
+    ) {
+        let chapter_path = chapter_path(chapter);
+
+        for (kind, index) in scanner.kinds.iter().zip(indexes) {
+            for key in front_matter.keys(kind) {
+                let occurrence = Occurrence {
+                    page: chapter_path.clone(),
+                    offset: 0,
+                    snippet: String::new(),
+                    anchor: String::new(),
+                };
+                record_key(index, kind, &key, occurrence);
+            }
+        }
+    }
+
+    /// Records an occurrence of `key`, or of what it's an alias of, and of
+    /// each of its ancestors if the kind is nested.
+    fn record_key(index: &mut Index, kind: &EntityKind, key: &str, occurrence: Occurrence) {
+        let targets = kind.alias_targets(key, &occurrence.page);
+        if targets.len() > 1 {
+            eprintln!(
+                "Warning: {}{key} in {} could stand for any of {}; \
+                 give their aliases an alias_in to tell them apart",
+                kind.prefix,
+                occurrence.page,
+                targets.join(", ")
+            );
+        }
+        let key = &kind.canonical(key, &occurrence.page);
+        if kind.nested {
+            for (end, _) in key.match_indices('/') {
+                index.record(&key[..end], occurrence.clone());
+            }
+        }
+        index.record(key, occurrence);
+    }
+
+    fn rewrite_chapter(
+        chapter: &Chapter,
+        scanner: &Scanner,
+        scanned: &ScannedChapter,
+        indexes: &[Index],
+        config: &IndexerConfig,
+    ) -> String {
+        // Token ranges are relative to the body, after any front matter
+        let (front_matter, source) = chapter.content.split_at(scanned.body);
+        let chapter_path = chapter.path.clone().unwrap_or_default();
+        let mut content = String::with_capacity(chapter.content.len());
+        if config.front_matter == FrontMatterMode::Keep {
+            content.push_str(front_matter);
+        }
+        let mut last = 0;
+
+        // Rewrite each token in place so code and unrelated text stay untouched
+        for (token, anchor) in scanned.tokens.iter().zip(&scanned.anchors) {
+            let kind = &scanner.kinds[token.kind];
+            // A linked token may still carry the anchor of an earlier run
+            let anchor = Some(format!("<a id=\"{anchor}\"></a>"))
+                .filter(|html| !anchor.is_empty() && !source[..token.range.start].ends_with(html));
+            let replacement = match token.form {
+                Form::Escaped => scanner.literal(source, token),
+                Form::Linked => {
+                    let link = &source[token.range.clone()];
+                    let key = kind.canonical(&token.key, &chapter_path.to_string_lossy());
+                    let retargeted = indexes[token.kind].get(&key).and_then(|entry| {
+                        let target = link_to(&chapter_path, Path::new(&entry.file), &entry.anchor);
+                        retarget(kind, link, &target)
+                    });
+                    match retargeted {
+                        Some(link) => link,
+                        None if anchor.is_some() => link.to_string(),
+                        None => continue,
+                    }
+                }
+                Form::Bare => {
+                    let key = kind.canonical(&token.key, &chapter_path.to_string_lossy());
+                    let Some(entry) = indexes[token.kind].get(&key) else {
+                        continue;
+                    };
+                    let profile = Some(&key)
+                        .filter(|_| kind.name == "mentions")
+                        .and_then(|key| config.profile_url(key));
+                    let mode = config.mention_links_mode;
+                    let mut replacement = if let Some(url) = profile
+                        .as_ref()
+                        .filter(|_| mode == MentionLinksMode::Instead)
+                    {
+                        format!("[{}{}]({url})", kind.prefix, token.key)
+                    } else if !config.link_rare && entry.count() < config.min_occurrences {
+                        source[token.range.clone()].to_string()
+                    } else {
+                        let link = link_to(&chapter_path, Path::new(&entry.file), &entry.anchor);
+                        let mut linked = format!("[{}{}]({})", kind.prefix, token.key, link);
+                        if config.count_badges {
+                            let badge =
+                                format!("<sup class=\"indexer-count\">{}</sup>", entry.count());
+                            linked.push_str(&badge);
+                        }
+                        linked
+                    };
+                    if let Some(url) = profile.filter(|_| mode == MentionLinksMode::Alongside) {
+                        let label = &config.labels.profile;
+                        replacement.push_str(&format!(" [{label}]({url})"));
+                    }
+                    replacement
+                }
+            };
+            content.push_str(&source[last..token.range.start]);
+            if let Some(anchor) = &anchor {
+                content.push_str(anchor);
+            }
+            content.push_str(&replacement);
+            last = token.range.end;
+        }
+        content.push_str(&source[last..]);
+
+        content
+    }
+
+    /// The inline `link` to an entry of `kind`'s index pointing at `target`
+    /// instead, if it points at another page of the index, like one the
+    /// entry left by moving to a page of its own or another split page.
+    fn retarget(kind: &EntityKind, link: &str, target: &str) -> Option<String> {
+        let (text, dest) = link.strip_suffix(')')?.rsplit_once("](")?;
+        let page = dest.split('#').next().unwrap_or_default();
+        (dest != target && kind.links_to(page)).then(|| format!("{text}]({target})"))
+    }
+
+    /// The pages left at the former names of `chapters`, as their path, the
+    /// alias and the chapter it names, in reading order. An alias naming a
+    /// chapter, or another alias, is reported and left out.
+    fn redirects(chapters: &HashMap<String, ChapterInfo>) -> Vec<(String, String, String)> {
+        let mut pages: Vec<(&String, &ChapterInfo)> = chapters
+            .iter()
+            .filter(|(page, _)| !page.is_empty())
+            .collect();
+        pages.sort_by_key(|(_, chapter)| chapter.position);
+        let mut redirects: Vec<(String, String, String)> = Vec::new();
+        for (page, chapter) in pages {
+            for alias in &chapter.aliases {
+                let name = alias.trim_start_matches('/');
+                let path = if name.ends_with(".md") {
+                    name.to_string()
+                } else {
+                    match page.rsplit_once('/') {
+                        Some((dir, _)) => format!("{dir}/{name}.md"),
+                        None => format!("{name}.md"),
+                    }
+                };
+                if chapters.contains_key(&path) || redirects.iter().any(|(p, _, _)| *p == path) {
+                    eprintln!("Warning: the alias {alias} of {page} is taken, so it gets no page");
+                    continue;
+                }
+                redirects.push((path, alias.clone(), page.clone()));
+            }
+        }
+        redirects
+    }
+
+    /// Gives the notes of `book` their Zettelkasten IDs, which `pattern`
+    /// matches, warning about those several notes share.
+    fn assign_ids(book: &Book, chapters: &mut HashMap<String, ChapterInfo>, pattern: &Regex) {
+        let mut notes: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for item in book.iter() {
+            let BookItem::Chapter(chapter) = item else {
+                continue;
+            };
+            let page = chapter_path(chapter);
+            if page.is_empty() {
+                continue;
+            }
+            if let Some(id) = zettel::id(&page, &chapter.content, pattern) {
+                notes.entry(id.clone()).or_default().push(page.clone());
+                if let Some(info) = chapters.get_mut(&page) {
+                    info.id = Some(id);
+                }
+            }
+        }
+        for (id, pages) in notes.iter().filter(|(_, pages)| pages.len() > 1) {
+            eprintln!("Warning: {} share the ID {id}", pages.join(", "));
+        }
+    }
+
+    fn chapter_infos(book: &Book, src_dir: &Path) -> HashMap<String, ChapterInfo> {
+        // Part titles only appear at the top level, before their chapters
+        let mut part = None;
+        book.iter()
+            .filter_map(|item| match item {
+                BookItem::PartTitle(title) => {
+                    part = Some(title.clone());
+                    None
+                }
+                BookItem::Chapter(chapter) => Some((chapter, part.clone())),
+                BookItem::Separator => None,
+            })
+            .enumerate()
+            .map(|(position, (chapter, part))| {
+                let modified = chapter
+                    .source_path
+                    .as_ref()
+                    .and_then(|source| fs::metadata(src_dir.join(source)).ok())
+                    .and_then(|metadata| metadata.modified().ok());
+                let info = ChapterInfo {
+                    name: chapter.name.clone(),
+                    parents: chapter.parent_names.clone(),
+                    position,
+                    part,
+                    modified,
+                    id: None,
+                    aliases: FrontMatter::parse(&chapter.content)
+                        .map(|front| front.list("aliases"))
+                        .unwrap_or_default(),
+                };
+                (chapter_path(chapter), info)
+            })
+            .collect()
     }
 
     fn chapter_path(chapter: &Chapter) -> String {
@@ -0,0 +1,88 @@
+use crate::autolinks;
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// `@@(48.85,2.35)`: a latitude and a longitude, in degrees.
+static COORDINATES: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"@@\(\s*-?\d{1,2}(?:\.\d+)?\s*,\s*-?\d{1,3}(?:\.\d+)?\s*\)")
+        .expect("the coordinates pattern is valid")
+});
+
+/// A point on the map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Point {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl Point {
+    /// As shown, like `48.85, 2.35`.
+    pub fn label(&self) -> String {
+        format!("{}, {}", self.lat, self.lon)
+    }
+
+    /// Where OpenStreetMap shows it.
+    pub fn url(&self) -> String {
+        let Point { lat, lon } = self;
+        format!("https://www.openstreetmap.org/?mlat={lat}&mlon={lon}#map=12/{lat}/{lon}")
+    }
+}
+
+/// A place chapters are set in: one a tag names, like `paris` for
+/// `#loc/paris`, or coordinates written out.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Location {
+    /// As first written, or the label of the coordinates.
+    pub name: String,
+    /// Where it is, if known.
+    pub point: Option<Point>,
+    /// Chapters set there, in reading order.
+    pub pages: Vec<String>,
+}
+
+/// The coordinates written in `content` outside code, HTML and links, with
+/// the latitude within ±90 and the longitude within ±180.
+pub(crate) fn find(content: &str) -> Vec<(Range<usize>, Point)> {
+    autolinks::find(content, &COORDINATES, &[])
+        .into_iter()
+        .filter_map(|(range, marker)| {
+            let inner = marker.strip_prefix("@@(")?.strip_suffix(')')?;
+            Some((range, point(inner)?))
+        })
+        .collect()
+}
+
+/// The coordinates of the links of `content` an earlier run made, those
+/// showing a point's label and leading to the map.
+pub(crate) fn linked(content: &str) -> Vec<Point> {
+    let url = |label: &str| point(label).map(|point| point.url()).unwrap_or_default();
+    autolinks::linked(content, url)
+        .iter()
+        .filter_map(|label| point(label))
+        .collect()
+}
+
+/// The point `text`, like `48.85, 2.35`, stands for, if it is on the globe.
+fn point(text: &str) -> Option<Point> {
+    let (lat, lon) = text.split_once(',')?;
+    let point = Point {
+        lat: lat.trim().parse().ok()?,
+        lon: lon.trim().parse().ok()?,
+    };
+    let valid = point.lat.abs() <= 90.0 && point.lon.abs() <= 180.0;
+    valid.then_some(point)
+}
+
+/// `content` with each of `found` shown by its label and linked to the map.
+pub(crate) fn link(content: &str, found: &[(Range<usize>, Point)]) -> String {
+    let mut linked = String::with_capacity(content.len());
+    let mut last = 0;
+    for (range, point) in found {
+        linked.push_str(&content[last..range.start]);
+        linked.push_str(&format!("[{}]({})", point.label(), point.url()));
+        last = range.end;
+    }
+    linked.push_str(&content[last..]);
+    linked
+}
@@ -0,0 +1,38 @@
+// Leaflet map of the places embedded next to it, on OpenStreetMap tiles.
+// Each marker opens the list of the chapters set there.
+(function () {
+    "use strict";
+    const data = JSON.parse(document.getElementById("indexer-map-data").textContent);
+    const map = L.map("indexer-map");
+    L.tileLayer("https://tile.openstreetmap.org/{z}/{x}/{y}.png", {
+        maxZoom: 19,
+        attribution:
+            '&copy; <a href="https://www.openstreetmap.org/copyright">OpenStreetMap</a> contributors',
+    }).addTo(map);
+
+    const points = [];
+    for (const place of data.places) {
+        // Built from nodes so no name or title is read as HTML
+        const popup = document.createElement("div");
+        const name = document.createElement("strong");
+        name.textContent = place.name;
+        popup.appendChild(name);
+        const list = document.createElement("ul");
+        for (const page of place.pages) {
+            const item = document.createElement("li");
+            const link = document.createElement("a");
+            link.href = page.url;
+            link.textContent = page.title;
+            item.appendChild(link);
+            list.appendChild(item);
+        }
+        popup.appendChild(list);
+        L.marker([place.lat, place.lon]).addTo(map).bindPopup(popup);
+        points.push([place.lat, place.lon]);
+    }
+    if (points.length > 0) {
+        map.fitBounds(points, { padding: [40, 40], maxZoom: 12 });
+    } else {
+        map.setView([20, 0], 2);
+    }
+})();
@@ -0,0 +1,213 @@
+use crate::extract::parser;
+use crate::paths::relative_link;
+use anyhow::Context;
+use mdbook::book::{Book, BookItem, Chapter};
+use mdbook::errors::Error;
+use pulldown_cmark::{Event, Tag};
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the attachments `![[...]]` shows as images.
+const IMAGES: [&str; 7] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "avif"];
+
+/// Ranges of the code of `content`, which Obsidian syntax doesn't reach.
+fn code(content: &str) -> Vec<Range<usize>> {
+    parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| {
+            matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Code(_)).then_some(range)
+        })
+        .collect()
+}
+
+/// `content` without its `%%comments%%`, inline or over several lines,
+/// outside code. An unclosed `%%` is kept.
+pub(crate) fn strip_comments(content: &str) -> String {
+    let code = code(content);
+    let mut stripped = String::with_capacity(content.len());
+    let mut last = 0;
+    let mut from = 0;
+    while let Some(found) = content[from..].find("%%") {
+        let start = from + found;
+        from = start + 2;
+        if code.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+        let Some(len) = content[from..].find("%%") else {
+            break;
+        };
+        stripped.push_str(&content[last..start]);
+        last = from + len + 2;
+        from = last;
+    }
+    stripped.push_str(&content[last..]);
+    stripped
+}
+
+/// `content` of the chapter at `from` with every image embed outside code,
+/// like `![[diagram.png]]` or `![[diagram.png|300]]`, shown as an image of
+/// the attachment of that name among `files`, the paths of the source
+/// directory. At a width, the image is shown in HTML.
+pub(crate) fn embed_images(content: &str, from: &str, files: &[String]) -> String {
+    let code = code(content);
+    let mut embedded = String::with_capacity(content.len());
+    let mut last = 0;
+    let mut at = 0;
+    while let Some(found) = content[at..].find("![[") {
+        let start = at + found;
+        at = start + 3;
+        let Some(len) = content[at..].find("]]") else {
+            break;
+        };
+        let inner = &content[at..at + len];
+        let (name, size) = match inner.split_once('|') {
+            Some((name, size)) => (name.trim(), Some(size.trim())),
+            None => (inner.trim(), None),
+        };
+        let is_image = Path::new(name)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| IMAGES.contains(&ext.to_lowercase().as_str()));
+        if !is_image || inner.contains(['[', '\n']) || code.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+        // Like Obsidian, a name without folders means the file anywhere
+        let file = files.iter().find(|file| {
+            file.as_str() == name || (!name.contains('/') && file.rsplit('/').next() == Some(name))
+        });
+        let Some(file) = file else {
+            eprintln!("Warning: the embed ![[{inner}]] in {from} names no attachment");
+            continue;
+        };
+        let src = relative_link(Path::new(from), Path::new(file));
+        let alt = name.rsplit('/').next().unwrap_or(name);
+        embedded.push_str(&content[last..start]);
+        match size.filter(|size| size.chars().all(|c| c.is_ascii_digit())) {
+            Some(width) => embedded.push_str(&format!(
+                "<img src=\"{src}\" alt=\"{alt}\" width=\"{width}\">"
+            )),
+            None => embedded.push_str(&format!("![{alt}](<{src}>)")),
+        }
+        last = at + len + 2;
+        at = last;
+    }
+    embedded.push_str(&content[last..]);
+    embedded
+}
+
+/// The paths, relative to `src_dir`, of every file of the vault, leaving
+/// out hidden ones like `.obsidian`.
+pub(crate) fn files(src_dir: &Path) -> Result<Vec<String>, Error> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let entries = fs::read_dir(src_dir.join(&dir))
+            .with_context(|| format!("couldn't read the vault folder {}", dir.display()))?;
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') {
+                continue;
+            }
+            let path = dir.join(&name);
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+            } else {
+                files.push(path.to_string_lossy().replace('\\', "/"));
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Adds the notes of `files` the summary leaves out to the end of `book`,
+/// laid out as the folders of the vault are: each folder a chapter with its
+/// folder note, `README.md`, `index.md` or named after it, else a draft,
+/// its folders first, then its notes, by name.
+pub(crate) fn add_notes(book: &mut Book, src_dir: &Path, files: &[String]) -> Result<(), Error> {
+    let mut listed: HashSet<String> = book
+        .iter()
+        .filter_map(|item| match item {
+            BookItem::Chapter(chapter) => chapter.source_path.as_ref(),
+            _ => None,
+        })
+        .map(|path| path.to_string_lossy().replace('\\', "/"))
+        .collect();
+    listed.insert(String::from("SUMMARY.md"));
+    let notes: Vec<&str> = files
+        .iter()
+        .map(String::as_str)
+        .filter(|file| file.ends_with(".md") && !listed.contains(*file))
+        .collect();
+    let items = folder(src_dir, "", &notes, &mut listed, &[])?;
+    book.sections.extend(items);
+    Ok(())
+}
+
+/// The chapters of the notes in `dir`, and below it, not `listed` yet.
+fn folder(
+    src_dir: &Path,
+    dir: &str,
+    notes: &[&str],
+    listed: &mut HashSet<String>,
+    parents: &[String],
+) -> Result<Vec<BookItem>, Error> {
+    let prefix = if dir.is_empty() {
+        String::new()
+    } else {
+        format!("{dir}/")
+    };
+    let below: Vec<&str> = notes
+        .iter()
+        .filter_map(|note| note.strip_prefix(prefix.as_str()))
+        .collect();
+    let mut folders: Vec<&str> = below
+        .iter()
+        .filter_map(|rest| rest.split_once('/').map(|(folder, _)| folder))
+        .collect();
+    folders.sort_by_key(|folder| folder.to_lowercase());
+    folders.dedup();
+
+    let mut items = Vec::new();
+    for name in folders {
+        let path = format!("{prefix}{name}");
+        let folder_note = ["README.md", "index.md", &format!("{name}.md")]
+            .iter()
+            .map(|file| format!("{path}/{file}"))
+            .find(|file| notes.contains(&file.as_str()) && !listed.contains(file));
+        let mut chapter = match folder_note {
+            Some(file) => {
+                listed.insert(file.clone());
+                note(src_dir, &file, name, parents)?
+            }
+            None => Chapter::new_draft(name, parents.to_vec()),
+        };
+        let mut inner = parents.to_vec();
+        inner.push(name.to_string());
+        chapter.sub_items = folder(src_dir, &path, notes, listed, &inner)?;
+        items.push(BookItem::Chapter(chapter));
+    }
+    let mut files: Vec<&str> = below
+        .into_iter()
+        .filter(|rest| !rest.contains('/'))
+        .collect();
+    files.sort_by_key(|file| file.to_lowercase());
+    for file in files {
+        let path = format!("{prefix}{file}");
+        if listed.insert(path.clone()) {
+            let name = file.strip_suffix(".md").unwrap_or(file);
+            items.push(BookItem::Chapter(note(src_dir, &path, name, parents)?));
+        }
+    }
+    Ok(items)
+}
+
+/// The chapter of the note at `path`, titled `name` as Obsidian does.
+fn note(src_dir: &Path, path: &str, name: &str, parents: &[String]) -> Result<Chapter, Error> {
+    let content = fs::read_to_string(src_dir.join(path))
+        .with_context(|| format!("couldn't read the note {path}"))?;
+    Ok(Chapter::new(name, content, path, parents.to_vec()))
+}
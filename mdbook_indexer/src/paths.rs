@@ -0,0 +1,104 @@
+use std::path::{Component, Path};
+
+/// Link from the chapter at `from` to the file at `to`, both relative to the
+/// book's source directory, e.g. `../tags.md` from `guides/setup.md`. Spaces,
+/// common in the names of notes, are percent-encoded so the link holds.
+pub(crate) fn relative_link(from: &Path, to: &Path) -> String {
+    let from_dir = components(from.parent().unwrap_or(Path::new("")));
+    let to = components(to);
+    let common = from_dir.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut parts = vec![".."; from_dir.len() - common];
+    parts.extend(to[common..].iter().map(String::as_str));
+    parts.join("/").replace(' ', "%20")
+}
+
+/// Link from the chapter at `from` to `anchor` in the file at `to`, or to
+/// the top of the file if `anchor` is empty.
+pub(crate) fn link_to(from: &Path, to: &Path, anchor: &str) -> String {
+    let link = relative_link(from, to);
+    if anchor.is_empty() {
+        link
+    } else {
+        format!("{}#{}", link, percent_encode(anchor))
+    }
+}
+
+/// Whether the link `dest` is relative to the chapter it is written in: no
+/// URL with a scheme, and no absolute path.
+pub(crate) fn is_relative_link(dest: &str) -> bool {
+    let scheme = dest.split_once(':').is_some_and(|(scheme, _)| {
+        !scheme.is_empty()
+            && scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+    });
+    !scheme && !dest.starts_with('/')
+}
+
+/// The path, relative to the book's source directory, of the file a link
+/// written in the chapter at `from` points to, without its fragment.
+pub(crate) fn resolve_link(from: &Path, link: &str) -> Option<String> {
+    let link = link.split(['#', '?']).next().unwrap_or(link);
+    if link.is_empty() {
+        return None;
+    }
+    let mut parts = components(from.parent().unwrap_or(Path::new("")));
+    for component in Path::new(link).components() {
+        match component {
+            Component::Normal(part) => parts.push(percent_decode(&part.to_string_lossy())),
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            _ => {}
+        }
+    }
+    Some(parts.join("/"))
+}
+
+/// Fragments are percent-encoded so non-ASCII ids survive every renderer.
+pub(crate) fn percent_encode(fragment: &str) -> String {
+    fragment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// `fragment` with its percent-encoded bytes decoded, as ids are compared.
+pub(crate) fn percent_decode(fragment: &str) -> String {
+    let bytes = fragment.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = fragment
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The normal components of `path`, as written in Markdown links.
+fn components(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part.to_string_lossy().into_owned()),
+            _ => None,
+        })
+        .collect()
+}
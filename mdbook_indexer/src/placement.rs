@@ -0,0 +1,181 @@
+use crate::config::Placement;
+use crate::generate::is_generated;
+use mdbook::book::{Book, BookItem, Chapter, SectionNumber};
+use mdbook::errors::Error;
+
+/// Inserts the `generated` chapters into `book` where `placement` asks:
+/// appended to the book by default, or next to a chapter given by path or
+/// name, optionally under a part title and numbered along with the book.
+/// The chapters of `in_parts` close the part they are paired with.
+pub(crate) fn place(
+    book: &mut Book,
+    mut generated: Vec<Chapter>,
+    in_parts: Vec<(String, Vec<Chapter>)>,
+    placement: &Placement,
+) -> Result<(), Error> {
+    for (part, mut chapters) in in_parts {
+        let items = &mut book.sections;
+        let Some(start) = items
+            .iter()
+            .position(|item| matches!(item, BookItem::PartTitle(title) if *title == part))
+        else {
+            continue;
+        };
+        // After the part's last chapter, before any separator ending it
+        let next_part = items[start + 1..]
+            .iter()
+            .position(|item| matches!(item, BookItem::PartTitle(_)))
+            .map_or(items.len(), |next| start + 1 + next);
+        let end = items[start + 1..next_part]
+            .iter()
+            .rposition(|item| matches!(item, BookItem::Chapter(_)))
+            .map_or(start + 1, |last| start + last + 2);
+        for chapter in &mut chapters {
+            set_parents(chapter, &[]);
+            if placement.numbered {
+                mark_numbered(chapter);
+            }
+        }
+        items.splice(end..end, chapters.into_iter().map(BookItem::Chapter));
+    }
+
+    let target = match (&placement.after, &placement.before) {
+        (Some(_), Some(_)) => {
+            anyhow::bail!("[preprocessor.indexer.placement] sets both `after` and `before`")
+        }
+        (Some(target), None) => Some((target, 1)),
+        (None, Some(target)) => Some((target, 0)),
+        (None, None) => None,
+    };
+
+    let (items, at, parents) = match target {
+        None => {
+            let end = book.sections.len();
+            (&mut book.sections, end, Vec::new())
+        }
+        Some((target, offset)) => {
+            let Some(path) = locate(&book.sections, target) else {
+                anyhow::bail!("no chapter `{target}` to place the generated chapters next to");
+            };
+            if placement.part.is_some() && path.len() > 1 {
+                anyhow::bail!("a part can't start inside the nested chapter `{target}`");
+            }
+            let (items, parents) = siblings(&mut book.sections, &path);
+            (items, path[path.len() - 1] + offset, parents)
+        }
+    };
+
+    for chapter in &mut generated {
+        set_parents(chapter, &parents);
+        if placement.numbered {
+            mark_numbered(chapter);
+        }
+    }
+    let mut placed: Vec<BookItem> = generated.into_iter().map(BookItem::Chapter).collect();
+    if let Some(part) = &placement.part {
+        placed.insert(0, BookItem::PartTitle(part.clone()));
+    }
+    items.splice(at..at, placed);
+
+    if placement.numbered {
+        renumber(&mut book.sections, &[]);
+    }
+    Ok(())
+}
+
+/// Drops the part an earlier run opened for its generated chapters, which
+/// must happen before those chapters are removed.
+pub(crate) fn remove_part(items: &mut Vec<BookItem>, placement: &Placement) {
+    let Some(part) = &placement.part else {
+        return;
+    };
+    let mut i = 0;
+    while i < items.len() {
+        let is_placed = matches!(&items[i], BookItem::PartTitle(title) if title == part)
+            && match items.get(i + 1) {
+                Some(BookItem::Chapter(chapter)) => is_generated(chapter),
+                Some(_) => false,
+                None => true,
+            };
+        if is_placed {
+            items.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+/// Indices leading to the chapter whose path or name is `target`.
+fn locate(items: &[BookItem], target: &str) -> Option<Vec<usize>> {
+    for (i, item) in items.iter().enumerate() {
+        let BookItem::Chapter(chapter) = item else {
+            continue;
+        };
+        let path = chapter.path.as_deref().map(|path| path.to_string_lossy());
+        if chapter.name == target || path.as_deref() == Some(target) {
+            return Some(vec![i]);
+        }
+        if let Some(mut path) = locate(&chapter.sub_items, target) {
+            path.insert(0, i);
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// The list holding the chapter at `path`, with the names of its parents.
+fn siblings<'a>(
+    mut items: &'a mut Vec<BookItem>,
+    path: &[usize],
+) -> (&'a mut Vec<BookItem>, Vec<String>) {
+    let mut parents = Vec::new();
+    for &i in &path[..path.len() - 1] {
+        let BookItem::Chapter(chapter) = &mut items[i] else {
+            unreachable!("located paths only go through chapters");
+        };
+        parents = chapter.parent_names.clone();
+        parents.push(chapter.name.clone());
+        items = &mut chapter.sub_items;
+    }
+    (items, parents)
+}
+
+fn set_parents(chapter: &mut Chapter, parents: &[String]) {
+    chapter.parent_names = parents.to_vec();
+    let mut below = parents.to_vec();
+    below.push(chapter.name.clone());
+    for item in &mut chapter.sub_items {
+        if let BookItem::Chapter(sub) = item {
+            set_parents(sub, &below);
+        }
+    }
+}
+
+/// Gives `chapter` and everything below it a placeholder number for
+/// [`renumber`] to fill in.
+fn mark_numbered(chapter: &mut Chapter) {
+    chapter.number = Some(SectionNumber(Vec::new()));
+    for item in &mut chapter.sub_items {
+        if let BookItem::Chapter(sub) = item {
+            mark_numbered(sub);
+        }
+    }
+}
+
+/// Numbers the numbered chapters in order, as mdBook does for `SUMMARY.md`.
+fn renumber(items: &mut [BookItem], prefix: &[u32]) {
+    let mut n = 0;
+    for item in items {
+        let BookItem::Chapter(chapter) = item else {
+            continue;
+        };
+        if chapter.number.is_none() {
+            continue;
+        }
+        n += 1;
+        let mut number = prefix.to_vec();
+        number.push(n);
+        renumber(&mut chapter.sub_items, &number);
+        chapter.number = Some(SectionNumber(number));
+    }
+}
@@ -0,0 +1,218 @@
+use crate::extract::{contained, container_prefix, parser};
+use pulldown_cmark::{CodeBlockKind, Event, Tag};
+use std::ops::Range;
+
+/// Info string of the fenced blocks holding a query.
+pub(crate) const LANGUAGE: &str = "indexer-query";
+
+/// One condition on a chapter, like `tag:#rust`, or `#rust` with the field
+/// left to the prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Condition {
+    /// In lowercase, empty when the value opens with a prefix instead.
+    pub field: String,
+    pub value: String,
+}
+
+/// Conditions chapters are listed by, combined with `AND`, `OR` and `NOT`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Query {
+    Condition(Condition),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+    Not(Box<Query>),
+}
+
+impl Query {
+    /// Whether a chapter matches, `test` telling which conditions it meets.
+    pub fn matches(&self, test: &impl Fn(&Condition) -> bool) -> bool {
+        match self {
+            Query::Condition(condition) => test(condition),
+            Query::And(a, b) => a.matches(test) && b.matches(test),
+            Query::Or(a, b) => a.matches(test) || b.matches(test),
+            Query::Not(query) => !query.matches(test),
+        }
+    }
+}
+
+/// The query blocks of `content`, fenced as ```` ```indexer-query ````, with
+/// their text.
+pub(crate) fn blocks(content: &str) -> Vec<(Range<usize>, String)> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(Range<usize>, String)> = None;
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info)))
+                if info.split_whitespace().next() == Some(LANGUAGE) =>
+            {
+                open = Some((range, String::new()));
+            }
+            Event::Text(text) => {
+                if let Some((_, query)) = open.as_mut() {
+                    query.push_str(&text);
+                }
+            }
+            Event::End(_) => blocks.extend(open.take()),
+            _ => {}
+        }
+    }
+    blocks
+}
+
+/// Replaces the query block at `range` of `content` by `list`, its results,
+/// kept in the block quotes and list items holding the block, and one blank
+/// line apart from what follows.
+pub(crate) fn answer(content: &mut String, range: Range<usize>, list: &str) {
+    let prefix = container_prefix(content, range.start);
+    let blank = prefix.trim_end();
+    let mut rest = content[range.end..].trim_start_matches(['\r', '\n']);
+    // Blank lines of a block quote hold its `>` alone
+    while let Some(after) = rest
+        .strip_prefix(blank)
+        .filter(|after| !blank.is_empty() && after.trim_start_matches(' ').starts_with('\n'))
+    {
+        rest = after
+            .trim_start_matches(' ')
+            .trim_start_matches(['\r', '\n']);
+    }
+    let end = content.len() - rest.len();
+    let ending = if rest.is_empty() {
+        String::from("\n")
+    } else {
+        format!("\n{blank}\n")
+    };
+    let list = format!("{}{ending}", contained(list.trim_end(), &prefix));
+    content.replace_range(range.start..end, &list);
+}
+
+/// Reads `text`, where conditions next to each other must all hold, `AND` binds
+/// tighter than `OR`, and parentheses group. Values with spaces are quoted,
+/// as in `author:"Ann Lee"`.
+pub(crate) fn parse(text: &str) -> Result<Query, String> {
+    let tokens = tokenize(text)?;
+    let mut at = 0;
+    let query = or(&tokens, &mut at)?;
+    match tokens.get(at) {
+        None => Ok(query),
+        Some(token) => Err(format!("unexpected `{token}`")),
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' || c == ')' {
+            tokens.push(c.to_string());
+            chars.next();
+        } else {
+            let mut token = String::new();
+            let mut quoted = false;
+            while let Some(&c) = chars.peek() {
+                if !quoted && (c.is_whitespace() || c == '(' || c == ')') {
+                    break;
+                }
+                if c == '"' {
+                    quoted = !quoted;
+                } else {
+                    token.push(c);
+                }
+                chars.next();
+            }
+            if quoted {
+                return Err(String::from("a quote isn't closed"));
+            }
+            tokens.push(token);
+        }
+    }
+    Ok(tokens)
+}
+
+fn or(tokens: &[String], at: &mut usize) -> Result<Query, String> {
+    let mut query = and(tokens, at)?;
+    while tokens.get(*at).map(String::as_str) == Some("OR") {
+        *at += 1;
+        query = Query::Or(Box::new(query), Box::new(and(tokens, at)?));
+    }
+    Ok(query)
+}
+
+fn and(tokens: &[String], at: &mut usize) -> Result<Query, String> {
+    let mut query = not(tokens, at)?;
+    loop {
+        match tokens.get(*at).map(String::as_str) {
+            Some("AND") => *at += 1,
+            Some("OR" | ")") | None => return Ok(query),
+            Some(_) => {}
+        }
+        query = Query::And(Box::new(query), Box::new(not(tokens, at)?));
+    }
+}
+
+fn not(tokens: &[String], at: &mut usize) -> Result<Query, String> {
+    let Some(token) = tokens.get(*at) else {
+        return Err(String::from("a condition is missing"));
+    };
+    *at += 1;
+    match token.as_str() {
+        "NOT" => Ok(Query::Not(Box::new(not(tokens, at)?))),
+        "(" => {
+            let query = or(tokens, at)?;
+            if tokens.get(*at).map(String::as_str) != Some(")") {
+                return Err(String::from("a parenthesis isn't closed"));
+            }
+            *at += 1;
+            Ok(query)
+        }
+        ")" | "AND" | "OR" => Err(format!("unexpected `{token}`")),
+        _ => {
+            let (field, value) = match token.split_once(':') {
+                Some((field, value)) => (field.to_lowercase(), value.to_string()),
+                None => (String::new(), token.clone()),
+            };
+            if value.is_empty() {
+                return Err(format!("`{token}` has no value"));
+            }
+            Ok(Query::Condition(Condition { field, value }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn answered(content: &str) -> String {
+        let mut content = content.to_string();
+        for (range, _) in blocks(&content).into_iter().rev() {
+            answer(&mut content, range, "- [A](a.md)\n- [B](b.md)\n");
+        }
+        content
+    }
+
+    #[test]
+    fn answers_stay_in_block_quotes() {
+        let content = "> Pages:\n>\n> ```indexer-query\n> #rust\n> ```\n>\n> After.\n";
+        assert_eq!(
+            answered(content),
+            "> Pages:\n>\n> - [A](a.md)\n> - [B](b.md)\n>\n> After.\n"
+        );
+    }
+
+    #[test]
+    fn answers_stay_in_list_items() {
+        let content = "- Pages:\n\n  ```indexer-query\n  #rust\n  ```\n- Next\n";
+        assert_eq!(
+            answered(content),
+            "- Pages:\n\n  - [A](a.md)\n  - [B](b.md)\n\n- Next\n"
+        );
+    }
+
+    #[test]
+    fn answers_stay_in_the_item_they_open() {
+        let content = "1. ```indexer-query\n   #rust\n   ```\n";
+        assert_eq!(answered(content), "1. - [A](a.md)\n   - [B](b.md)\n");
+    }
+}
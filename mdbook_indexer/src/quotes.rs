@@ -0,0 +1,164 @@
+use crate::extract::{collapse_whitespace, parser};
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::ops::Range;
+
+/// Dashes opening the attribution of a quote, as in `— Alan Kay`.
+const DASHES: &[&str] = &["—", "–", "--", "~"];
+
+/// A callout of a chapter, like `> [!WARNING]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Callout {
+    /// Kind, in lowercase, like `warning`.
+    pub kind: String,
+    /// Path of the chapter.
+    pub page: String,
+    /// What follows the kind on its line, if anything.
+    pub title: String,
+    /// Plain text below the title.
+    pub text: String,
+}
+
+/// A blockquote of a chapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Quote {
+    /// Path of the chapter quoting it.
+    pub page: String,
+    /// Plain text of its paragraphs.
+    pub paragraphs: Vec<String>,
+    /// Who said it, from a last paragraph opening with a dash.
+    pub attribution: Option<String>,
+}
+
+/// The ranges of the blockquotes of `content` not nested in another, but
+/// for callouts like `> [!NOTE]`.
+pub(crate) fn blockquotes(content: &str) -> Vec<Range<usize>> {
+    top_level(content)
+        .into_iter()
+        .filter(|range| callout_kind(&content[range.clone()]).is_none())
+        .collect()
+}
+
+/// The callouts of `content`, blockquotes opening with their kind as in
+/// `> [!WARNING]`, with the kind in lowercase.
+pub(crate) fn callouts(content: &str) -> Vec<(String, Range<usize>)> {
+    top_level(content)
+        .into_iter()
+        .filter_map(|range| Some((callout_kind(&content[range.clone()])?, range)))
+        .collect()
+}
+
+/// The ranges of the blockquotes of `content` not nested in another.
+fn top_level(content: &str) -> Vec<Range<usize>> {
+    let mut quotes = Vec::new();
+    let mut depth = 0;
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::BlockQuote(_)) => {
+                if depth == 0 {
+                    quotes.push(range);
+                }
+                depth += 1;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => depth -= 1,
+            _ => {}
+        }
+    }
+    quotes
+}
+
+/// The kind of the callout `quote` is, if it opens like `> [!NOTE]`.
+fn callout_kind(quote: &str) -> Option<String> {
+    let text = quote.trim_start_matches(['>', ' ', '\t']);
+    let (kind, _) = text.strip_prefix("[!")?.split_once(']')?;
+    let is_kind = !kind.is_empty() && kind.chars().all(|c| c.is_alphanumeric() || c == '-');
+    is_kind.then(|| kind.to_lowercase())
+}
+
+/// The callout of the kind `kind` at `range` of `content`, the body of the
+/// chapter at `page`. Its first line may give it a title after the kind.
+pub(crate) fn callout(content: &str, page: &str, kind: String, range: Range<usize>) -> Callout {
+    let first_line = content[range.clone()].lines().next().unwrap_or_default();
+    let title = first_line
+        .split_once(']')
+        .map(|(_, title)| collapse_whitespace(title))
+        .unwrap_or_default();
+    let quote = quote(content, page, range, &[]);
+    let mut paragraphs = quote.paragraphs;
+    if let Some(first) = paragraphs.first_mut() {
+        if let Some((_, rest)) = first.split_once(']') {
+            let rest = rest.trim();
+            *first = rest
+                .strip_prefix(title.as_str())
+                .unwrap_or(rest)
+                .trim()
+                .to_string();
+        }
+    }
+    paragraphs.retain(|paragraph| !paragraph.is_empty());
+    Callout {
+        kind,
+        page: page.to_string(),
+        title,
+        text: paragraphs.join(" "),
+    }
+}
+
+/// The quote at `range` of `content`, the body of the chapter at `page`,
+/// leaving out the text at `left_out`, like the tags marking it.
+pub(crate) fn quote(
+    content: &str,
+    page: &str,
+    range: Range<usize>,
+    left_out: &[Range<usize>],
+) -> Quote {
+    let mut source = String::new();
+    let mut last = range.start;
+    for skip in left_out
+        .iter()
+        .filter(|skip| skip.start >= range.start && skip.end <= range.end)
+    {
+        source.push_str(&content[last..skip.start]);
+        last = skip.end;
+    }
+    source.push_str(&content[last..range.end]);
+
+    let mut paragraphs: Vec<String> = Vec::new();
+    let mut paragraph = String::new();
+    for event in parser(&source) {
+        match event {
+            Event::Text(text) | Event::Code(text) => paragraph.push_str(&text),
+            Event::SoftBreak | Event::HardBreak => paragraph.push('\n'),
+            Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item) => {
+                if !paragraph.trim().is_empty() {
+                    paragraphs.push(paragraph.trim().to_string());
+                }
+                paragraph.clear();
+            }
+            _ => {}
+        }
+    }
+    // The attribution may open the last line of the last paragraph
+    let mut attribution = None;
+    if let Some(last) = paragraphs.last_mut() {
+        let (before, line) = last.rsplit_once('\n').unwrap_or(("", last));
+        let name = DASHES
+            .iter()
+            .find_map(|dash| line.strip_prefix(dash))
+            .map(collapse_whitespace)
+            .filter(|name| !name.is_empty());
+        if name.is_some() {
+            attribution = name;
+            *last = before.to_string();
+        }
+    }
+    let paragraphs = paragraphs
+        .iter()
+        .map(|paragraph| collapse_whitespace(paragraph))
+        .filter(|paragraph| !paragraph.is_empty())
+        .collect();
+    Quote {
+        page: page.to_string(),
+        paragraphs,
+        attribution,
+    }
+}
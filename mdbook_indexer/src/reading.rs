@@ -0,0 +1,87 @@
+use crate::extract::{collapse_whitespace, parser};
+use crate::paths::{is_relative_link, resolve_link};
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::ops::Range;
+use std::path::Path;
+
+/// Something to read, taken from where a chapter tags it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Reading {
+    /// Path of the chapter tagging it.
+    pub page: String,
+    /// Title or description, in plain text. Empty for a chapter tagged as
+    /// a whole.
+    pub title: String,
+    /// Where to find it: a URL, or the path of a chapter for a relative
+    /// link.
+    pub link: Option<String>,
+}
+
+/// What the line of `content`, the body of the chapter at `page`, holding
+/// the tag at `at` stands for: the first link on it, or a URL written out,
+/// else its text. `tags` are the ranges of the tags to leave out.
+pub(crate) fn read(content: &str, page: &str, at: Range<usize>, tags: &[Range<usize>]) -> Reading {
+    let start = content[..at.start].rfind('\n').map_or(0, |i| i + 1);
+    let end = content[at.end..]
+        .find('\n')
+        .map_or(content.len(), |i| at.end + i);
+    let mut line = String::new();
+    let mut last = start;
+    for tag in tags
+        .iter()
+        .filter(|tag| tag.start >= start && tag.end <= end)
+    {
+        line.push_str(&content[last..tag.start]);
+        last = tag.end;
+    }
+    line.push_str(&content[last..end]);
+
+    let mut text = String::new();
+    // Destination and text of the first link
+    let mut link: Option<(String, String)> = None;
+    let mut in_link = false;
+    for event in parser(&line) {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. }) if link.is_none() => {
+                link = Some((dest_url.into_string(), String::new()));
+                in_link = true;
+            }
+            Event::End(TagEnd::Link) => in_link = false,
+            Event::Text(part) | Event::Code(part) => match &mut link {
+                Some((_, title)) if in_link => title.push_str(&part),
+                _ => text.push_str(&part),
+            },
+            Event::SoftBreak | Event::HardBreak => text.push(' '),
+            _ => {}
+        }
+    }
+    let (link, title) = match link {
+        Some((dest, title)) => (Some(dest), title),
+        None => {
+            let url = text
+                .split_whitespace()
+                .find(|word| word.starts_with("https://") || word.starts_with("http://"))
+                .map(|url| url.trim_end_matches(['.', ',', ';', ')']).to_string());
+            if let Some(url) = &url {
+                text = text.replacen(url.as_str(), "", 1);
+            }
+            (url, text)
+        }
+    };
+    let link = link.and_then(|dest| {
+        if is_relative_link(&dest) {
+            resolve_link(Path::new(page), &dest)
+        } else {
+            Some(dest)
+        }
+    });
+    let title = collapse_whitespace(&title);
+    let title = title
+        .trim_matches(|c: char| c.is_whitespace() || "-–—:,;".contains(c))
+        .to_string();
+    Reading {
+        page: page.to_string(),
+        title,
+        link,
+    }
+}
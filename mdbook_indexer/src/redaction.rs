@@ -0,0 +1,153 @@
+use crate::extract::parser;
+use pulldown_cmark::{Event, HeadingLevel, Tag, TagEnd};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// The marker opening a list item, with the spaces after it.
+static MARKER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:[-*+]|\d+[.)])\s+").expect("the marker pattern is valid"));
+
+/// The `<!-- indexer:... -->` comment `html` is, by what follows `indexer:`.
+fn directive(html: &str) -> Option<&str> {
+    let comment = html.trim().strip_prefix("<!--")?.strip_suffix("-->")?;
+    comment.trim().strip_prefix("indexer:")
+}
+
+/// What a private part of a chapter is, for the notice left in its place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Block,
+    Item,
+    Row,
+}
+
+/// `content` without its private parts: the paragraphs, list items and
+/// table rows holding `tag`, like `#private`, whole tables for a tag in
+/// their header, the sections of headings holding it, and what lies between
+/// `<!-- indexer:private -->` and `<!-- indexer:public -->`, or the end of
+/// the chapter when the region isn't closed. Each part leaves `notice` in
+/// its place, if given.
+pub(crate) fn redact(content: &str, tag: &str, notice: Option<&str>) -> String {
+    let tagged = Regex::new(&format!(
+        r"(?i)(?:^|[^\w/]){}(?:$|[^\w/-])",
+        regex::escape(tag)
+    ))
+    .expect("an escaped tag makes a valid pattern");
+
+    let mut parts: Vec<(Range<usize>, Part)> = Vec::new();
+    let mut blocks: Vec<(Range<usize>, Part, bool)> = Vec::new();
+    let mut headings: Vec<(usize, HeadingLevel, bool)> = Vec::new();
+    let mut table = 0..0;
+    let mut region: Option<usize> = None;
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Paragraph) => blocks.push((range, Part::Block, false)),
+            Event::Start(Tag::Item) => blocks.push((range, Part::Item, false)),
+            Event::Start(Tag::TableRow) => blocks.push((range, Part::Row, false)),
+            Event::Start(Tag::Table(_)) => table = range,
+            // The header takes the whole table along
+            Event::Start(Tag::TableHead) => blocks.push((table.clone(), Part::Block, false)),
+            Event::Start(Tag::Heading { level, .. }) => {
+                headings.push((range.start, level, false));
+                blocks.push((range, Part::Block, false));
+            }
+            Event::End(TagEnd::Paragraph | TagEnd::Item | TagEnd::TableRow | TagEnd::TableHead) => {
+                if let Some((range, part, true)) = blocks.pop() {
+                    parts.push((range, part));
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((_, _, true)) = blocks.pop() {
+                    if let Some(heading) = headings.last_mut() {
+                        heading.2 = true;
+                    }
+                }
+            }
+            Event::Text(text) if tagged.is_match(&text) => {
+                if let Some(block) = blocks.last_mut() {
+                    block.2 = true;
+                }
+            }
+            Event::Html(html) | Event::InlineHtml(html) => match directive(&html) {
+                Some("private") if region.is_none() => region = Some(range.start),
+                Some("public") => {
+                    if let Some(start) = region.take() {
+                        parts.push((start..range.end, Part::Block));
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+    if let Some(start) = region {
+        parts.push((start..content.len(), Part::Block));
+    }
+    // A private heading takes its section along, up to the next heading as high
+    for (i, &(start, level, tagged)) in headings.iter().enumerate() {
+        if tagged {
+            let end = headings[i + 1..]
+                .iter()
+                .find(|(_, other, _)| *other <= level)
+                .map_or(content.len(), |(next, _, _)| *next);
+            parts.push((start..end, Part::Block));
+        }
+    }
+
+    // Parts overlapping another go along with it
+    parts.sort_by_key(|(range, _)| (range.start, usize::MAX - range.end));
+    let mut redacted = String::with_capacity(content.len());
+    let mut last = 0;
+    for (mut range, part) in parts {
+        if range.start < last {
+            last = last.max(range.end);
+            continue;
+        }
+        if part == Part::Row && notice.is_none() {
+            // A row left as a blank line would end the table
+            range.end += usize::from(content[range.end..].starts_with('\n'));
+        }
+        redacted.push_str(&content[last..range.start]);
+        let removed = &content[range.clone()];
+        if let Some(notice) = notice {
+            match part {
+                Part::Block => redacted.push_str(&format!("*{notice}*")),
+                Part::Item => {
+                    let marker = MARKER.find(removed).map_or("- ", |marker| marker.as_str());
+                    redacted.push_str(&format!("{marker}*{notice}*"));
+                }
+                Part::Row => redacted.push_str(&format!("| *{notice}* |")),
+            }
+            // Keeping the blank lines after it keeps the next block apart
+            redacted.push_str(&removed[removed.trim_end().len()..]);
+        }
+        last = range.end;
+    }
+    redacted.push_str(&content[last..]);
+    redacted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacts_table_rows() {
+        let content = "| a |\n|---|\n| shown |\n| secret #private |\n| shown too |\n";
+        assert_eq!(
+            redact(content, "#private", None),
+            "| a |\n|---|\n| shown |\n| shown too |\n"
+        );
+        assert_eq!(
+            redact(content, "#private", Some("Private.")),
+            "| a |\n|---|\n| shown |\n| *Private.* |\n| shown too |\n"
+        );
+    }
+
+    #[test]
+    fn redacts_heading_sections() {
+        let content = "## Secret #private\n\nText.\n\n### Below\n\nMore.\n\n## Shown\n\nText.\n";
+        assert_eq!(redact(content, "#private", None), "## Shown\n\nText.\n");
+    }
+}
@@ -0,0 +1,195 @@
+use crate::extract::parser;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+
+/// Number of similar chapters found for each chapter when `related` doesn't
+/// say.
+pub(crate) const DEFAULT_LIMIT: usize = 5;
+
+/// Shortest word counted.
+const MIN_WORD_LEN: usize = 3;
+
+/// Terms kept for each chapter, the most weighted first. Chapters are only
+/// compared with those sharing one, which keeps big books from comparing
+/// every pair.
+const TERMS_PER_CHAPTER: usize = 50;
+
+/// Format of the cache, bumped whenever words are counted differently.
+const CACHE_VERSION: u32 = 2;
+
+/// Common English words, which say nothing about a chapter.
+const STOP_WORDS: &[&str] = &[
+    "about", "after", "all", "also", "and", "any", "are", "because", "been", "before", "being",
+    "but", "can", "could", "did", "does", "each", "for", "from", "had", "has", "have", "her",
+    "here", "him", "his", "how", "into", "its", "just", "more", "most", "not", "now", "only",
+    "other", "our", "out", "over", "she", "should", "some", "such", "than", "that", "the", "their",
+    "them", "then", "there", "these", "they", "this", "those", "through", "too", "under", "very",
+    "was", "were", "what", "when", "where", "which", "while", "who", "why", "will", "with",
+    "would", "you", "your",
+];
+
+/// The words of each chapter, by path, kept between builds so that only the
+/// chapters whose text changed are read again.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Cache {
+    version: u32,
+    chapters: BTreeMap<String, Counted>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Counted {
+    /// Hash of the text the words were counted in.
+    hash: u64,
+    words: BTreeMap<String, usize>,
+}
+
+impl Cache {
+    /// The cache saved as `json`, or an empty one if it can't be used.
+    pub fn parse(json: &str) -> Self {
+        serde_json::from_str(json)
+            .ok()
+            .filter(|cache: &Cache| cache.version == CACHE_VERSION)
+            .unwrap_or_default()
+    }
+
+    pub fn to_json(&self) -> String {
+        json!({ "version": CACHE_VERSION, "chapters": self.chapters }).to_string()
+    }
+
+    /// Counts the words of `text`, the body of `page`, unless they were
+    /// counted in the same text before.
+    fn update(&mut self, page: &str, text: &str) {
+        let mut hasher = DefaultHasher::new();
+        text.hash(&mut hasher);
+        let hash = hasher.finish();
+        if self
+            .chapters
+            .get(page)
+            .is_none_or(|counted| counted.hash != hash)
+        {
+            let words = count_words(text);
+            self.chapters
+                .insert(page.to_string(), Counted { hash, words });
+        }
+    }
+}
+
+/// The words of each chapter of `sources`, the bodies of the chapters by
+/// path, weighted by TF-IDF, the heaviest first. Words found in every
+/// chapter weigh nothing and are left out. Chapters dropped from `sources`
+/// are forgotten by `cache`.
+pub(crate) fn keywords(
+    sources: &HashMap<String, String>,
+    cache: &mut Cache,
+) -> BTreeMap<String, Vec<(String, f64)>> {
+    cache.chapters.retain(|page, _| sources.contains_key(page));
+    for (page, text) in sources {
+        cache.update(page, text);
+    }
+
+    let mut chapters_with: HashMap<&str, usize> = HashMap::new();
+    for counted in cache.chapters.values() {
+        for word in counted.words.keys() {
+            *chapters_with.entry(word).or_default() += 1;
+        }
+    }
+    let total = cache.chapters.len() as f64;
+    let mut keywords = BTreeMap::new();
+    for (page, counted) in &cache.chapters {
+        let mut weights: Vec<(String, f64)> = counted
+            .words
+            .iter()
+            .map(|(word, &count)| {
+                let idf = (total / chapters_with[word.as_str()] as f64).ln();
+                (word.clone(), (1.0 + (count as f64).ln()) * idf)
+            })
+            .filter(|&(_, weight)| weight > 0.0)
+            .collect();
+        weights.sort_by(|(a, a_weight), (b, b_weight)| {
+            b_weight.total_cmp(a_weight).then_with(|| a.cmp(b))
+        });
+        keywords.insert(page.clone(), weights);
+    }
+    keywords
+}
+
+/// For every chapter of `keywords`, the `limit` others closest to it in
+/// wording, with their cosine similarity between 0 and 1, the closest
+/// first.
+pub(crate) fn similar(
+    keywords: &BTreeMap<String, Vec<(String, f64)>>,
+    limit: usize,
+) -> HashMap<String, Vec<(String, f64)>> {
+    let pages: Vec<&String> = keywords.keys().collect();
+    let mut postings: BTreeMap<&str, Vec<(usize, f64)>> = BTreeMap::new();
+    for (i, weights) in keywords.values().enumerate() {
+        let weights = &weights[..weights.len().min(TERMS_PER_CHAPTER)];
+        let norm = weights
+            .iter()
+            .map(|(_, weight)| weight * weight)
+            .sum::<f64>()
+            .sqrt();
+        for (word, weight) in weights {
+            postings.entry(word).or_default().push((i, weight / norm));
+        }
+    }
+
+    let mut scores: HashMap<(usize, usize), f64> = HashMap::new();
+    for chapters in postings.values() {
+        for (n, &(a, a_weight)) in chapters.iter().enumerate() {
+            for &(b, b_weight) in &chapters[n + 1..] {
+                *scores.entry((a, b)).or_default() += a_weight * b_weight;
+            }
+        }
+    }
+    let mut closest: Vec<Vec<(usize, f64)>> = vec![Vec::new(); pages.len()];
+    for (&(a, b), &score) in &scores {
+        closest[a].push((b, score));
+        closest[b].push((a, score));
+    }
+
+    let mut similar = HashMap::new();
+    for (i, mut others) in closest.into_iter().enumerate() {
+        if others.is_empty() {
+            continue;
+        }
+        others.sort_by(|(a, a_score), (b, b_score)| b_score.total_cmp(a_score).then(a.cmp(b)));
+        let others = others
+            .into_iter()
+            .take(limit)
+            .map(|(other, score)| (pages[other].clone(), score.min(1.0)))
+            .collect();
+        similar.insert(pages[i].clone(), others);
+    }
+    similar
+}
+
+/// The lowercased words of the prose of `text`, leaving out code, HTML,
+/// numbers and stop words, with how often they appear.
+fn count_words(text: &str) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    let mut in_code = false;
+    for event in parser(text) {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::MetadataBlock(_)) => in_code = true,
+            Event::End(TagEnd::CodeBlock | TagEnd::MetadataBlock(_)) => in_code = false,
+            Event::Text(text) if !in_code => {
+                for word in text.split(|c: char| !c.is_alphanumeric()) {
+                    if word.chars().count() < MIN_WORD_LEN || word.chars().all(|c| c.is_numeric()) {
+                        continue;
+                    }
+                    let word = word.to_lowercase();
+                    if !STOP_WORDS.contains(&word.as_str()) {
+                        *counts.entry(word).or_default() += 1;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    counts
+}
@@ -0,0 +1,119 @@
+use crate::embeds::{rebase_links, section};
+use crate::extract::parser;
+use crate::frontmatter::FrontMatter;
+use crate::obsidian;
+use anyhow::Context;
+use mdbook::errors::Error;
+use pulldown_cmark::{Event, Tag};
+use regex::Regex;
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// `{{#snippet name}}`.
+static SNIPPET: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{#snippet\s+([^{}\s]+)\s*\}\}").expect("the snippet pattern is valid")
+});
+
+/// The snippets chapters include, by name.
+pub(crate) struct Snippets {
+    /// Body of each file of the snippets' directory, by name, like
+    /// `legal/disclaimer` for `legal/disclaimer.md`, with its path.
+    files: HashMap<String, (String, String)>,
+    /// Path and body of the chapter whose sections are snippets.
+    chapter: Option<(String, String)>,
+}
+
+impl Snippets {
+    /// The snippets of the files below `dir`, a path of the source directory
+    /// `src_dir`, and of the sections of `chapter`, given as its path and
+    /// content.
+    pub fn load(
+        src_dir: &Path,
+        dir: &str,
+        chapter: Option<(String, String)>,
+    ) -> Result<Self, Error> {
+        let mut files = HashMap::new();
+        let root = src_dir.join(dir);
+        if root.is_dir() {
+            for file in obsidian::files(&root)? {
+                let Some(name) = file.strip_suffix(".md") else {
+                    continue;
+                };
+                let path = format!("{}/{file}", dir.trim_end_matches('/'));
+                let content = fs::read_to_string(root.join(&file))
+                    .with_context(|| format!("couldn't read the snippet {path}"))?;
+                let body = FrontMatter::parse(&content).map_or(0, |front| front.len);
+                files.insert(name.to_string(), (path, content[body..].to_string()));
+            }
+        }
+        let chapter = chapter.map(|(path, content)| {
+            let body = FrontMatter::parse(&content).map_or(0, |front| front.len);
+            (path, content[body..].to_string())
+        });
+        Ok(Snippets { files, chapter })
+    }
+
+    /// Where the snippet `name` is written, and its text.
+    fn get(&self, name: &str) -> Option<(&str, &str)> {
+        if let Some((path, body)) = self.files.get(name) {
+            return Some((path, body));
+        }
+        let (path, content) = self.chapter.as_ref()?;
+        let section = section(content, name)?;
+        // The heading names the snippet and isn't part of it
+        let text = section.split_once('\n').map_or("", |(_, text)| text);
+        Some((path, text))
+    }
+
+    /// `content` of the chapter at `page` with every `{{#snippet name}}`
+    /// outside code replaced by that snippet, its relative links changed to
+    /// work from the chapter. Snippets are expanded recursively; one naming
+    /// no snippet, or including itself, is reported and left out, as its
+    /// `#snippet` would otherwise be read as a tag.
+    pub fn expand(&self, content: &str, page: &str) -> String {
+        self.expand_within(content, page, page, &mut Vec::new())
+    }
+
+    /// Expands the snippets of `content`, written at `from` and included
+    /// into the chapter at `page`, that aren't among `stack`.
+    fn expand_within(
+        &self,
+        content: &str,
+        from: &str,
+        page: &str,
+        stack: &mut Vec<String>,
+    ) -> String {
+        let code: Vec<Range<usize>> = parser(content)
+            .into_offset_iter()
+            .filter_map(|(event, range)| {
+                matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Code(_)).then_some(range)
+            })
+            .collect();
+
+        let mut expanded = String::with_capacity(content.len());
+        let mut last = 0;
+        for captures in SNIPPET.captures_iter(content) {
+            let whole = captures.get(0).expect("a match has a range");
+            if code.iter().any(|r| r.contains(&whole.start())) {
+                continue;
+            }
+            let name = &captures[1];
+            expanded.push_str(&content[last..whole.start()]);
+            last = whole.end();
+            let Some((path, text)) = self.get(name).filter(|_| !stack.iter().any(|n| n == name))
+            else {
+                eprintln!("Warning: the snippet `{name}` in {page} can't be expanded");
+                continue;
+            };
+            stack.push(name.to_string());
+            let nested = self.expand_within(text, path, page, stack);
+            stack.pop();
+            expanded.push_str(rebase_links(&nested, path, from).trim());
+        }
+        expanded.push_str(&content[last..]);
+        expanded
+    }
+}
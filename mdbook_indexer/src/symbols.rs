@@ -0,0 +1,41 @@
+use crate::extract::parser;
+use pulldown_cmark::Event;
+use regex::Regex;
+use std::collections::BTreeMap;
+
+/// What `symbol_pattern` matches by default: paths like `tokio::spawn` and
+/// calls like `spawn()` or `Vec::new()`.
+pub(crate) const DEFAULT_PATTERN: &str =
+    r"^(?:[A-Za-z_][A-Za-z0-9_]*::)+[A-Za-z_][A-Za-z0-9_]*(?:\(\))?$|^[A-Za-z_][A-Za-z0-9_]*\(\)$";
+
+/// The symbol a code span stands for, `code` matching `pattern`, without
+/// the parentheses of a call.
+fn symbol(code: &str, pattern: &Regex) -> Option<String> {
+    let code = code.trim();
+    pattern
+        .is_match(code)
+        .then(|| code.strip_suffix("()").unwrap_or(code).to_string())
+}
+
+/// The symbols the inline code spans of `chapters`, given by path and
+/// content in reading order, show, with the chapters showing each.
+pub(crate) fn collect<'a>(
+    chapters: impl Iterator<Item = (String, &'a str)>,
+    pattern: &Regex,
+) -> BTreeMap<String, Vec<String>> {
+    let mut symbols: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (page, content) in chapters {
+        for event in parser(content) {
+            let Event::Code(code) = event else {
+                continue;
+            };
+            if let Some(symbol) = symbol(&code, pattern) {
+                let pages = symbols.entry(symbol).or_default();
+                if !pages.contains(&page) {
+                    pages.push(page.clone());
+                }
+            }
+        }
+    }
+    symbols
+}
@@ -0,0 +1,178 @@
+use crate::extract::{collapse_whitespace, container_prefix, parser};
+use pulldown_cmark::{Event, Tag};
+use std::ops::Range;
+
+/// A table of the book, numbered in reading order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Table {
+    /// Path of the chapter.
+    pub page: String,
+    /// Number, from 1, also giving its anchor `table-1`.
+    pub number: usize,
+    /// Caption, as written after `Table:`.
+    pub caption: Option<String>,
+}
+
+/// A table of a chapter and the paragraph captioning it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Found {
+    pub range: Range<usize>,
+    /// Paragraph right before or after the table, preferably after, like
+    /// `Table: Supported formats`, or the anchor an earlier run put before
+    /// a table without a caption.
+    pub paragraph: Option<Range<usize>>,
+    pub caption: Option<String>,
+}
+
+/// The anchor of the table numbered `number`.
+pub(crate) fn anchor(number: usize) -> String {
+    format!("table-{number}")
+}
+
+/// The tables of `content`, in order. Captions open with `Table:`, or with
+/// `label` and a number as an earlier run wrote them.
+pub(crate) fn tables(content: &str, label: &str) -> Vec<Found> {
+    let mut tables = Vec::new();
+    let mut paragraphs = Vec::new();
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Table(_)) => tables.push(range),
+            Event::Start(Tag::Paragraph) => {
+                // Up to the end of its last line, keeping the line break
+                let end = range.start + content[range.clone()].trim_end().len();
+                paragraphs.push(range.start..end);
+            }
+            _ => {}
+        }
+    }
+    let mut taken: Vec<Range<usize>> = Vec::new();
+    let mut found = Vec::new();
+    for table in tables {
+        let after = paragraphs
+            .iter()
+            .find(|p| p.start >= table.end && is_gap(&content[table.end..p.start]));
+        let before = paragraphs
+            .iter()
+            .rev()
+            .find(|p| p.end <= table.start && is_gap(&content[p.end..table.start]));
+        let captioned = [after, before]
+            .into_iter()
+            .flatten()
+            .filter(|p| !taken.contains(p))
+            .find_map(|p| Some((p.clone(), caption(&content[p.clone()], label)?)));
+        let (paragraph, caption) = match captioned {
+            Some((p, caption)) => (Some(p), Some(caption)),
+            None => {
+                let earlier = before.filter(|p| is_anchor(&content[(*p).clone()]));
+                (earlier.cloned(), None)
+            }
+        };
+        if let Some(paragraph) = &paragraph {
+            taken.push(paragraph.clone());
+        }
+        found.push(Found {
+            range: table,
+            paragraph,
+            caption,
+        });
+    }
+    found
+}
+
+/// What goes before the table at `table` of `content` to give it `anchor`:
+/// a paragraph of its own, in the block quotes and list items holding the
+/// table.
+pub(crate) fn anchor_before(content: &str, table: &Range<usize>, anchor: &str) -> String {
+    let prefix = container_prefix(content, table.start);
+    format!("{anchor}\n{}\n{prefix}", prefix.trim_end())
+}
+
+/// Whether only blank lines, maybe holding the `>` of block quotes, are
+/// between two blocks.
+fn is_gap(text: &str) -> bool {
+    text.chars().all(|c| c.is_whitespace() || c == '>')
+}
+
+/// The caption `paragraph` gives, as in `Table: Supported formats`.
+fn caption(paragraph: &str, label: &str) -> Option<String> {
+    // Lines after the first open with the markers of their block quotes
+    let text: Vec<&str> = paragraph
+        .lines()
+        .map(|line| line.trim_start_matches(|c: char| c == '>' || c.is_whitespace()))
+        .collect();
+    let text = text.join(" ");
+    let text = skip_anchor(text.trim());
+    let rest = match text.strip_prefix("Table:") {
+        Some(rest) => rest,
+        None => {
+            let rest = text.strip_prefix(label)?.trim_start();
+            let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            rest[digits..].strip_prefix(':').filter(|_| digits > 0)?
+        }
+    };
+    let caption = collapse_whitespace(rest);
+    (!caption.is_empty()).then_some(caption)
+}
+
+/// Whether `paragraph` is only the anchor of a table.
+fn is_anchor(paragraph: &str) -> bool {
+    let text = paragraph.trim();
+    !text.is_empty() && skip_anchor(text).is_empty()
+}
+
+/// `text` without the anchor of a table opening it, like
+/// `<a id="table-3"></a>`.
+fn skip_anchor(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("<a id=\"table-") else {
+        return text;
+    };
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    match rest[digits..].strip_prefix("\"></a>") {
+        Some(rest) if digits > 0 => rest.trim_start(),
+        _ => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_captions_in_block_quotes() {
+        let content = "> | a |\n> |---|\n> | 1 |\n>\n> Table: Quoted\n";
+        let found = tables(content, "Table");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].caption.as_deref(), Some("Quoted"));
+    }
+
+    #[test]
+    fn anchors_stay_in_block_quotes() {
+        let content = "> Before.\n>\n> | a |\n> |---|\n> | 1 |\n";
+        let table = &tables(content, "Table")[0];
+        let at = table.range.start;
+        let anchor = anchor_before(content, &table.range, "<a id=\"table-1\"></a>");
+        let anchored = format!("{}{anchor}{}", &content[..at], &content[at..]);
+        assert_eq!(
+            anchored,
+            "> Before.\n>\n> <a id=\"table-1\"></a>\n>\n> | a |\n> |---|\n> | 1 |\n"
+        );
+        // A later run finds the anchor again
+        let found = tables(&anchored, "Table");
+        assert_eq!(found.len(), 1);
+        assert!(found[0].paragraph.is_some());
+    }
+
+    #[test]
+    fn anchors_stay_in_list_items() {
+        let content = "- Item\n\n  | a |\n  |---|\n  | 1 |\n";
+        let table = &tables(content, "Table")[0];
+        let at = table.range.start;
+        let anchor = anchor_before(content, &table.range, "<a id=\"table-1\"></a>");
+        let anchored = format!("{}{anchor}{}", &content[..at], &content[at..]);
+        assert_eq!(
+            anchored,
+            "- Item\n\n  <a id=\"table-1\"></a>\n\n  | a |\n  |---|\n  | 1 |\n"
+        );
+        assert!(tables(&anchored, "Table")[0].paragraph.is_some());
+    }
+}
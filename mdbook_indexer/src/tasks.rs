@@ -0,0 +1,69 @@
+use crate::extract::{collapse_whitespace, parser};
+use pulldown_cmark::{Event, Tag, TagEnd};
+
+/// An item of a task list, `- [ ]` or `- [x]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Task {
+    /// Plain text of the item, up to any list nested in it.
+    pub text: String,
+    pub done: bool,
+}
+
+/// A column of the board: the chapters and tasks tagged with a status.
+#[derive(Debug, Clone)]
+pub(crate) struct Column {
+    pub status: String,
+    /// Chapters carrying the tag outside their tasks, by path.
+    pub pages: Vec<String>,
+    /// Tasks carrying the tag, each with the path of its chapter.
+    pub tasks: Vec<(String, Task)>,
+}
+
+/// Something due on a date: a task, or a whole chapter.
+#[derive(Debug, Clone)]
+pub(crate) struct Due {
+    /// `YYYY-MM-DD`.
+    pub date: String,
+    /// Path of the chapter.
+    pub page: String,
+    /// The task, unless it is the chapter that is due.
+    pub task: Option<Task>,
+}
+
+/// The tasks of `content`, in order. Nested tasks follow the one they are
+/// nested in.
+pub(crate) fn tasks(content: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut current: Option<Task> = None;
+    let mut finish = |current: &mut Option<Task>| {
+        if let Some(mut task) = current.take() {
+            task.text = collapse_whitespace(&task.text);
+            tasks.push(task);
+        }
+    };
+    for event in parser(content) {
+        match event {
+            Event::TaskListMarker(done) => {
+                current = Some(Task {
+                    text: String::new(),
+                    done,
+                });
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some(task) = &mut current {
+                    task.text.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some(task) = &mut current {
+                    task.text.push(' ');
+                }
+            }
+            Event::Start(Tag::List(_) | Tag::Item)
+            | Event::End(TagEnd::Paragraph | TagEnd::Item) => finish(&mut current),
+            _ => {}
+        }
+    }
+    finish(&mut current);
+    tasks
+}
@@ -0,0 +1,41 @@
+use crate::config::EntityKind;
+use anyhow::Context;
+use handlebars::Handlebars;
+use mdbook::errors::Error;
+use serde_json::Value;
+use std::path::Path;
+
+/// Handlebars templates replacing the built-in layout of index pages, one
+/// per kind named after it, like `tags.hbs`.
+pub(crate) struct Templates {
+    registry: Handlebars<'static>,
+}
+
+impl Templates {
+    /// Registers the template of every kind found in `dir`. Kinds without
+    /// one keep the built-in layout.
+    pub fn load(dir: &Path, kinds: &[EntityKind]) -> Result<Self, Error> {
+        let mut registry = Handlebars::new();
+        // Index pages are Markdown, which renderers escape themselves
+        registry.register_escape_fn(handlebars::no_escape);
+        for kind in kinds {
+            let path = dir.join(format!("{}.hbs", kind.name));
+            if path.is_file() {
+                registry
+                    .register_template_file(&kind.name, &path)
+                    .with_context(|| format!("invalid template {}", path.display()))?;
+            }
+        }
+        Ok(Templates { registry })
+    }
+
+    /// The page rendered by the template of `kind` from `data`, or `None` if
+    /// the kind has no template.
+    pub fn render(&self, kind: &str, data: &Value) -> Option<Result<String, Error>> {
+        self.registry.has_template(kind).then(|| {
+            self.registry
+                .render(kind, data)
+                .with_context(|| format!("failed to render the `{kind}` template"))
+        })
+    }
+}
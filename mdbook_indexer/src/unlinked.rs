@@ -0,0 +1,239 @@
+use crate::config::EntityKind;
+use crate::emails;
+use crate::extract::parser;
+use crate::generate::ChapterInfo;
+use crate::index::Index;
+use crate::paths::{link_to, percent_decode, resolve_link};
+use pulldown_cmark::{Event, Tag, TagEnd};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::Path;
+
+/// Shortest text looked for, so that short titles don't match everywhere.
+const MIN_TERM_LEN: usize = 3;
+
+/// Text that should link somewhere when it appears in a chapter.
+#[derive(Debug, Clone)]
+pub(crate) struct Term {
+    pub text: String,
+    /// Page the text stands for, relative to the source directory.
+    pub page: String,
+    /// Id in `page`, empty for the top of the page.
+    pub anchor: String,
+}
+
+/// A term found as plain text in a chapter.
+#[derive(Debug, Clone)]
+pub(crate) struct Unlinked {
+    pub range: Range<usize>,
+    /// Index of the term in the list searched.
+    pub term: usize,
+}
+
+/// The chapter titles of the book, and for the `mentions` kind, the keys and
+/// profile names of its entries, with where they lead.
+pub(crate) fn terms(
+    chapters: &HashMap<String, ChapterInfo>,
+    kinds: &[EntityKind],
+    indexes: &[Index],
+) -> Vec<Term> {
+    let mut terms: Vec<Term> = chapters
+        .iter()
+        .filter(|(page, _)| !page.is_empty())
+        .map(|(page, chapter)| Term {
+            text: chapter.name.clone(),
+            page: page.clone(),
+            anchor: String::new(),
+        })
+        .collect();
+    for (kind, index) in kinds.iter().zip(indexes) {
+        if kind.name != "mentions" {
+            continue;
+        }
+        for entry in index.entries() {
+            let name = kind.profile(&entry.display).and_then(|p| p.name.clone());
+            for text in std::iter::once(entry.display.clone()).chain(name) {
+                terms.push(Term {
+                    text,
+                    page: entry.file.clone(),
+                    anchor: entry.anchor.clone(),
+                });
+            }
+        }
+    }
+    terms.retain(|term| term.text.chars().count() >= MIN_TERM_LEN);
+    // Longer terms win where they overlap, like `Alice Smith` over `Alice`
+    terms.sort_by(|a, b| {
+        (b.text.len().cmp(&a.text.len()))
+            .then_with(|| a.text.cmp(&b.text))
+            .then_with(|| a.page.cmp(&b.page))
+    });
+    terms
+}
+
+/// The first place each of `terms` appears, ignoring ASCII case, as a whole
+/// word of plain text in `content`, the body of the chapter at `page`. Code,
+/// HTML, headings, link texts, email addresses, plain or obfuscated, and
+/// prefixed tokens don't count, nor do terms for `page` itself or for a page
+/// it already links to.
+pub(crate) fn find(content: &str, page: &str, terms: &[Term], prefixes: &[char]) -> Vec<Unlinked> {
+    let (linked, texts) = plain_text(content, page);
+    let mut found = Finder::new(content, texts, prefixes);
+    for (i, term) in terms.iter().enumerate() {
+        let target = match term.anchor.as_str() {
+            "" => term.page.clone(),
+            anchor => format!("{}#{anchor}", term.page),
+        };
+        if term.page == page || linked.contains(&target) {
+            continue;
+        }
+        found.next(i, term);
+    }
+    found.into_found()
+}
+
+/// Every place `terms` appear in `content`, the body of the chapter at
+/// `page`, in the same way as with [`find`], but whatever `page` links to.
+pub(crate) fn find_all(
+    content: &str,
+    page: &str,
+    terms: &[Term],
+    prefixes: &[char],
+) -> Vec<Unlinked> {
+    let (_, texts) = plain_text(content, page);
+    let mut found = Finder::new(content, texts, prefixes);
+    for (i, term) in terms.iter().enumerate() {
+        while found.next(i, term) {}
+    }
+    found.into_found()
+}
+
+/// The pages `content`, the body of the chapter at `page`, links to, and the
+/// same with the id they link to if any.
+pub(crate) fn linked(content: &str, page: &str) -> HashSet<String> {
+    plain_text(content, page).0
+}
+
+/// The pages `content`, the body of the chapter at `page`, links to, and
+/// the same with the id they link to if any, along with the ranges of its
+/// plain text.
+fn plain_text(content: &str, page: &str) -> (HashSet<String>, Vec<Range<usize>>) {
+    let mut linked: HashSet<String> = HashSet::new();
+    let mut texts = Vec::new();
+    let mut skipped = 0;
+    for (event, range) in parser(content).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link { dest_url, .. }) => {
+                if let Some(target) = resolve_link(Path::new(page), &dest_url) {
+                    if let Some((_, anchor)) = dest_url.split_once('#') {
+                        linked.insert(format!("{target}#{}", percent_decode(anchor)));
+                    }
+                    linked.insert(target);
+                }
+                skipped += 1;
+            }
+            Event::Start(
+                Tag::Heading { .. } | Tag::Image { .. } | Tag::CodeBlock(_) | Tag::MetadataBlock(_),
+            ) => skipped += 1,
+            Event::End(
+                TagEnd::Link
+                | TagEnd::Heading(_)
+                | TagEnd::Image
+                | TagEnd::CodeBlock
+                | TagEnd::MetadataBlock(_),
+            ) => skipped -= 1,
+            Event::Text(_) if skipped == 0 => texts.push(range),
+            _ => {}
+        }
+    }
+    (linked, texts)
+}
+
+/// Looks for terms in the plain text of a chapter, longer terms first so
+/// that no two matches overlap.
+struct Finder<'a> {
+    content: &'a str,
+    lowercase: String,
+    texts: Vec<Range<usize>>,
+    /// Email addresses, which a link inside would break.
+    addresses: Vec<Range<usize>>,
+    prefixes: &'a [char],
+    found: Vec<Unlinked>,
+}
+
+impl<'a> Finder<'a> {
+    fn new(content: &'a str, texts: Vec<Range<usize>>, prefixes: &'a [char]) -> Self {
+        let addresses = emails::find(content, &[])
+            .into_iter()
+            .chain(emails::obfuscated(content))
+            .map(|(range, _)| range)
+            .collect();
+        Finder {
+            content,
+            lowercase: content.to_ascii_lowercase(),
+            texts,
+            addresses,
+            prefixes,
+            found: Vec::new(),
+        }
+    }
+
+    /// Notes the first place `term`, number `i`, appears that no match
+    /// covers yet, returning whether there was one.
+    fn next(&mut self, i: usize, term: &Term) -> bool {
+        let needle = term.text.to_ascii_lowercase();
+        let content = self.content;
+        let hit = self.texts.iter().find_map(|text| {
+            self.lowercase[text.clone()]
+                .match_indices(&needle)
+                .map(|(at, _)| text.start + at..text.start + at + needle.len())
+                .find(|range| {
+                    let before = content[..range.start].chars().next_back();
+                    let after = content[range.end..].chars().next();
+                    !before.is_some_and(|c| is_word(c) || self.prefixes.contains(&c))
+                        && !after.is_some_and(is_word)
+                        && !self.found.iter().any(|other| overlaps(&other.range, range))
+                        && !self
+                            .addresses
+                            .iter()
+                            .any(|address| overlaps(address, range))
+                })
+        });
+        match hit {
+            Some(range) => {
+                self.found.push(Unlinked { range, term: i });
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn into_found(mut self) -> Vec<Unlinked> {
+        self.found.sort_by_key(|unlinked| unlinked.range.start);
+        self.found
+    }
+}
+
+/// `content`, the body of the chapter at `page`, with each of `found` made a
+/// link to its term's page.
+pub(crate) fn link(content: &str, page: &str, found: &[Unlinked], terms: &[Term]) -> String {
+    let mut linked = String::with_capacity(content.len());
+    let mut last = 0;
+    for unlinked in found {
+        let term = &terms[unlinked.term];
+        let href = link_to(Path::new(page), Path::new(&term.page), &term.anchor);
+        linked.push_str(&content[last..unlinked.range.start]);
+        linked.push_str(&format!("[{}]({})", &content[unlinked.range.clone()], href));
+        last = unlinked.range.end;
+    }
+    linked.push_str(&content[last..]);
+    linked
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end
+}
@@ -0,0 +1,49 @@
+use crate::extract::parser;
+use pulldown_cmark::{Event, Tag};
+use regex::Regex;
+use std::ops::Range;
+use std::sync::LazyLock;
+
+/// `{{name}}` or `{{chapter.title}}`, but not mdBook's `{{#include ...}}`.
+static VARIABLE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\{\{\s*([A-Za-z_][\w.-]*)\s*\}\}").expect("the variable pattern is valid")
+});
+
+/// `content` with every variable outside code replaced by the value `value`
+/// gives it. Variables escaped as `\{{name}}`, which Markdown shows as
+/// written, and those without a value are left alone; the names of the
+/// latter are returned.
+pub(crate) fn expand(
+    content: &str,
+    value: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<String>) {
+    let code: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| {
+            matches!(event, Event::Start(Tag::CodeBlock(_)) | Event::Code(_)).then_some(range)
+        })
+        .collect();
+
+    let mut expanded = String::with_capacity(content.len());
+    let mut unknown = Vec::new();
+    let mut last = 0;
+    for captures in VARIABLE.captures_iter(content) {
+        let whole = captures.get(0).expect("a match has a range");
+        let escaped = content[..whole.start()].ends_with('\\');
+        if escaped || code.iter().any(|r| r.contains(&whole.start())) {
+            continue;
+        }
+        let name = &captures[1];
+        let Some(value) = value(name) else {
+            if !unknown.iter().any(|known| known == name) {
+                unknown.push(name.to_string());
+            }
+            continue;
+        };
+        expanded.push_str(&content[last..whole.start()]);
+        expanded.push_str(&value);
+        last = whole.end();
+    }
+    expanded.push_str(&content[last..]);
+    (expanded, unknown)
+}
@@ -0,0 +1,239 @@
+use crate::blocks;
+use crate::config::UnresolvedLinks;
+use crate::extract::parser;
+use crate::generate::{slug, ChapterInfo};
+use crate::paths::{link_to, relative_link};
+use crate::workspace::Sibling;
+use mdbook::utils::normalize_id;
+use pulldown_cmark::{Event, Tag};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::path::Path;
+
+/// A `[[Target]]`, `[[Target#Heading]]` or `[[Target|text]]` link.
+struct Wikilink {
+    range: Range<usize>,
+    /// Chapter name or path, empty for a heading of the same chapter.
+    target: String,
+    heading: Option<String>,
+    /// Text shown for the link: the alias, or what was written otherwise.
+    text: String,
+}
+
+/// A chapter generated for a wikilink target that doesn't exist yet.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Stub {
+    /// The target as first written.
+    pub title: String,
+    /// Chapters linking to the stub.
+    pub sources: Vec<String>,
+}
+
+/// The stubs of a book, by path, all in one directory.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Stubs {
+    pub dir: String,
+    pub pages: BTreeMap<String, Stub>,
+}
+
+impl Stubs {
+    /// Path of the stub for `target`, which is created if there is none yet.
+    pub fn page(&mut self, target: &str) -> String {
+        let file = format!("{}.md", slug(target));
+        let page = match self.dir.trim_matches('/') {
+            "" => file,
+            dir => format!("{dir}/{file}"),
+        };
+        self.pages.entry(page.clone()).or_insert_with(|| Stub {
+            title: target.to_string(),
+            sources: Vec::new(),
+        });
+        page
+    }
+}
+
+/// `content` of the chapter at `from` with every wikilink outside code and
+/// HTML turned into a Markdown link to the chapter it names. One naming a
+/// chapter of `siblings`, the other books of the workspace, like
+/// `[[runbooks:Deploy]]` or one the book lacks, links to where it is
+/// published. Wikilinks that name no chapter link to one of the `stubs`, or
+/// are reported and kept or reduced to their text.
+pub(crate) fn rewrite(
+    content: &str,
+    from: &str,
+    chapters: &HashMap<String, ChapterInfo>,
+    siblings: &[Sibling],
+    unresolved: UnresolvedLinks,
+    stubs: &mut Stubs,
+) -> String {
+    let mut rewritten = String::with_capacity(content.len());
+    let mut last = 0;
+    for link in find(content) {
+        let heading = link.heading.as_deref();
+        let qualified = link.target.split_once(':').and_then(|(book, target)| {
+            let sibling = siblings
+                .iter()
+                .find(|sibling| sibling.name == book.trim())?;
+            Some((sibling, target))
+        });
+        let elsewhere = match qualified {
+            Some((sibling, target)) => sibling.chapter(target, heading),
+            None if resolve(&link.target, chapters).is_none() => siblings
+                .iter()
+                .find_map(|sibling| sibling.chapter(&link.target, heading)),
+            None => None,
+        };
+        let replacement = if let Some(url) = elsewhere {
+            // The book's name only qualifies the target
+            let text = match qualified {
+                Some((sibling, _)) => link
+                    .text
+                    .strip_prefix(&format!("{}:", sibling.name))
+                    .unwrap_or(&link.text),
+                None => &link.text,
+            };
+            format!("[{}]({})", text.trim(), url)
+        } else if let Some((sibling, _)) = qualified {
+            eprintln!(
+                "Warning: the wikilink [[{}]] in {} names no chapter of `{}`",
+                link.target, from, sibling.name
+            );
+            match unresolved {
+                UnresolvedLinks::Keep => continue,
+                UnresolvedLinks::Text | UnresolvedLinks::Stub => link.text.clone(),
+            }
+        } else if link.target.is_empty() {
+            let anchor = link.heading.as_deref().map(anchor).unwrap_or_default();
+            format!("[{}](#{})", link.text, anchor)
+        } else if let Some(page) = resolve(&link.target, chapters) {
+            let anchor = link.heading.as_deref().map(anchor).unwrap_or_default();
+            let href = link_to(Path::new(from), Path::new(page), &anchor);
+            format!("[{}]({})", link.text, href)
+        } else if unresolved == UnresolvedLinks::Stub {
+            let page = stubs.page(&link.target);
+            let href = relative_link(Path::new(from), Path::new(&page));
+            format!("[{}]({})", link.text, href)
+        } else {
+            eprintln!(
+                "Warning: the wikilink [[{}]] in {} names no chapter",
+                link.target, from
+            );
+            match unresolved {
+                UnresolvedLinks::Keep => continue,
+                UnresolvedLinks::Text => link.text.clone(),
+                UnresolvedLinks::Stub => unreachable!("unresolved wikilinks link to stubs"),
+            }
+        };
+        rewritten.push_str(&content[last..link.range.start]);
+        rewritten.push_str(&replacement);
+        last = link.range.end;
+    }
+    rewritten.push_str(&content[last..]);
+    rewritten
+}
+
+/// The anchor of `heading` in a wikilink, or of the block with `^id`.
+pub(crate) fn anchor(heading: &str) -> String {
+    match heading.strip_prefix('^') {
+        Some(id) => blocks::anchor(id),
+        None => normalize_id(heading),
+    }
+}
+
+/// The chapter named `target`: by Zettelkasten ID, by path, with or without
+/// `.md`, then by name or alias, then by file name, ignoring case. The
+/// first in reading order wins.
+pub(crate) fn resolve<'a>(
+    target: &str,
+    chapters: &'a HashMap<String, ChapterInfo>,
+) -> Option<&'a str> {
+    let target = target.trim().to_lowercase();
+    let mut candidates: Vec<(&String, &ChapterInfo)> = chapters
+        .iter()
+        .filter(|(page, _)| !page.is_empty())
+        .collect();
+    candidates.sort_by_key(|(_, chapter)| chapter.position);
+
+    let stem = |page: &str| {
+        let name = page.rsplit('/').next().unwrap_or(page);
+        name.strip_suffix(".md").unwrap_or(name).to_lowercase()
+    };
+    let find = |matches: &dyn Fn(&str, &ChapterInfo) -> bool| {
+        candidates
+            .iter()
+            .find(|(page, chapter)| matches(page, chapter))
+            .map(|(page, _)| page.as_str())
+    };
+    find(&|_, chapter| {
+        chapter
+            .id
+            .as_ref()
+            .is_some_and(|id| id.to_lowercase() == target)
+    })
+    .or_else(|| {
+        find(&|page, _| {
+            let page = page.to_lowercase();
+            page == target || page.strip_suffix(".md") == Some(target.as_str())
+        })
+    })
+    .or_else(|| find(&|_, chapter| chapter.name.to_lowercase() == target))
+    .or_else(|| {
+        find(&|_, chapter| {
+            chapter
+                .aliases
+                .iter()
+                .any(|alias| alias.to_lowercase() == target)
+        })
+    })
+    .or_else(|| find(&|page, _| stem(page) == target))
+}
+
+/// Every wikilink in `content`, skipping code, HTML, escaped brackets and
+/// embeds like `![[image.png]]`.
+fn find(content: &str) -> Vec<Wikilink> {
+    let skipped: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_))
+            | Event::Code(_)
+            | Event::Html(_)
+            | Event::InlineHtml(_) => Some(range),
+            _ => None,
+        })
+        .collect();
+
+    let mut links = Vec::new();
+    let mut from = 0;
+    while let Some(found) = content[from..].find("[[") {
+        let start = from + found;
+        from = start + 2;
+        let before = content[..start].chars().next_back();
+        if matches!(before, Some('\\' | '!')) || skipped.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+        let Some(len) = content[from..].find("]]") else {
+            break;
+        };
+        let inner = &content[from..from + len];
+        if inner.trim().is_empty() || inner.contains(['[', ']', '\n']) {
+            continue;
+        }
+
+        let (target, alias) = match inner.split_once('|') {
+            Some((target, alias)) => (target, Some(alias.trim())),
+            None => (inner, None),
+        };
+        let (target, heading) = match target.split_once('#') {
+            Some((target, heading)) => (target, Some(heading.trim().to_string())),
+            None => (target, None),
+        };
+        links.push(Wikilink {
+            range: start..from + len + 2,
+            target: target.trim().to_string(),
+            heading,
+            text: alias.unwrap_or(inner.trim()).to_string(),
+        });
+        from += len + 2;
+    }
+    links
+}
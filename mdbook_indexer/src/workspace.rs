@@ -0,0 +1,191 @@
+use crate::config::EntityKind;
+use crate::generate::{html_link, ChapterInfo, Generator};
+use crate::index::Index;
+use crate::wikilinks;
+use anyhow::Context;
+use mdbook::errors::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// What a book exports for the other books of its workspace to link to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Export {
+    pub title: String,
+    /// Where the book is published.
+    pub url: String,
+    /// Chapters, in reading order.
+    pub chapters: Vec<ExportedChapter>,
+    /// Listed entries by kind name, in listing order.
+    pub entries: BTreeMap<String, Vec<ExportedEntry>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ExportedChapter {
+    pub path: String,
+    pub title: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ExportedEntry {
+    pub key: String,
+    /// Generated chapter listing the entry.
+    pub file: String,
+    /// Id of its heading in `file`, empty for the top.
+    pub anchor: String,
+}
+
+impl Export {
+    /// The export of a book titled `title`, published at `url`, once its
+    /// entries have their targets.
+    pub fn new(
+        title: &str,
+        url: &str,
+        generator: &Generator,
+        kinds: &[EntityKind],
+        indexes: &[Index],
+    ) -> Self {
+        let mut chapters: Vec<(&String, &ChapterInfo)> = generator
+            .chapters
+            .iter()
+            .filter(|(page, _)| !page.is_empty())
+            .collect();
+        chapters.sort_by_key(|(_, chapter)| chapter.position);
+        let chapters = chapters
+            .into_iter()
+            .map(|(page, chapter)| ExportedChapter {
+                path: page.clone(),
+                title: chapter.name.clone(),
+            })
+            .collect();
+        let entries = kinds
+            .iter()
+            .zip(indexes)
+            .map(|(kind, index)| {
+                let entries = index
+                    .entries()
+                    .filter(|entry| generator.is_listed(entry))
+                    .map(|entry| ExportedEntry {
+                        key: entry.display.clone(),
+                        file: entry.file.clone(),
+                        anchor: entry.anchor.clone(),
+                    })
+                    .collect();
+                (kind.name.clone(), entries)
+            })
+            .collect();
+        Export {
+            title: title.to_string(),
+            url: url.to_string(),
+            chapters,
+            entries,
+        }
+    }
+}
+
+/// Another book of the workspace, read from its export.
+#[derive(Debug, Clone)]
+pub(crate) struct Sibling {
+    /// Name the workspace gives it, as in `[[runbooks:Deploy]]`.
+    pub name: String,
+    pub title: String,
+    url: String,
+    chapters: HashMap<String, ChapterInfo>,
+    /// Where each entry is, by kind name and key in lowercase.
+    entries: HashMap<String, HashMap<String, String>>,
+}
+
+impl Sibling {
+    fn new(name: &str, export: Export) -> Self {
+        let url = format!("{}/", export.url.trim_end_matches('/'));
+        let chapters = export
+            .chapters
+            .into_iter()
+            .enumerate()
+            .map(|(position, chapter)| {
+                let info = ChapterInfo {
+                    name: chapter.title,
+                    parents: Vec::new(),
+                    position,
+                    part: None,
+                    modified: None,
+                    id: None,
+                    aliases: Vec::new(),
+                };
+                (chapter.path, info)
+            })
+            .collect();
+        let entries = export
+            .entries
+            .into_iter()
+            .map(|(kind, entries)| {
+                let entries = entries
+                    .into_iter()
+                    .map(|entry| {
+                        let link = format!("{url}{}", html_link(&entry.file));
+                        let link = match entry.anchor.as_str() {
+                            "" => link,
+                            anchor => format!("{link}#{anchor}"),
+                        };
+                        (entry.key.to_lowercase(), link)
+                    })
+                    .collect();
+                (kind, entries)
+            })
+            .collect();
+        Sibling {
+            name: name.to_string(),
+            title: export.title,
+            url,
+            chapters,
+            entries,
+        }
+    }
+
+    /// Where the chapter `target` names, at `heading` if given, is published,
+    /// resolving it as wikilinks are.
+    pub fn chapter(&self, target: &str, heading: Option<&str>) -> Option<String> {
+        let page = wikilinks::resolve(target, &self.chapters)?;
+        let link = format!("{}{}", self.url, html_link(page));
+        Some(match heading.map(wikilinks::anchor) {
+            Some(anchor) if !anchor.is_empty() => format!("{link}#{anchor}"),
+            _ => link,
+        })
+    }
+
+    /// Where the entry `key` of the kind `kind` is published, if the book
+    /// lists it.
+    pub fn entry(&self, kind: &str, key: &str) -> Option<&str> {
+        self.entries
+            .get(kind)?
+            .get(&key.to_lowercase())
+            .map(String::as_str)
+    }
+}
+
+/// The books of `workspace`, by name with the path of their export
+/// relative to `root`. Books without an export yet are left out with a
+/// warning, as they may be built after this one.
+pub(crate) fn load(
+    root: &Path,
+    workspace: &BTreeMap<String, String>,
+) -> Result<Vec<Sibling>, Error> {
+    let mut siblings = Vec::new();
+    for (name, file) in workspace {
+        let path = root.join(file);
+        if !path.exists() {
+            eprintln!(
+                "Warning: the workspace book `{name}` has no export at {} yet",
+                path.display()
+            );
+            continue;
+        }
+        let json = fs::read_to_string(&path)
+            .with_context(|| format!("couldn't read the export of `{name}`"))?;
+        let export: Export = serde_json::from_str(&json)
+            .with_context(|| format!("{} isn't the export of a book", path.display()))?;
+        siblings.push(Sibling::new(name, export));
+    }
+    Ok(siblings)
+}
@@ -0,0 +1,151 @@
+use crate::extract::{collapse_whitespace, parser};
+use crate::generate::{escape_markdown, ChapterInfo};
+use crate::paths::link_to;
+use crate::wikilinks;
+use mdbook::utils::unique_id_from_content;
+use pulldown_cmark::{Event, Tag, TagEnd};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::path::Path;
+
+/// Opens a cross-reference, as in `{#ref:installing}`.
+const OPENING: &str = "{#ref:";
+
+/// A heading of the book, as the registry of cross-references knows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Heading {
+    /// Path of the chapter.
+    pub page: String,
+    /// Id the HTML renderer gives it, or its `{#id}`.
+    pub id: String,
+    /// Plain text.
+    pub title: String,
+    pub level: usize,
+}
+
+/// The headings of `content`, the chapter at `page`, in order, with the
+/// ids the HTML renderer gives them.
+pub(crate) fn headings(content: &str, page: &str) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut counts = HashMap::new();
+    let mut current: Option<(Option<String>, usize, String)> = None;
+    for event in parser(content) {
+        match event {
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                current = Some((id.map(|id| id.into_string()), level as usize, String::new()));
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((_, _, title)) = &mut current {
+                    title.push_str(&text);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some((id, level, title)) = current.take() {
+                    let id = id.unwrap_or_else(|| unique_id_from_content(&title, &mut counts));
+                    headings.push(Heading {
+                        page: page.to_string(),
+                        id,
+                        title: collapse_whitespace(&title),
+                        level,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    headings
+}
+
+/// `content` of the chapter at `from` with every cross-reference outside
+/// code and HTML, `{#ref:id}` or `{#ref:chapter#id}`, turned into a link to
+/// the heading of `registry` with that id, shown by its title. A heading no
+/// chapter or more than one has is reported in the errors instead.
+pub(crate) fn rewrite(
+    content: &str,
+    from: &str,
+    registry: &[Heading],
+    chapters: &HashMap<String, ChapterInfo>,
+    errors: &mut Vec<String>,
+) -> String {
+    let mut rewritten = String::with_capacity(content.len());
+    let mut last = 0;
+    for (range, target) in find(content) {
+        let (page, id) = match target.rsplit_once('#') {
+            Some((chapter, id)) => match wikilinks::resolve(chapter, chapters) {
+                Some(page) => (Some(page), id),
+                None => {
+                    errors.push(format!("{from}: {OPENING}{target}}} names no chapter"));
+                    continue;
+                }
+            },
+            None => (None, target.as_str()),
+        };
+        let found: Vec<&Heading> = registry
+            .iter()
+            .filter(|heading| heading.id == id)
+            .filter(|heading| page.is_none_or(|page| heading.page == page))
+            .collect();
+        let heading = match found.as_slice() {
+            [heading] => heading,
+            [] => {
+                errors.push(format!("{from}: {OPENING}{target}}} names no heading"));
+                continue;
+            }
+            several => {
+                let pages: HashSet<&str> = several.iter().map(|h| h.page.as_str()).collect();
+                let mut pages: Vec<&str> = pages.into_iter().collect();
+                pages.sort();
+                errors.push(format!(
+                    "{from}: {OPENING}{target}}} could be any of {}; \
+                     name the chapter as in {OPENING}{}#{id}}}",
+                    pages.join(", "),
+                    pages[0]
+                ));
+                continue;
+            }
+        };
+        let href = link_to(Path::new(from), Path::new(&heading.page), &heading.id);
+        rewritten.push_str(&content[last..range.start]);
+        rewritten.push_str(&format!("[{}]({href})", escape_markdown(&heading.title)));
+        last = range.end;
+    }
+    rewritten.push_str(&content[last..]);
+    rewritten
+}
+
+/// Every cross-reference in `content` and what it names, skipping code,
+/// HTML and escaped braces.
+fn find(content: &str) -> Vec<(Range<usize>, String)> {
+    let skipped: Vec<Range<usize>> = parser(content)
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Start(Tag::CodeBlock(_))
+            | Event::Code(_)
+            | Event::Html(_)
+            | Event::InlineHtml(_) => Some(range),
+            _ => None,
+        })
+        .collect();
+
+    let mut refs = Vec::new();
+    let mut from = 0;
+    while let Some(found) = content[from..].find(OPENING) {
+        let start = from + found;
+        from = start + OPENING.len();
+        let escaped = content[..start].ends_with('\\');
+        if escaped || skipped.iter().any(|r| r.contains(&start)) {
+            continue;
+        }
+        let Some(len) = content[from..].find('}') else {
+            break;
+        };
+        let target = content[from..from + len].trim();
+        if target.is_empty() || target.contains(['{', '\n']) {
+            continue;
+        }
+        refs.push((start..from + len + 1, target.to_string()));
+        from += len + 1;
+    }
+    refs
+}
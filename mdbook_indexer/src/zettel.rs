@@ -0,0 +1,20 @@
+use crate::frontmatter::FrontMatter;
+use regex::Regex;
+
+/// What `zettel_pattern` matches by default: timestamps down to the minute
+/// or second, like `202501151230`.
+pub(crate) const DEFAULT_PATTERN: &str = r"^\d{12}(?:\d{2})?$";
+
+/// The ID of the note at `page`: its `id` in the front matter of `content`,
+/// or else the first word of its file name, as in `202501151230 Habits.md`,
+/// if `pattern` matches it.
+pub(crate) fn id(page: &str, content: &str, pattern: &Regex) -> Option<String> {
+    let declared = FrontMatter::parse(content).and_then(|front| front.text("id"));
+    let stem = page.rsplit('/').next().unwrap_or(page);
+    let stem = stem.strip_suffix(".md").unwrap_or(stem);
+    let named = stem.split([' ', '-', '_']).next().map(str::to_string);
+    declared
+        .into_iter()
+        .chain(named)
+        .find(|id| pattern.is_match(id))
+}